@@ -7,7 +7,7 @@ use reversi::board::Indexer;
 use reversi::Move;
 use reversi::PlayerColor;
 use reversi::Position;
-use std::rc::Rc;
+use std::sync::Arc;
 
 fn action_table() -> [Move; 10] {
     [
@@ -28,7 +28,7 @@ fn index_board(c: &mut Criterion) {
     c.bench_function("Index Board", |b| {
         b.iter(|| {
             let actions = action_table();
-            let indexer = Rc::new(Indexer::new());
+            let indexer = Arc::new(Indexer::new());
             for _ in 0..1000 {
                 let mut board = IndexBoard::new_initial(indexer.clone());
                 for action in &actions {
@@ -67,5 +67,29 @@ fn bit_board(c: &mut Criterion) {
     });
 }
 
-criterion_group!(benches, index_board, array_board, bit_board);
+fn bit_board_make_move(c: &mut Criterion) {
+    c.bench_function("Bit Board (make/undo)", |b| {
+        b.iter(|| {
+            let actions = action_table();
+            for _ in 0..1000 {
+                let mut board = BitBoard::new_initial();
+                let mut undos = Vec::with_capacity(actions.len());
+                for action in &actions {
+                    undos.push(board.make_move(action).unwrap());
+                }
+                while let Some(undo) = undos.pop() {
+                    board.undo_move(&undo);
+                }
+            }
+        })
+    });
+}
+
+criterion_group!(
+    benches,
+    index_board,
+    array_board,
+    bit_board,
+    bit_board_make_move
+);
 criterion_main!(benches);
@@ -1,7 +1,8 @@
 use criterion::{criterion_group, criterion_main, Criterion};
 use reversi::ai::{
-    NegaAlpha, NegaAlphaNode, NegaMax, NegaMaxNode, Node, SimpleNegaAlphaEvaluationFunction,
-    SimpleNegaMaxEvaluationFunction,
+    LazySmp, MoveOrdering, Mtdf, NegaAlpha, NegaAlphaNode, NegaMax, NegaMaxNode, NegaScout, Node,
+    ReplacementPolicy, SearchLimits, SharedTranspositionTable, SimpleNegaAlphaEvaluationFunction,
+    SimpleNegaMaxEvaluationFunction, SimpleNegaScoutEvaluationFunction, TranspositionTable,
 };
 use reversi::board::*;
 use reversi::*;
@@ -38,5 +39,256 @@ fn nega_alpha(c: &mut Criterion) {
     });
 }
 
-criterion_group!(benches, nega_max, nega_alpha);
+fn nega_alpha_board(c: &mut Criterion) {
+    c.bench_function("NegaAlpha (make/undo)", |b| {
+        b.iter(|| {
+            let mut nega_alpha = NegaAlpha::new(SimpleNegaAlphaEvaluationFunction::new());
+            let board = BitBoard::new_initial();
+            let _result = nega_alpha.search_board(&board, PlayerColor::Black, 7);
+        })
+    });
+}
+
+fn nega_alpha_transposition_table(c: &mut Criterion) {
+    c.bench_function("NegaAlpha (transposition table)", |b| {
+        b.iter(|| {
+            let table = TranspositionTable::new(16, ReplacementPolicy::DepthPreferred);
+            let mut nega_alpha = NegaAlpha::with_transposition_table(
+                SimpleNegaAlphaEvaluationFunction::new(),
+                table,
+            );
+            let board = BitBoard::new_initial();
+            let _result = nega_alpha.search_board(&board, PlayerColor::Black, 7);
+        })
+    });
+}
+
+/// 同じ深さでNegaAlphaとNegaScoutを比べる
+fn nega_scout_vs_nega_alpha(c: &mut Criterion) {
+    const DEPTH: usize = 7;
+
+    // ノード数は実行ごとに変わらないので最初に1回だけ表示する
+    let board = BitBoard::new_initial();
+    let alpha_result = NegaAlpha::new(SimpleNegaAlphaEvaluationFunction::new()).search_board(
+        &board,
+        PlayerColor::Black,
+        DEPTH,
+    );
+    let scout_result = NegaScout::new(SimpleNegaScoutEvaluationFunction::new()).search_board(
+        &board,
+        PlayerColor::Black,
+        DEPTH,
+    );
+    println!(
+        "depth {}: NegaAlpha {} nodes, NegaScout {} nodes",
+        DEPTH, alpha_result.nodes, scout_result.nodes
+    );
+
+    let mut group = c.benchmark_group("NegaScout vs NegaAlpha");
+    group.bench_function("NegaAlpha", |b| {
+        b.iter(|| {
+            let mut nega_alpha = NegaAlpha::new(SimpleNegaAlphaEvaluationFunction::new());
+            let board = BitBoard::new_initial();
+            nega_alpha.search_board(&board, PlayerColor::Black, DEPTH)
+        })
+    });
+    group.bench_function("NegaScout", |b| {
+        b.iter(|| {
+            let mut nega_scout = NegaScout::new(SimpleNegaScoutEvaluationFunction::new());
+            let board = BitBoard::new_initial();
+            nega_scout.search_board(&board, PlayerColor::Black, DEPTH)
+        })
+    });
+    group.finish();
+}
+
+/// 初期局面から決まった手順で進めた中盤の局面
+fn midgame_positions() -> Vec<(BitBoard, PlayerColor)> {
+    let mut positions = Vec::new();
+    for seed in 0..6 {
+        let mut board = BitBoard::new_initial();
+        for ply in 0..(10 + seed * 2) {
+            let color = board.turn();
+            let moves = board.get_movable_positions(&color);
+            let m = if moves.is_empty() {
+                Move::new_pass(color)
+            } else {
+                Move::new_position(color, moves[(seed * 7 + ply * 3) % moves.len()])
+            };
+            board = board.apply_move(&m).unwrap();
+        }
+        let color = board.turn();
+        positions.push((board, color));
+    }
+    positions
+}
+
+/// MTD(f)とNegaScoutを同じ深さで比べる
+///
+/// 初期局面だけでは偏るので、中盤の局面もまとめて探索する。
+fn mtdf_vs_nega_scout(c: &mut Criterion) {
+    const DEPTH: usize = 7;
+
+    let new_mtdf = || {
+        let table = TranspositionTable::new(16, ReplacementPolicy::DepthPreferred);
+        Mtdf::new(NegaAlpha::with_transposition_table(
+            SimpleNegaAlphaEvaluationFunction::new(),
+            table,
+        ))
+    };
+
+    let mut positions = vec![(BitBoard::new_initial(), PlayerColor::Black)];
+    positions.extend(midgame_positions());
+    for (i, (board, color)) in positions.iter().enumerate() {
+        let mut mtdf = new_mtdf();
+        let mtdf_result = mtdf.search(board, *color, DEPTH, 0);
+        let scout_result = NegaScout::new(SimpleNegaScoutEvaluationFunction::new())
+            .search_board(board, *color, DEPTH);
+        println!(
+            "position {} ({} empties), depth {}: MTD(f) {} nodes ({} passes), NegaScout {} nodes",
+            i,
+            board.empty_count(),
+            DEPTH,
+            mtdf_result.nodes,
+            mtdf.passes(),
+            scout_result.nodes
+        );
+    }
+
+    let mut group = c.benchmark_group("MTD(f) vs NegaScout");
+    group.bench_function("MTD(f)", |b| {
+        b.iter(|| {
+            for (board, color) in positions.iter() {
+                new_mtdf().search(board, *color, DEPTH, 0);
+            }
+        })
+    });
+    group.bench_function("NegaScout", |b| {
+        b.iter(|| {
+            for (board, color) in positions.iter() {
+                let mut nega_scout = NegaScout::new(SimpleNegaScoutEvaluationFunction::new());
+                nega_scout.search_board(board, *color, DEPTH);
+            }
+        })
+    });
+    group.finish();
+}
+
+/// 手の並べ替えの情報ごとにNegaAlphaのノード数を比べる
+fn move_ordering(c: &mut Criterion) {
+    const DEPTH: usize = 7;
+
+    let orderings = [
+        ("none", MoveOrdering::none()),
+        (
+            "mobility",
+            MoveOrdering {
+                mobility: true,
+                ..MoveOrdering::none()
+            },
+        ),
+        (
+            "square prior",
+            MoveOrdering {
+                square_prior: true,
+                ..MoveOrdering::none()
+            },
+        ),
+        (
+            "killer + history",
+            MoveOrdering {
+                killer: true,
+                history: true,
+                ..MoveOrdering::none()
+            },
+        ),
+        (
+            "shallow search",
+            MoveOrdering {
+                shallow_search_depth: 2,
+                ..MoveOrdering::none()
+            },
+        ),
+        ("all", MoveOrdering::all()),
+    ];
+
+    for (name, ordering) in orderings.iter() {
+        let mut nega_alpha =
+            NegaAlpha::new(SimpleNegaAlphaEvaluationFunction::new()).with_move_ordering(*ordering);
+        let board = BitBoard::new_initial();
+        let result = nega_alpha.search_board(&board, PlayerColor::Black, DEPTH);
+        println!("depth {} ({}): {} nodes", DEPTH, name, result.nodes);
+    }
+
+    let mut group = c.benchmark_group("Move ordering");
+    for (name, ordering) in orderings.iter() {
+        group.bench_function(*name, |b| {
+            b.iter(|| {
+                let mut nega_alpha = NegaAlpha::new(SimpleNegaAlphaEvaluationFunction::new())
+                    .with_move_ordering(*ordering);
+                let board = BitBoard::new_initial();
+                nega_alpha.search_board(&board, PlayerColor::Black, DEPTH)
+            })
+        });
+    }
+    group.finish();
+}
+
+fn lazy_smp(c: &mut Criterion) {
+    const DEPTH: usize = 11;
+
+    let max_threads = std::thread::available_parallelism().map_or(1, |n| n.get());
+    let mut threads = vec![1];
+    while threads.last().unwrap() * 2 <= max_threads.max(2) {
+        threads.push(threads.last().unwrap() * 2);
+    }
+    let search = |threads: usize| {
+        let table = SharedTranspositionTable::new(16, ReplacementPolicy::DepthPreferred);
+        let mut lazy_smp =
+            LazySmp::new(SimpleNegaAlphaEvaluationFunction::new(), table).with_threads(threads);
+        let board = BitBoard::new_initial();
+        lazy_smp.search(&board, PlayerColor::Black, &SearchLimits::depth(DEPTH))
+    };
+
+    // 1スレッドと比べた速度向上率と、探索ノードの増加率(探索オーバーヘッド)
+    // 時間は3回のうち最短のもの
+    let measure = |threads: usize| {
+        (0..3)
+            .map(|_| search(threads))
+            .min_by_key(|result| result.elapsed)
+            .unwrap()
+    };
+    let single = measure(1);
+    for &n in threads.iter() {
+        let result = measure(n);
+        println!(
+            "depth {} ({} threads): {} nodes, {:?}, speedup {:.2}, overhead {:.1}%",
+            DEPTH,
+            n,
+            result.nodes,
+            result.elapsed,
+            single.elapsed.as_secs_f64() / result.elapsed.as_secs_f64(),
+            (result.nodes as f64 / single.nodes as f64 - 1.0) * 100.0
+        );
+    }
+
+    let mut group = c.benchmark_group("Lazy SMP");
+    group.sample_size(10);
+    for &n in threads.iter() {
+        group.bench_function(format!("{} threads", n), |b| b.iter(|| search(n)));
+    }
+    group.finish();
+}
+
+criterion_group!(
+    benches,
+    nega_max,
+    nega_alpha,
+    nega_alpha_board,
+    nega_alpha_transposition_table,
+    nega_scout_vs_nega_alpha,
+    mtdf_vs_nega_scout,
+    move_ordering,
+    lazy_smp
+);
 criterion_main!(benches);
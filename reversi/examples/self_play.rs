@@ -0,0 +1,52 @@
+use reversi::ai::{
+    AlphaZeroEvaluator, MctsLimits, PuctConfig, PuctEngine, SelfPlayConfig, SelfPlayPool,
+    SimpleEvaluator,
+};
+
+/// PUCT探索で自己対局して、学習データを書き出す
+///
+/// `cargo run --release --example self_play -- <出力先> [対局数] [ネットワークの重みファイル]`
+///
+/// 重みファイルを省略すると[`SimpleEvaluator`]を使う。
+/// 中断しても同じ引数で実行すれば続きから再開する。
+fn main() -> std::io::Result<()> {
+    let args = std::env::args().collect::<Vec<_>>();
+    if args.len() < 2 {
+        eprintln!("usage: {} <output directory> [games] [network]", args[0]);
+        std::process::exit(1);
+    }
+
+    let mut config = SelfPlayConfig {
+        random_moves: 4,
+        augment: true,
+        ..Default::default()
+    };
+    if let Some(games) = args.get(2) {
+        config.games = games.parse().expect("invalid games");
+    }
+    let limits = MctsLimits::iterations(200);
+    let report = |stats: &reversi::ai::SelfPlayStats| {
+        if stats.games % 10 == 0 {
+            println!("{}", stats);
+        }
+    };
+
+    let stats = match args.get(3) {
+        Some(network) => {
+            let evaluator = AlphaZeroEvaluator::load(network)?;
+            SelfPlayPool::new(|_| {
+                PuctEngine::new(evaluator.clone(), limits, PuctConfig::self_play())
+            })
+            .run(&config, &args[1], report)?
+        }
+        None => SelfPlayPool::new(|_| {
+            PuctEngine::new(SimpleEvaluator::new(), limits, PuctConfig::self_play())
+        })
+        .run(&config, &args[1], report)?,
+    };
+    if stats.skipped > 0 {
+        println!("skipped {} finished games", stats.skipped);
+    }
+    println!("{}", stats);
+    Ok(())
+}
@@ -0,0 +1,32 @@
+use reversi::ai::{load_transcripts, PatternTrainer, TrainerConfig};
+
+/// 棋譜ファイルからパターン評価関数の重みを学習して保存する
+///
+/// `cargo run --release --example train_pattern -- <棋譜ファイル> <重みファイル> [エポック数]`
+fn main() -> std::io::Result<()> {
+    let args = std::env::args().collect::<Vec<_>>();
+    if args.len() < 3 {
+        eprintln!("usage: {} <transcripts> <weights> [epochs]", args[0]);
+        std::process::exit(1);
+    }
+
+    let samples = load_transcripts(&args[1])?;
+    println!("{} positions", samples.len());
+
+    let mut config = TrainerConfig::default();
+    if let Some(epochs) = args.get(3) {
+        config.epochs = epochs.parse().expect("invalid epochs");
+    }
+    let mut trainer = PatternTrainer::new(config);
+    for error in trainer.train(&samples) {
+        match error.validation_error {
+            Some(validation) => println!(
+                "epoch {}: train {:.3}, validation {:.3}",
+                error.epoch, error.train_error, validation
+            ),
+            None => println!("epoch {}: train {:.3}", error.epoch, error.train_error),
+        }
+    }
+
+    trainer.weights().save(&args[2])
+}
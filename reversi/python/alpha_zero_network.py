@@ -1,3 +1,7 @@
+import glob
+import os
+import struct
+
 import numpy as np
 import tensorflow as tf
 import keras.layers as kl
@@ -171,6 +175,180 @@ class ResBlock(tf.keras.layers.Layer):
         return x
 
 
+#: weight file read by `reversi::ai::AlphaZeroNetwork`
+WEIGHT_FILE_MAGIC = b"RVNN"
+REFERENCE_FILE_MAGIC = b"RVNR"
+NETWORK_FILE_VERSION = 1
+
+#: self-play data written by `reversi::ai::run_self_play`
+SELF_PLAY_FILE_MAGIC = b"RVSP"
+SELF_PLAY_FILE_VERSION = 1
+
+
+def _write_floats(f, values):
+    f.write(np.asarray(values, dtype="<f4").tobytes())
+
+
+def _conv_and_bn(conv, bn):
+    """ Kernel and batch-norm weights, with the conv bias folded into the moving mean
+    """
+    weights = conv.get_weights()
+    gamma, beta, mean, variance = bn.get_weights()
+    if len(weights) == 2:
+        mean = mean - weights[1]
+    return weights[0], [gamma, beta, mean, variance]
+
+
+def export_weights(model, path):
+    """ Write AlphaZeroResNet weights for the pure-Rust inference (`ai::AlphaZeroNetwork`)
+
+        Header: magic, version, input channels, filters, residual blocks,
+        action space (u32) and batch-norm epsilon (f32), all little endian.
+        Then each layer in call order as little endian f32:
+        conv kernels (HWIO), batch norms (gamma, beta, mean, variance),
+        dense kernels (in, out) followed by biases.
+        The model must have been called once so that the weights exist.
+    """
+    input_channels = model.conv1.get_weights()[0].shape[2]
+    with open(path, "wb") as f:
+        f.write(WEIGHT_FILE_MAGIC)
+        f.write(struct.pack("<5I", NETWORK_FILE_VERSION, input_channels,
+                            model.filters, model.n_blocks, model.action_space))
+        f.write(struct.pack("<f", model.bn1.epsilon))
+
+        layers = [(model.conv1, model.bn1)]
+        for n in range(model.n_blocks):
+            block = getattr(model, f"resblock{n}")
+            layers += [(block.conv1, block.bn1), (block.conv2, block.bn2)]
+        for conv, bn in layers:
+            kernel, bn_weights = _conv_and_bn(conv, bn)
+            _write_floats(f, kernel)
+            for w in bn_weights:
+                _write_floats(f, w)
+
+        for conv, bn, dense in [(model.conv_p, model.bn_p, model.logits),
+                                (model.conv_v, model.bn_v, model.value)]:
+            kernel, bn_weights = _conv_and_bn(conv, bn)
+            _write_floats(f, kernel)
+            for w in bn_weights:
+                _write_floats(f, w)
+            for w in dense.get_weights():
+                _write_floats(f, w)
+
+
+def export_reference(model, inputs, path):
+    """ Write inputs (N, 8, 8, C) and the model outputs for the Rust parity test
+    """
+    inputs = np.asarray(inputs, dtype=np.float32)
+    policy, value = model(inputs, training=False)
+    with open(path, "wb") as f:
+        f.write(REFERENCE_FILE_MAGIC)
+        f.write(struct.pack("<3I", NETWORK_FILE_VERSION, inputs.shape[0], inputs.shape[3]))
+        _write_floats(f, inputs)
+        _write_floats(f, policy.numpy())
+        _write_floats(f, value.numpy())
+
+
+def export_saved_model(model, path):
+    """ Save the model as a SavedModel for `ai::TensorflowEvaluator` (Rust)
+
+        The `serving_default` signature takes `board` (N, 8, 8, C)
+        and returns `policy` (N, 64) and `value` (N, 1).
+    """
+    input_channels = model.conv1.get_weights()[0].shape[2]
+
+    @tf.function(input_signature=[
+        tf.TensorSpec([None, 8, 8, input_channels], tf.float32, name="board")])
+    def serve(board):
+        policy, value = model(board, training=False)
+        return {"policy": policy, "value": value}
+
+    tf.saved_model.save(model, path, signatures={"serving_default": serve})
+
+
+def load_self_play(path):
+    """ Read one self-play chunk written by `ai::SelfPlayChunk` (Rust)
+
+        Returns board planes (N, 8, 8, 3) as float32 (own discs, opponent discs, legal moves),
+        side to move (N,) (0 black, 1 white), policies (N, 64) and outcomes (N, 1)
+        from the side to move.
+    """
+    with open(path, "rb") as f:
+        data = f.read()
+    if data[:4] != SELF_PLAY_FILE_MAGIC:
+        raise ValueError(f"not a self-play data file: {path}")
+    version, count = struct.unpack_from("<2I", data, 4)
+    if version != SELF_PLAY_FILE_VERSION:
+        raise ValueError(f"unsupported self-play data file version: {version}")
+
+    offset = 12
+    planes = np.frombuffer(data, np.uint8, count * 64 * 3, offset)
+    offset += planes.size
+    colors = np.frombuffer(data, np.uint8, count, offset)
+    offset += colors.size
+    policies = np.frombuffer(data, "<f4", count * 64, offset)
+    offset += policies.nbytes
+    outcomes = np.frombuffer(data, "<f4", count, offset)
+
+    return (planes.reshape(count, 8, 8, 3).astype(np.float32), colors.copy(),
+            policies.reshape(count, 64).astype(np.float32),
+            outcomes.reshape(count, 1).astype(np.float32))
+
+
+def load_self_play_directory(directory):
+    """ Concatenate all `chunk_*.bin` files in `directory`
+    """
+    chunks = [load_self_play(path)
+              for path in sorted(glob.glob(os.path.join(directory, "chunk_*.bin")))]
+    if not chunks:
+        raise ValueError(f"no self-play data in {directory}")
+    return tuple(np.concatenate(arrays) for arrays in zip(*chunks))
+
+
+def load_training_data(path):
+    """ Read boards, policies and values exported by `ai::TrainingArrays` (Rust)
+
+        `path` is either a `.npz` file or a directory with `boards.npy`,
+        `policies.npy` and `values.npy`. All arrays are float32.
+    """
+    if os.path.isdir(path):
+        return tuple(np.load(os.path.join(path, f"{name}.npy"))
+                     for name in ("boards", "policies", "values"))
+    with np.load(path) as data:
+        return data["boards"], data["policies"], data["values"]
+
+
+def train(model, path, epochs=10, batch_size=256, learning_rate=0.001):
+    """ Fit the policy and value heads to exported training data
+    """
+    boards, policies, values = load_training_data(path)
+    model.compile(optimizer=tf.keras.optimizers.Adam(learning_rate),
+                  loss=["categorical_crossentropy", "mean_squared_error"])
+    return model.fit(boards, [policies, values], epochs=epochs, batch_size=batch_size)
+
+
+def make_test_data(directory):
+    """ Small network, reference outputs and SavedModel used by the ignored parity tests
+        in `ai/alpha_zero.rs` and `ai/tensorflow_evaluator.rs`
+    """
+    os.makedirs(directory, exist_ok=True)
+    rng = np.random.RandomState(0)
+    network = AlphaZeroResNet(action_space=64, n_blocks=2, filters=8)
+    inputs = rng.randint(0, 2, size=(4, 8, 8, 3)).astype(np.float32)
+    network(inputs)
+
+    #: random statistics so that the batch norms are not the identity
+    for layer in network.submodules:
+        if isinstance(layer, kl.BatchNormalization):
+            n = layer.gamma.shape[0]
+            layer.set_weights([rng.uniform(0.5, 1.5, n), rng.normal(0, 0.1, n),
+                               rng.normal(0, 0.1, n), rng.uniform(0.5, 1.5, n)])
+
+    export_weights(network, os.path.join(directory, "alpha_zero_test.bin"))
+    export_reference(network, inputs,
+                     os.path.join(directory, "alpha_zero_test_reference.bin"))
+    export_saved_model(network, os.path.join(directory, "saved_model"))
+
+
 if __name__ == "__main__":
-    network = AlphaZeroResNet(action_space=64, n_blocks=5, filters=64)
-    print(network([0]))
\ No newline at end of file
+    make_test_data(os.path.join(os.path.dirname(__file__), "testdata"))
@@ -1,11 +1,50 @@
+mod alpha_zero;
+mod endgame;
 mod evaluator;
+mod iterative_deepening;
+mod lazy_smp;
+mod mcts;
+mod move_ordering;
+mod mtdf;
 mod nega_alpha;
 mod nega_max;
+mod nega_scout;
 mod node;
+mod npy;
+mod pattern_evaluator;
+mod pattern_trainer;
+mod probcut;
+mod puct;
+mod random;
+mod search_position;
+mod search_result;
 mod self_play;
-mod tensorflow_example;
+mod self_play_pool;
+#[cfg(feature = "tensorflow")]
+mod tensorflow_evaluator;
+mod transposition_table;
 
+pub use alpha_zero::*;
+pub use endgame::*;
 pub use evaluator::*;
+pub use iterative_deepening::*;
+pub use lazy_smp::*;
+pub use mcts::*;
+pub use move_ordering::*;
+pub use mtdf::*;
 pub use nega_alpha::*;
 pub use nega_max::*;
+pub use nega_scout::*;
 pub use node::*;
+pub use npy::*;
+pub use pattern_evaluator::*;
+pub use pattern_trainer::*;
+pub use probcut::*;
+pub use puct::*;
+pub use search_position::*;
+pub use search_result::*;
+pub use self_play::*;
+pub use self_play_pool::*;
+#[cfg(feature = "tensorflow")]
+pub use tensorflow_evaluator::*;
+pub use transposition_table::*;
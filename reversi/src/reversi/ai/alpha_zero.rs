@@ -0,0 +1,766 @@
+use std::convert::TryInto;
+use std::io::{Error, ErrorKind};
+use std::path::Path;
+use std::sync::Arc;
+
+use crate::board::movable_position;
+use crate::reversi::common::*;
+
+use super::evaluator::{EvalResult, Evaluator};
+use super::puct::DEFAULT_VALUE_SCALE;
+
+/// 入力の面の数(手番側の石、相手の石、着手可能位置)
+pub const PLANE_COUNT: usize = 3;
+
+/// 方策の確率を整数にするときの倍率
+pub const POLICY_SCALE: f32 = 1_000_000.0;
+
+/// 重みファイルの先頭の識別子
+const WEIGHT_FILE_MAGIC: &[u8; 4] = b"RVNN";
+
+/// 重みファイルの形式のバージョン
+pub const NETWORK_FILE_VERSION: u32 = 1;
+
+const SQUARE_COUNT: usize = BOARD_SIZE * BOARD_SIZE;
+
+/// 方策の出力の数(パスは含まない)
+const ACTION_SPACE: usize = SQUARE_COUNT;
+
+/// 方策の頭部の畳み込みの出力チャンネル数
+const POLICY_CHANNELS: usize = 2;
+
+/// ネットワークの入力の面
+///
+/// `[行][列][面]`の順(NHWC)に並べる。
+/// 面は手番側の石、相手の石、手番側の着手可能位置の順で、石や着手可能位置があれば1。
+pub fn board_planes(board: &Squares, color: &PlayerColor) -> Vec<f32> {
+    let (own, opponent) = match color {
+        PlayerColor::Black => (Square::Black, Square::White),
+        PlayerColor::White => (Square::White, Square::Black),
+    };
+    let (player, opponent_data) = board.iter().enumerate().fold((0, 0), |(p, o), (i, s)| {
+        if *s == own {
+            (p | 1 << i, o)
+        } else if *s == opponent {
+            (p, o | 1 << i)
+        } else {
+            (p, o)
+        }
+    });
+    mask_planes(player, opponent_data)
+}
+
+/// 手番側と相手の石のビットボードから求めた[`board_planes`]
+pub fn mask_planes(player: u64, opponent: u64) -> Vec<f32> {
+    let movable = movable_position(player, opponent);
+
+    let mut planes = vec![0.0; SQUARE_COUNT * PLANE_COUNT];
+    for (i, plane) in planes.chunks_exact_mut(PLANE_COUNT).enumerate() {
+        for (p, data) in plane.iter_mut().zip([player, opponent, movable]) {
+            if data & 1 << i != 0 {
+                *p = 1.0;
+            }
+        }
+    }
+    planes
+}
+
+/// 重みファイルを先頭から読む
+struct Reader<'a> {
+    bytes: &'a [u8],
+    position: usize,
+}
+
+impl<'a> Reader<'a> {
+    fn take(&mut self, len: usize) -> std::io::Result<&'a [u8]> {
+        let bytes = self
+            .bytes
+            .get(self.position..self.position + len)
+            .ok_or_else(|| invalid("network weight file is too short".to_string()))?;
+        self.position += len;
+        Ok(bytes)
+    }
+
+    fn u32(&mut self) -> std::io::Result<u32> {
+        Ok(u32::from_le_bytes(self.take(4)?.try_into().unwrap()))
+    }
+
+    fn floats(&mut self, len: usize) -> std::io::Result<Vec<f32>> {
+        Ok(self
+            .take(len * 4)?
+            .chunks_exact(4)
+            .map(|chunk| f32::from_le_bytes(chunk.try_into().unwrap()))
+            .collect())
+    }
+}
+
+fn invalid(message: String) -> Error {
+    Error::new(ErrorKind::InvalidData, message)
+}
+
+fn write_floats(bytes: &mut Vec<u8>, values: &[f32]) {
+    for value in values {
+        bytes.extend_from_slice(&value.to_le_bytes());
+    }
+}
+
+/// 盤面と同じ大きさを保つ畳み込み(Kerasの`padding="same"`、バイアスなし)
+#[derive(Clone, PartialEq, Debug)]
+struct Conv2d {
+    kernel_size: usize,
+    input_channels: usize,
+    output_channels: usize,
+    /// `[縦][横][入力][出力]`の順(KerasのConv2Dのカーネルと同じ)
+    kernel: Vec<f32>,
+}
+
+impl Conv2d {
+    fn read(
+        reader: &mut Reader,
+        kernel_size: usize,
+        input_channels: usize,
+        output_channels: usize,
+    ) -> std::io::Result<Self> {
+        Ok(Conv2d {
+            kernel_size,
+            input_channels,
+            output_channels,
+            kernel: reader.floats(kernel_size * kernel_size * input_channels * output_channels)?,
+        })
+    }
+
+    /// `input`は盤面ごとの入力を並べたもので、出力も同じ順に並ぶ
+    fn forward(&self, input: &[f32]) -> Vec<f32> {
+        let (cin, cout) = (self.input_channels, self.output_channels);
+        let mut output = vec![0.0; input.len() / cin * cout];
+        for (input, output) in input
+            .chunks_exact(SQUARE_COUNT * cin)
+            .zip(output.chunks_exact_mut(SQUARE_COUNT * cout))
+        {
+            self.forward_board(input, output);
+        }
+        output
+    }
+
+    fn forward_board(&self, input: &[f32], output: &mut [f32]) {
+        let (k, cin, cout) = (self.kernel_size, self.input_channels, self.output_channels);
+        let pad = k / 2;
+        for r in 0..BOARD_SIZE {
+            for c in 0..BOARD_SIZE {
+                let out = &mut output[(r * BOARD_SIZE + c) * cout..][..cout];
+                for ky in 0..k {
+                    // 盤外は0
+                    let y = match (r + ky).checked_sub(pad) {
+                        Some(y) if y < BOARD_SIZE => y,
+                        _ => continue,
+                    };
+                    for kx in 0..k {
+                        let x = match (c + kx).checked_sub(pad) {
+                            Some(x) if x < BOARD_SIZE => x,
+                            _ => continue,
+                        };
+                        let pixel = &input[(y * BOARD_SIZE + x) * cin..][..cin];
+                        let kernel = &self.kernel[(ky * k + kx) * cin * cout..][..cin * cout];
+                        for (&v, weights) in pixel.iter().zip(kernel.chunks_exact(cout)) {
+                            if v == 0.0 {
+                                continue;
+                            }
+                            for (o, w) in out.iter_mut().zip(weights) {
+                                *o += v * w;
+                            }
+                        }
+                    }
+                }
+            }
+        }
+    }
+}
+
+/// 推論時のバッチ正規化
+#[derive(Clone, PartialEq, Debug)]
+struct BatchNorm {
+    gamma: Vec<f32>,
+    beta: Vec<f32>,
+    mean: Vec<f32>,
+    variance: Vec<f32>,
+    epsilon: f32,
+}
+
+impl BatchNorm {
+    fn read(reader: &mut Reader, channels: usize, epsilon: f32) -> std::io::Result<Self> {
+        Ok(BatchNorm {
+            gamma: reader.floats(channels)?,
+            beta: reader.floats(channels)?,
+            mean: reader.floats(channels)?,
+            variance: reader.floats(channels)?,
+            epsilon,
+        })
+    }
+
+    fn write(&self, bytes: &mut Vec<u8>) {
+        for values in [&self.gamma, &self.beta, &self.mean, &self.variance] {
+            write_floats(bytes, values);
+        }
+    }
+
+    /// 正規化して、`relu`ならReLUも適用する
+    fn apply(&self, values: &mut [f32], relu: bool) {
+        let scale = self
+            .gamma
+            .iter()
+            .zip(&self.variance)
+            .map(|(g, v)| g / (v + self.epsilon).sqrt())
+            .collect::<Vec<_>>();
+        for pixel in values.chunks_exact_mut(scale.len()) {
+            for (i, value) in pixel.iter_mut().enumerate() {
+                *value = (*value - self.mean[i]) * scale[i] + self.beta[i];
+                if relu {
+                    *value = value.max(0.0);
+                }
+            }
+        }
+    }
+}
+
+/// 全結合層
+#[derive(Clone, PartialEq, Debug)]
+struct Dense {
+    /// `[入力][出力]`の順
+    weights: Vec<f32>,
+    bias: Vec<f32>,
+}
+
+impl Dense {
+    fn read(reader: &mut Reader, inputs: usize, outputs: usize) -> std::io::Result<Self> {
+        Ok(Dense {
+            weights: reader.floats(inputs * outputs)?,
+            bias: reader.floats(outputs)?,
+        })
+    }
+
+    fn write(&self, bytes: &mut Vec<u8>) {
+        write_floats(bytes, &self.weights);
+        write_floats(bytes, &self.bias);
+    }
+
+    fn forward(&self, input: &[f32]) -> Vec<f32> {
+        let mut output = self.bias.clone();
+        for (&v, weights) in input.iter().zip(self.weights.chunks_exact(self.bias.len())) {
+            for (o, w) in output.iter_mut().zip(weights) {
+                *o += v * w;
+            }
+        }
+        output
+    }
+}
+
+/// 残差ブロック
+#[derive(Clone, PartialEq, Debug)]
+struct ResBlock {
+    conv1: Conv2d,
+    bn1: BatchNorm,
+    conv2: Conv2d,
+    bn2: BatchNorm,
+}
+
+impl ResBlock {
+    fn forward(&self, input: &[f32]) -> Vec<f32> {
+        let mut x = self.conv1.forward(input);
+        self.bn1.apply(&mut x, true);
+        let mut x = self.conv2.forward(&x);
+        self.bn2.apply(&mut x, false);
+        for (v, skip) in x.iter_mut().zip(input) {
+            *v = (*v + skip).max(0.0);
+        }
+        x
+    }
+}
+
+/// `python/alpha_zero_network.py`の`AlphaZeroResNet`のCPUでの推論
+///
+/// 重みは`export_weights`で書き出したファイルから読み込む。
+/// 入力は[`board_planes`]の先頭から、ネットワークの入力チャンネル数の面を使う。
+#[derive(Clone, PartialEq, Debug)]
+pub struct AlphaZeroNetwork {
+    input_channels: usize,
+    filters: usize,
+    conv1: Conv2d,
+    bn1: BatchNorm,
+    blocks: Vec<ResBlock>,
+    conv_p: Conv2d,
+    bn_p: BatchNorm,
+    logits: Dense,
+    conv_v: Conv2d,
+    bn_v: BatchNorm,
+    value: Dense,
+}
+
+impl AlphaZeroNetwork {
+    pub fn input_channels(&self) -> usize {
+        self.input_channels
+    }
+
+    pub fn filters(&self) -> usize {
+        self.filters
+    }
+
+    /// 残差ブロックの数
+    pub fn block_count(&self) -> usize {
+        self.blocks.len()
+    }
+
+    /// 盤面を入力にして方策と価値を求める
+    pub fn predict(&self, board: &Squares, color: &PlayerColor) -> (Vec<f32>, f32) {
+        self.forward(&self.input(board, color))
+    }
+
+    /// 複数の盤面をまとめて[`AlphaZeroNetwork::predict`]する
+    pub fn predict_batch(&self, positions: &[(Squares, PlayerColor)]) -> Vec<(Vec<f32>, f32)> {
+        let inputs = positions
+            .iter()
+            .flat_map(|(board, color)| self.input(board, color))
+            .collect::<Vec<_>>();
+        self.forward_batch(&inputs)
+    }
+
+    fn input(&self, board: &Squares, color: &PlayerColor) -> Vec<f32> {
+        board_planes(board, color)
+            .chunks_exact(PLANE_COUNT)
+            .flat_map(|plane| &plane[..self.input_channels])
+            .copied()
+            .collect()
+    }
+
+    /// `[行][列][チャンネル]`の順の入力から、方策(合計1)と価値([-1, 1])を求める
+    pub fn forward(&self, input: &[f32]) -> (Vec<f32>, f32) {
+        assert_eq!(input.len(), SQUARE_COUNT * self.input_channels);
+        self.forward_batch(input).swap_remove(0)
+    }
+
+    /// 盤面ごとの入力を並べたものから、盤面ごとの方策と価値を求める
+    ///
+    /// 層ごとに全ての盤面を処理するので、1つずつ[`AlphaZeroNetwork::forward`]するより重みを読み直す回数が少ない。
+    pub fn forward_batch(&self, inputs: &[f32]) -> Vec<(Vec<f32>, f32)> {
+        assert_eq!(inputs.len() % (SQUARE_COUNT * self.input_channels), 0);
+        let mut x = self.conv1.forward(inputs);
+        self.bn1.apply(&mut x, true);
+        for block in &self.blocks {
+            x = block.forward(&x);
+        }
+
+        let mut p = self.conv_p.forward(&x);
+        self.bn_p.apply(&mut p, true);
+        let mut v = self.conv_v.forward(&x);
+        self.bn_v.apply(&mut v, true);
+
+        p.chunks_exact(SQUARE_COUNT * POLICY_CHANNELS)
+            .zip(v.chunks_exact(SQUARE_COUNT))
+            .map(|(p, v)| {
+                let logits = self.logits.forward(p);
+                let max = logits.iter().fold(f32::NEG_INFINITY, |m, &l| m.max(l));
+                let exp = logits.iter().map(|l| (l - max).exp()).collect::<Vec<_>>();
+                let total = exp.iter().sum::<f32>();
+                let policy = exp.iter().map(|e| e / total).collect();
+                let value = self.value.forward(v)[0].tanh();
+                (policy, value)
+            })
+            .collect()
+    }
+
+    /// 重みファイルの形式にする
+    ///
+    /// ヘッダ(識別子`RVNN`、バージョン、入力チャンネル数、フィルタ数、残差ブロック数、
+    /// 方策の出力数、バッチ正規化のepsilon)の後に、各層の重みをKerasの`get_weights`と同じ並びで書く。
+    /// 数値は全てリトルエンディアンで、epsilonと重みはf32、それ以外はu32。
+    pub fn to_bytes(&self) -> Vec<u8> {
+        let mut bytes = Vec::new();
+        bytes.extend_from_slice(WEIGHT_FILE_MAGIC);
+        for value in [
+            NETWORK_FILE_VERSION,
+            self.input_channels as u32,
+            self.filters as u32,
+            self.blocks.len() as u32,
+            ACTION_SPACE as u32,
+        ] {
+            bytes.extend_from_slice(&value.to_le_bytes());
+        }
+        bytes.extend_from_slice(&self.bn1.epsilon.to_le_bytes());
+
+        write_floats(&mut bytes, &self.conv1.kernel);
+        self.bn1.write(&mut bytes);
+        for block in &self.blocks {
+            write_floats(&mut bytes, &block.conv1.kernel);
+            block.bn1.write(&mut bytes);
+            write_floats(&mut bytes, &block.conv2.kernel);
+            block.bn2.write(&mut bytes);
+        }
+        write_floats(&mut bytes, &self.conv_p.kernel);
+        self.bn_p.write(&mut bytes);
+        self.logits.write(&mut bytes);
+        write_floats(&mut bytes, &self.conv_v.kernel);
+        self.bn_v.write(&mut bytes);
+        self.value.write(&mut bytes);
+        bytes
+    }
+
+    /// [`AlphaZeroNetwork::to_bytes`]の形式から読み込む
+    pub fn from_bytes(bytes: &[u8]) -> std::io::Result<Self> {
+        let mut reader = Reader { bytes, position: 0 };
+        if reader.take(4)? != WEIGHT_FILE_MAGIC {
+            return Err(invalid("not a network weight file".to_string()));
+        }
+        let version = reader.u32()?;
+        if version != NETWORK_FILE_VERSION {
+            return Err(invalid(format!(
+                "unsupported network weight file version: {}",
+                version
+            )));
+        }
+        let input_channels = reader.u32()? as usize;
+        let filters = reader.u32()? as usize;
+        let block_count = reader.u32()? as usize;
+        let action_space = reader.u32()? as usize;
+        let epsilon = reader.floats(1)?[0];
+        if !(1..=PLANE_COUNT).contains(&input_channels)
+            || filters == 0
+            || action_space != ACTION_SPACE
+        {
+            return Err(invalid(format!(
+                "invalid network shape: {} input channels, {} filters, {} actions",
+                input_channels, filters, action_space
+            )));
+        }
+
+        let reader = &mut reader;
+        let conv1 = Conv2d::read(reader, 3, input_channels, filters)?;
+        let bn1 = BatchNorm::read(reader, filters, epsilon)?;
+        let blocks = (0..block_count)
+            .map(|_| {
+                Ok(ResBlock {
+                    conv1: Conv2d::read(reader, 3, filters, filters)?,
+                    bn1: BatchNorm::read(reader, filters, epsilon)?,
+                    conv2: Conv2d::read(reader, 3, filters, filters)?,
+                    bn2: BatchNorm::read(reader, filters, epsilon)?,
+                })
+            })
+            .collect::<std::io::Result<Vec<_>>>()?;
+        let network = AlphaZeroNetwork {
+            input_channels,
+            filters,
+            conv1,
+            bn1,
+            blocks,
+            conv_p: Conv2d::read(reader, 1, filters, POLICY_CHANNELS)?,
+            bn_p: BatchNorm::read(reader, POLICY_CHANNELS, epsilon)?,
+            logits: Dense::read(reader, SQUARE_COUNT * POLICY_CHANNELS, ACTION_SPACE)?,
+            conv_v: Conv2d::read(reader, 1, filters, 1)?,
+            bn_v: BatchNorm::read(reader, 1, epsilon)?,
+            value: Dense::read(reader, SQUARE_COUNT, 1)?,
+        };
+        if reader.position != bytes.len() {
+            return Err(invalid(format!(
+                "network weight file has {} extra bytes",
+                bytes.len() - reader.position
+            )));
+        }
+        Ok(network)
+    }
+
+    pub fn save<P: AsRef<Path>>(&self, path: P) -> std::io::Result<()> {
+        std::fs::write(path, self.to_bytes())
+    }
+
+    pub fn load<P: AsRef<Path>>(path: P) -> std::io::Result<Self> {
+        Self::from_bytes(&std::fs::read(path)?)
+    }
+}
+
+/// [`AlphaZeroNetwork`]による評価関数
+///
+/// 価値は[`DEFAULT_VALUE_SCALE`]倍、方策は[`POLICY_SCALE`]倍した整数で返す。
+/// ネットワークは複製しても共有する。
+#[derive(Clone, Debug)]
+pub struct AlphaZeroEvaluator {
+    network: Arc<AlphaZeroNetwork>,
+}
+
+impl AlphaZeroEvaluator {
+    pub fn new(network: AlphaZeroNetwork) -> Self {
+        AlphaZeroEvaluator {
+            network: Arc::new(network),
+        }
+    }
+
+    /// 重みファイルから作成
+    pub fn load<P: AsRef<Path>>(path: P) -> std::io::Result<Self> {
+        Ok(AlphaZeroEvaluator::new(AlphaZeroNetwork::load(path)?))
+    }
+
+    pub fn network(&self) -> &AlphaZeroNetwork {
+        &self.network
+    }
+}
+
+impl Evaluator for AlphaZeroEvaluator {
+    fn evaluate(&mut self, board: &Squares, color: &PlayerColor) -> EvalResult {
+        let (policy, value) = self.network.predict(board, color);
+        eval_result(&policy, value)
+    }
+
+    fn evaluate_batch(&mut self, positions: &[(Squares, PlayerColor)]) -> Vec<EvalResult> {
+        self.network
+            .predict_batch(positions)
+            .iter()
+            .map(|(policy, value)| eval_result(policy, *value))
+            .collect()
+    }
+}
+
+fn eval_result(policy: &[f32], value: f32) -> EvalResult {
+    let mut scaled = [0; SQUARE_COUNT];
+    for (s, p) in scaled.iter_mut().zip(policy) {
+        *s = (p * POLICY_SCALE).round() as i32;
+    }
+    EvalResult {
+        value: (value * DEFAULT_VALUE_SCALE as f32).round() as i32,
+        policy: Some(scaled),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::ai::random::XorShift;
+    use crate::board::{BitBoard, Board};
+
+    fn conv(kernel_size: usize, input_channels: usize, output_channels: usize) -> Conv2d {
+        Conv2d {
+            kernel_size,
+            input_channels,
+            output_channels,
+            kernel: vec![0.0; kernel_size * kernel_size * input_channels * output_channels],
+        }
+    }
+
+    /// 入力をそのまま出力するバッチ正規化
+    fn identity_bn(channels: usize) -> BatchNorm {
+        BatchNorm {
+            gamma: vec![1.0; channels],
+            beta: vec![0.0; channels],
+            mean: vec![0.0; channels],
+            variance: vec![1.0; channels],
+            epsilon: 0.0,
+        }
+    }
+
+    /// 畳み込みと全結合の重みが全て0のネットワーク
+    fn zero_network(input_channels: usize, filters: usize, blocks: usize) -> AlphaZeroNetwork {
+        AlphaZeroNetwork {
+            input_channels,
+            filters,
+            conv1: conv(3, input_channels, filters),
+            bn1: identity_bn(filters),
+            blocks: (0..blocks)
+                .map(|_| ResBlock {
+                    conv1: conv(3, filters, filters),
+                    bn1: identity_bn(filters),
+                    conv2: conv(3, filters, filters),
+                    bn2: identity_bn(filters),
+                })
+                .collect(),
+            conv_p: conv(1, filters, POLICY_CHANNELS),
+            bn_p: identity_bn(POLICY_CHANNELS),
+            logits: Dense {
+                weights: vec![0.0; SQUARE_COUNT * POLICY_CHANNELS * ACTION_SPACE],
+                bias: vec![0.0; ACTION_SPACE],
+            },
+            conv_v: conv(1, filters, 1),
+            bn_v: identity_bn(1),
+            value: Dense {
+                weights: vec![0.0; SQUARE_COUNT],
+                bias: vec![0.0],
+            },
+        }
+    }
+
+    /// 左上のマスの手番側の石を方策と価値に写すネットワーク
+    ///
+    /// 最初の畳み込みはカーネルの左上だけが1なので、各マスに左上のマスの値が入る。
+    fn shift_network(blocks: usize) -> AlphaZeroNetwork {
+        let mut network = zero_network(PLANE_COUNT, 1, blocks);
+        // [縦0][横0][入力0][出力0]
+        network.conv1.kernel[0] = 1.0;
+        // 方策の1チャンネル目と価値はそのまま写す
+        network.conv_p.kernel[0] = 1.0;
+        network.conv_v.kernel[0] = 1.0;
+        for i in 0..SQUARE_COUNT {
+            network.logits.weights[i * POLICY_CHANNELS * ACTION_SPACE + i] = 10.0;
+            network.value.weights[i] = 0.1;
+        }
+        network
+    }
+
+    #[test]
+    fn test_board_planes() {
+        let board = BitBoard::new_initial();
+        let planes = board_planes(board.squares(), &PlayerColor::White);
+        assert_eq!(planes.len(), SQUARE_COUNT * PLANE_COUNT);
+        let plane = |p: usize| {
+            (0..SQUARE_COUNT)
+                .filter(|i| planes[i * PLANE_COUNT + p] == 1.0)
+                .collect::<Vec<_>>()
+        };
+        // 白はd4とe5、黒はe4とd5
+        assert_eq!(plane(0), vec![27, 36]);
+        assert_eq!(plane(1), vec![28, 35]);
+        assert_eq!(plane(2), vec![20, 29, 34, 43]);
+    }
+
+    #[test]
+    fn test_zero_network() {
+        let mut network = zero_network(2, 4, 2);
+        network.value.bias[0] = 0.5;
+        let board = BitBoard::new_initial();
+        let (policy, value) = network.predict(board.squares(), &PlayerColor::Black);
+        assert!(policy.iter().all(|p| (p - 1.0 / 64.0).abs() < 1e-6));
+        assert!((value - 0.5_f32.tanh()).abs() < 1e-6);
+    }
+
+    #[test]
+    fn test_forward() {
+        let board = BitBoard::new_initial();
+        for blocks in [0, 2] {
+            // 残差ブロックの畳み込みが0ならブロックは入力をそのまま返す
+            let network = shift_network(blocks);
+            let (policy, value) = network.predict(board.squares(), &PlayerColor::Black);
+            // 黒石e4(3, 4)とd5(4, 3)が右下にずれてf5(4, 5)とe6(5, 4)になる
+            let high = 10.0_f32.exp() / (2.0 * 10.0_f32.exp() + 62.0);
+            for (i, p) in policy.iter().enumerate() {
+                let expected = if i == 37 || i == 44 {
+                    high
+                } else {
+                    1.0 / (2.0 * 10.0_f32.exp() + 62.0)
+                };
+                assert!((p - expected).abs() < 1e-5, "{} {}", i, p);
+            }
+            assert!((value - 0.2_f32.tanh()).abs() < 1e-6);
+        }
+
+        // 盤外は0として扱い、右端の石が次の行の左端に回り込むことはない
+        let mut squares = [Square::Empty; SQUARE_COUNT];
+        squares[0] = Square::Black;
+        squares[7] = Square::Black;
+        squares[56] = Square::Black;
+        let (policy, _) = shift_network(0).predict(&squares, &PlayerColor::Black);
+        let total = 10.0_f32.exp() + 63.0;
+        for (i, p) in policy.iter().enumerate() {
+            let expected = if i == 9 { 10.0_f32.exp() } else { 1.0 } / total;
+            assert!((p - expected).abs() < 1e-5, "{} {}", i, p);
+        }
+    }
+
+    #[test]
+    fn test_evaluate_batch() {
+        let mut network = zero_network(PLANE_COUNT, 4, 1);
+        let mut rng = XorShift::new(3);
+        let block = &mut network.blocks[0];
+        let kernels = [
+            &mut network.conv1.kernel,
+            &mut block.conv1.kernel,
+            &mut block.conv2.kernel,
+            &mut network.conv_p.kernel,
+            &mut network.conv_v.kernel,
+            &mut network.logits.weights,
+            &mut network.value.weights,
+        ];
+        for kernel in kernels {
+            for w in kernel.iter_mut() {
+                *w = rng.normal() as f32 * 0.3;
+            }
+        }
+
+        let mut board = BitBoard::new_initial();
+        let mut positions = Vec::new();
+        for _ in 0..5 {
+            let color = board.turn();
+            positions.push((*board.squares(), color));
+            let m = Move::new_position(color, board.get_movable_positions(&color)[0]);
+            board = board.apply_move(&m).unwrap();
+        }
+        let mut evaluator = AlphaZeroEvaluator::new(network);
+        let batch = evaluator.evaluate_batch(&positions);
+        assert_eq!(batch.len(), positions.len());
+        for ((squares, color), result) in positions.iter().zip(&batch) {
+            assert_eq!(result, &evaluator.evaluate(squares, color));
+        }
+        assert!(evaluator.evaluate_batch(&[]).is_empty());
+    }
+
+    #[test]
+    fn test_save_and_load() {
+        let mut network = zero_network(2, 3, 1);
+        let mut rng = XorShift::new(1);
+        for w in network.blocks[0].conv2.kernel.iter_mut() {
+            *w = rng.normal() as f32;
+        }
+
+        let path = std::env::temp_dir().join(format!("network_test_{}.bin", std::process::id()));
+        network.save(&path).unwrap();
+        let mut evaluator = AlphaZeroEvaluator::load(&path).unwrap();
+        std::fs::remove_file(&path).unwrap();
+        assert_eq!(evaluator.network().filters(), 3);
+        assert_eq!(evaluator.network().block_count(), 1);
+        assert_eq!(evaluator.network().input_channels(), 2);
+        assert_eq!(evaluator.network(), &network);
+
+        let result = evaluator.evaluate(BitBoard::new_initial().squares(), &PlayerColor::Black);
+        assert_eq!(result.value, 0);
+        assert!(result.policy.unwrap().iter().all(|&p| p == 15625));
+
+        let bytes = network.to_bytes();
+        assert!(AlphaZeroNetwork::from_bytes(&bytes[..bytes.len() - 1]).is_err());
+        let mut extra = bytes.clone();
+        extra.push(0);
+        assert!(AlphaZeroNetwork::from_bytes(&extra).is_err());
+        let mut wrong_version = bytes.clone();
+        wrong_version[4] = 99;
+        assert!(AlphaZeroNetwork::from_bytes(&wrong_version).is_err());
+        let mut wrong_channels = bytes;
+        wrong_channels[8] = 4;
+        assert!(AlphaZeroNetwork::from_bytes(&wrong_channels).is_err());
+    }
+
+    /// Kerasのモデルの出力と一致するか
+    ///
+    /// `python/alpha_zero_network.py`を実行して`python/testdata`に重みと参照出力を書き出してから実行する。
+    /// 書き出しにはTensorFlow 2.xとnumpyが必要。
+    #[test]
+    #[ignore = "needs python/testdata written by `python python/alpha_zero_network.py`"]
+    fn test_keras_parity() {
+        let dir = Path::new(env!("CARGO_MANIFEST_DIR")).join("python/testdata");
+        let network = AlphaZeroNetwork::load(dir.join("alpha_zero_test.bin")).unwrap();
+        let bytes = std::fs::read(dir.join("alpha_zero_test_reference.bin")).unwrap();
+        let mut reader = Reader {
+            bytes: &bytes,
+            position: 0,
+        };
+        assert_eq!(reader.take(4).unwrap(), b"RVNR");
+        assert_eq!(reader.u32().unwrap(), NETWORK_FILE_VERSION);
+        let count = reader.u32().unwrap() as usize;
+        let channels = reader.u32().unwrap() as usize;
+        assert_eq!(channels, network.input_channels());
+        let inputs = reader.floats(count * SQUARE_COUNT * channels).unwrap();
+        let policies = reader.floats(count * ACTION_SPACE).unwrap();
+        let values = reader.floats(count).unwrap();
+
+        for i in 0..count {
+            let input = &inputs[i * SQUARE_COUNT * channels..][..SQUARE_COUNT * channels];
+            let (policy, value) = network.forward(input);
+            let expected = &policies[i * ACTION_SPACE..][..ACTION_SPACE];
+            for (p, e) in policy.iter().zip(expected) {
+                assert!((p - e).abs() < 1e-4, "{} {}", p, e);
+            }
+            assert!((value - values[i]).abs() < 1e-4, "{} {}", value, values[i]);
+        }
+    }
+}
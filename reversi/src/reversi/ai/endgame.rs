@@ -0,0 +1,543 @@
+use crate::{
+    board::{flip_data, movable_position, BitBoard},
+    index_to_position, Move, PlayerColor,
+};
+
+/// 最終スコア(石数差)の最大値
+pub const SCORE_MAX: i32 = 64;
+const SCORE_INF: i32 = SCORE_MAX + 1;
+
+/// これ以上空きマスが多いときは、着手後の相手の着手可能数が少ない手から探索する(速さ優先)
+const FASTEST_FIRST_EMPTIES: u32 = 7;
+
+/// この空きマス数以下では着手可能位置を生成せずに空きマスを直接調べる
+const SMALL_EMPTIES: u32 = 4;
+
+/// 盤面を4分割した領域
+/// 空きマスが奇数の領域から打つ(偶数理論)
+const QUADRANT_MASKS: [u64; 4] = [
+    0x0000_0000_0f0f_0f0f,
+    0x0000_0000_f0f0_f0f0,
+    0x0f0f_0f0f_0000_0000,
+    0xf0f0_f0f0_0000_0000,
+];
+
+/// 一局面の着手可能数の上限(知られている最大値は33)
+const MAX_MOVES: usize = 34;
+
+/// 完全読みの結果
+#[derive(Clone, Copy, PartialEq, Eq, Debug)]
+pub struct EndgameResult {
+    /// 手番側から見た最終石数差(空きマスは勝った側に加算する)
+    pub score: i32,
+    /// 最善手(終局している場合は`None`)
+    pub best_move: Option<Move>,
+    /// 探索したノード数
+    pub nodes: u64,
+}
+
+/// 勝敗
+#[derive(Clone, Copy, PartialEq, Eq, Hash, Debug)]
+pub enum GameOutcome {
+    Win,
+    Draw,
+    Loss,
+}
+
+/// 勝敗読みの結果
+#[derive(Clone, Copy, PartialEq, Eq, Debug)]
+pub struct WldResult {
+    /// 手番側から見た勝敗
+    pub outcome: GameOutcome,
+    /// 勝敗を保証する手(負けの場合はいずれかの合法手、終局している場合は`None`)
+    pub best_move: Option<Move>,
+    /// 探索したノード数
+    pub nodes: u64,
+}
+
+/// 終盤の完全読み
+///
+/// 盤面を手番側と相手側のビットボードのみで扱う。
+/// 空きマス24程度までを想定している。
+#[derive(Default)]
+pub struct EndgameSolver {
+    nodes: u64,
+}
+
+impl EndgameSolver {
+    pub fn new() -> Self {
+        EndgameSolver { nodes: 0 }
+    }
+
+    /// `color`の手番として最終石数差と最善手を求める
+    pub fn solve(&mut self, board: &BitBoard, color: PlayerColor) -> EndgameResult {
+        self.solve_window(board, color, -SCORE_INF, SCORE_INF)
+    }
+
+    /// 探索窓(alpha, beta)で探索する
+    ///
+    /// 結果の値が窓の外なら、真の値はその値以下(alpha以下の場合)または以上(beta以上の場合)。
+    pub fn solve_window(
+        &mut self,
+        board: &BitBoard,
+        color: PlayerColor,
+        alpha: i32,
+        beta: i32,
+    ) -> EndgameResult {
+        self.nodes = 0;
+        let (player, opponent) = match color {
+            PlayerColor::Black => (board.black(), board.white()),
+            PlayerColor::White => (board.white(), board.black()),
+        };
+        let (score, best_move) = self.search_root(player, opponent, color, alpha, beta);
+        EndgameResult {
+            score,
+            best_move,
+            nodes: self.nodes,
+        }
+    }
+
+    /// 勝ち・引き分け・負けだけを求める
+    ///
+    /// 0の周りのnull window探索を最大2回行う。石数差を求めるより速い。
+    pub fn solve_wld(&mut self, board: &BitBoard, color: PlayerColor) -> WldResult {
+        // 勝ちか(0より大きいか)
+        let win = self.solve_window(board, color, 0, 1);
+        if win.score > 0 {
+            return WldResult {
+                outcome: GameOutcome::Win,
+                best_move: win.best_move,
+                nodes: win.nodes,
+            };
+        }
+
+        // 引き分けか(0以上か)
+        let draw = self.solve_window(board, color, -1, 0);
+        let outcome = if draw.score >= 0 {
+            GameOutcome::Draw
+        } else {
+            GameOutcome::Loss
+        };
+        WldResult {
+            outcome,
+            best_move: draw.best_move,
+            nodes: win.nodes + draw.nodes,
+        }
+    }
+
+    fn search_root(
+        &mut self,
+        player: u64,
+        opponent: u64,
+        color: PlayerColor,
+        alpha: i32,
+        beta: i32,
+    ) -> (i32, Option<Move>) {
+        self.nodes += 1;
+        let moves = movable_position(player, opponent);
+        if moves == 0 {
+            if movable_position(opponent, player) == 0 {
+                return (final_score(player, opponent), None);
+            }
+            let score = -self.search(opponent, player, -beta, -alpha);
+            return (score, Some(Move::new_pass(color)));
+        }
+
+        let mut list = MoveList::new(player, opponent, moves);
+        let mut best = -SCORE_INF;
+        let mut best_move = None;
+        let mut alpha = alpha;
+        for &(pos, flips) in list.sorted() {
+            let v = -self.search(opponent ^ flips, player ^ flips ^ pos, -beta, -alpha);
+            if v > best {
+                best = v;
+                best_move = Some(Move::new_position(
+                    color,
+                    index_to_position(pos.trailing_zeros() as usize),
+                ));
+                if v > alpha {
+                    alpha = v;
+                    if alpha >= beta {
+                        break;
+                    }
+                }
+            }
+        }
+        (best, best_move)
+    }
+
+    fn search(&mut self, player: u64, opponent: u64, alpha: i32, beta: i32) -> i32 {
+        let empties = !(player | opponent);
+        if empties.count_ones() <= SMALL_EMPTIES {
+            return self.search_small(player, opponent, empties, alpha, beta);
+        }
+
+        self.nodes += 1;
+        let moves = movable_position(player, opponent);
+        if moves == 0 {
+            if movable_position(opponent, player) == 0 {
+                return final_score(player, opponent);
+            }
+            return -self.search(opponent, player, -beta, -alpha);
+        }
+
+        let mut list = MoveList::new(player, opponent, moves);
+        let mut best = -SCORE_INF;
+        let mut alpha = alpha;
+        for &(pos, flips) in list.sorted() {
+            let v = -self.search(opponent ^ flips, player ^ flips ^ pos, -beta, -alpha);
+            if v > best {
+                best = v;
+                if v > alpha {
+                    alpha = v;
+                    if alpha >= beta {
+                        break;
+                    }
+                }
+            }
+        }
+        best
+    }
+
+    /// 空きマスが少ないときの探索
+    ///
+    /// 着手可能位置を生成せず、空きマスごとにひっくり返せる石があるかを調べる。
+    fn search_small(
+        &mut self,
+        player: u64,
+        opponent: u64,
+        empties: u64,
+        alpha: i32,
+        beta: i32,
+    ) -> i32 {
+        self.nodes += 1;
+        match empties.count_ones() {
+            0 => final_score(player, opponent),
+            1 => last_move_score(player, opponent, empties),
+            _ => {
+                let squares = parity_ordered_squares(empties);
+                let mut best = -SCORE_INF;
+                let mut alpha = alpha;
+                for &pos in squares.iter().filter(|pos| **pos != 0) {
+                    let flips = flip_data(player, opponent, pos);
+                    if flips == 0 {
+                        continue;
+                    }
+                    let v = -self.search_small(
+                        opponent ^ flips,
+                        player ^ flips ^ pos,
+                        empties ^ pos,
+                        -beta,
+                        -alpha,
+                    );
+                    if v > best {
+                        best = v;
+                        if v > alpha {
+                            alpha = v;
+                            if alpha >= beta {
+                                break;
+                            }
+                        }
+                    }
+                }
+
+                if best == -SCORE_INF {
+                    // 置ける場所がない
+                    let opponent_can_move = squares
+                        .iter()
+                        .any(|pos| *pos != 0 && flip_data(opponent, player, *pos) != 0);
+                    if opponent_can_move {
+                        -self.search_small(opponent, player, empties, -beta, -alpha)
+                    } else {
+                        final_score(player, opponent)
+                    }
+                } else {
+                    best
+                }
+            }
+        }
+    }
+}
+
+/// 終局時の手番側から見た石数差
+/// 空きマスは勝った側の石として数える
+pub fn final_score(player: u64, opponent: u64) -> i32 {
+    let p = player.count_ones() as i32;
+    let o = opponent.count_ones() as i32;
+    let empties = SCORE_MAX - p - o;
+    match p.cmp(&o) {
+        std::cmp::Ordering::Greater => p - o + empties,
+        std::cmp::Ordering::Less => p - o - empties,
+        std::cmp::Ordering::Equal => 0,
+    }
+}
+
+/// 残り1マスの最終スコア
+fn last_move_score(player: u64, opponent: u64, pos: u64) -> i32 {
+    let flips = flip_data(player, opponent, pos);
+    if flips != 0 {
+        return final_score(player ^ flips ^ pos, opponent ^ flips);
+    }
+    let flips = flip_data(opponent, player, pos);
+    if flips != 0 {
+        return final_score(player ^ flips, opponent ^ flips ^ pos);
+    }
+    final_score(player, opponent)
+}
+
+/// 空きマスが奇数個の領域のマスク
+fn odd_region_mask(empties: u64) -> u64 {
+    QUADRANT_MASKS
+        .iter()
+        .filter(|q| (empties & **q).count_ones() % 2 == 1)
+        .fold(0, |mask, q| mask | q)
+}
+
+/// 空きマス(4つまで)を奇数領域のものから順に並べる
+fn parity_ordered_squares(empties: u64) -> [u64; SMALL_EMPTIES as usize] {
+    let odd = odd_region_mask(empties);
+    let mut squares = [0; SMALL_EMPTIES as usize];
+    let mut count = 0;
+    for region in [empties & odd, empties & !odd] {
+        let mut region = region;
+        while region != 0 && count < squares.len() {
+            let pos = region & region.wrapping_neg();
+            squares[count] = pos;
+            count += 1;
+            region ^= pos;
+        }
+    }
+    squares
+}
+
+/// 並べ替え済みの着手リスト
+struct MoveList {
+    moves: [(u64, u64); MAX_MOVES],
+    keys: [i32; MAX_MOVES],
+    len: usize,
+}
+
+impl MoveList {
+    fn new(player: u64, opponent: u64, moves: u64) -> Self {
+        let empties = !(player | opponent);
+        let odd = odd_region_mask(empties);
+        let fastest_first = empties.count_ones() > FASTEST_FIRST_EMPTIES;
+
+        let mut list = MoveList {
+            moves: [(0, 0); MAX_MOVES],
+            keys: [0; MAX_MOVES],
+            len: 0,
+        };
+        let mut rest = moves;
+        while rest != 0 {
+            let pos = rest & rest.wrapping_neg();
+            rest ^= pos;
+            let flips = flip_data(player, opponent, pos);
+            let parity = if pos & odd != 0 { 0 } else { 1 };
+            let key = if fastest_first {
+                // 相手の着手可能数を優先し、同じなら奇数領域の手
+                let mobility =
+                    movable_position(opponent ^ flips, player ^ flips ^ pos).count_ones() as i32;
+                mobility * 2 + parity
+            } else {
+                parity
+            };
+            list.moves[list.len] = (pos, flips);
+            list.keys[list.len] = key;
+            list.len += 1;
+        }
+        list
+    }
+
+    /// キーの小さい順(安定ソート)
+    fn sorted(&mut self) -> &[(u64, u64)] {
+        for i in 1..self.len {
+            let mut j = i;
+            while j > 0 && self.keys[j - 1] > self.keys[j] {
+                self.keys.swap(j - 1, j);
+                self.moves.swap(j - 1, j);
+                j -= 1;
+            }
+        }
+        &self.moves[..self.len]
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::ai::next_moves;
+    use crate::board::Board;
+    use crate::{Square, Squares, BOARD_SIZE};
+
+    /// 全幅探索による最終スコア
+    fn brute_force(board: &BitBoard, color: PlayerColor) -> i32 {
+        if board.is_game_over() {
+            let (player, opponent) = match color {
+                PlayerColor::Black => (board.black(), board.white()),
+                PlayerColor::White => (board.white(), board.black()),
+            };
+            return final_score(player, opponent);
+        }
+        next_moves(board, &color)
+            .iter()
+            .map(|m| -brute_force(&board.apply_move(m).unwrap(), color.opponent()))
+            .max()
+            .unwrap()
+    }
+
+    /// 初期局面から`select`で選んだ手を打って、空きマスが`empties`の局面を作る
+    fn make_position(empties: u32, select: impl Fn(usize, usize) -> usize) -> BitBoard {
+        let mut board = BitBoard::new_initial();
+        let mut color = PlayerColor::Black;
+        while board.empty_count() > empties && !board.is_game_over() {
+            let moves = next_moves(&board, &color);
+            let i = select(board.depth() as usize, moves.len());
+            board = board.apply_move(&moves[i]).unwrap();
+            color = color.opponent();
+        }
+        board
+    }
+
+    fn test_positions(empties: u32) -> Vec<BitBoard> {
+        vec![
+            make_position(empties, |_, _| 0),
+            make_position(empties, |_, len| len - 1),
+            make_position(empties, |depth, len| (depth * 7) % len),
+            make_position(empties, |depth, len| (depth * 13 + 5) % len),
+        ]
+    }
+
+    #[test]
+    fn test_final_score() {
+        assert_eq!(final_score(0b111, 0b1000), 62);
+        assert_eq!(final_score(0b1, 0b110), -62);
+        assert_eq!(final_score(0b1, 0b10), 0);
+        assert_eq!(final_score(u64::MAX >> 10, u64::MAX << 54), 44);
+    }
+
+    #[test]
+    fn test_known_positions() {
+        // a1だけが空いていて、b1が白、それ以外が黒
+        let mut squares: Squares = [Square::Black; BOARD_SIZE * BOARD_SIZE];
+        squares[0] = Square::Empty;
+        squares[1] = Square::White;
+        let board = BitBoard::new(&squares, 0);
+
+        let mut solver = EndgameSolver::new();
+        let result = solver.solve(&board, PlayerColor::Black);
+        assert_eq!(result.score, 64);
+        assert_eq!(
+            result.best_move,
+            Some(Move::new_position(
+                PlayerColor::Black,
+                crate::Position(0, 0)
+            ))
+        );
+
+        // 白はパスするしかなく、黒がa1に打って全滅する
+        let result = solver.solve(&board, PlayerColor::White);
+        assert_eq!(result.score, -64);
+        assert_eq!(result.best_move, Some(Move::new_pass(PlayerColor::White)));
+
+        // 終局している
+        let board = BitBoard::new_from_data(u64::MAX >> 4, 0, 0);
+        let result = solver.solve(&board, PlayerColor::White);
+        assert_eq!(result.score, -64);
+        assert_eq!(result.best_move, None);
+    }
+
+    #[test]
+    fn test_matches_brute_force() {
+        let mut solver = EndgameSolver::new();
+        for empties in 1..=8 {
+            for board in test_positions(empties) {
+                for color in [PlayerColor::Black, PlayerColor::White] {
+                    let expected = brute_force(&board, color);
+                    let result = solver.solve(&board, color);
+                    assert_eq!(result.score, expected, "{}", board.to_console_text());
+
+                    // 最善手を打った局面の値と一致する
+                    if let Some(best_move) = result.best_move {
+                        let next = board.apply_move(&best_move).unwrap();
+                        assert_eq!(-brute_force(&next, color.opponent()), expected);
+                    }
+                }
+            }
+        }
+    }
+
+    #[test]
+    fn test_solve_midgame_empties() {
+        let mut solver = EndgameSolver::new();
+        for board in test_positions(14) {
+            let color = board.turn();
+            let result = solver.solve(&board, color);
+            assert!(result.score.abs() <= SCORE_MAX);
+            assert!(result.nodes > 0);
+
+            let best_move = result.best_move.unwrap();
+            let next = board.apply_move(&best_move).unwrap();
+            let child = solver.solve(&next, color.opponent());
+            assert_eq!(-child.score, result.score);
+        }
+    }
+
+    #[test]
+    fn test_solve_wld() {
+        let mut solver = EndgameSolver::new();
+        let mut outcomes = Vec::new();
+        for empties in [1, 4, 8, 12] {
+            for board in test_positions(empties) {
+                for color in [PlayerColor::Black, PlayerColor::White] {
+                    let exact = solver.solve(&board, color).score;
+                    let result = solver.solve_wld(&board, color);
+                    let expected = match exact.cmp(&0) {
+                        std::cmp::Ordering::Greater => GameOutcome::Win,
+                        std::cmp::Ordering::Equal => GameOutcome::Draw,
+                        std::cmp::Ordering::Less => GameOutcome::Loss,
+                    };
+                    assert_eq!(result.outcome, expected);
+                    outcomes.push(result.outcome);
+
+                    // 勝ち・引き分けを保証する手を打つと、相手は負け・引き分けになる
+                    if let Some(best_move) = result.best_move {
+                        let next = board.apply_move(&best_move).unwrap();
+                        let child = solver.solve(&next, color.opponent()).score;
+                        match result.outcome {
+                            GameOutcome::Win => assert!(child < 0),
+                            GameOutcome::Draw => assert_eq!(child, 0),
+                            GameOutcome::Loss => assert!(child > 0),
+                        }
+                    }
+                }
+            }
+        }
+        assert!(outcomes.contains(&GameOutcome::Win));
+        assert!(outcomes.contains(&GameOutcome::Loss));
+
+        // 引き分けの局面
+        let board = BitBoard::new_from_data(0x0000_0000_ffff_ffff, 0xffff_ffff_0000_0000, 0);
+        let result = solver.solve_wld(&board, PlayerColor::Black);
+        assert_eq!(result.outcome, GameOutcome::Draw);
+        assert_eq!(result.best_move, None);
+    }
+
+    #[test]
+    fn test_solve_window() {
+        let mut solver = EndgameSolver::new();
+        for board in test_positions(10) {
+            let color = board.turn();
+            let exact = solver.solve(&board, color).score;
+
+            let high = solver.solve_window(&board, color, exact - 1, exact + 1);
+            assert_eq!(high.score, exact);
+
+            // 窓の外の値は境界として正しい
+            let low = solver.solve_window(&board, color, exact + 1, exact + 3);
+            assert!(low.score <= exact + 1);
+            let up = solver.solve_window(&board, color, exact - 3, exact - 1);
+            assert!(up.score >= exact - 1);
+        }
+    }
+}
@@ -1,18 +1,67 @@
 use crate::reversi::common::*;
 
+#[derive(Clone, PartialEq, Eq, Debug)]
 pub struct EvalResult {
     pub value: i32,
     pub policy: Option<[i32; BOARD_SIZE * BOARD_SIZE]>,
 }
 
+/// 盤面の評価関数
+///
+/// 重みやキャッシュ、読み込んだモデルなどを持てるよう、インスタンスごとに評価する。
 pub trait Evaluator {
-    fn evaluate(board: &Squares, color: &PlayerColor) -> EvalResult;
+    /// `color`から見た盤面の評価
+    fn evaluate(&mut self, board: &Squares, color: &PlayerColor) -> EvalResult;
+
+    /// 複数の局面をまとめて評価する
+    ///
+    /// 既定では1局面ずつ評価する。
+    /// ニューラルネットワークのように、まとめて評価すると速い評価関数では上書きする。
+    fn evaluate_batch(&mut self, positions: &[(Squares, PlayerColor)]) -> Vec<EvalResult> {
+        positions
+            .iter()
+            .map(|(board, color)| self.evaluate(board, color))
+            .collect()
+    }
+}
+
+impl<E> Evaluator for &mut E
+where
+    E: Evaluator + ?Sized,
+{
+    fn evaluate(&mut self, board: &Squares, color: &PlayerColor) -> EvalResult {
+        (**self).evaluate(board, color)
+    }
+
+    fn evaluate_batch(&mut self, positions: &[(Squares, PlayerColor)]) -> Vec<EvalResult> {
+        (**self).evaluate_batch(positions)
+    }
 }
 
+impl<E> Evaluator for Box<E>
+where
+    E: Evaluator + ?Sized,
+{
+    fn evaluate(&mut self, board: &Squares, color: &PlayerColor) -> EvalResult {
+        (**self).evaluate(board, color)
+    }
+
+    fn evaluate_batch(&mut self, positions: &[(Squares, PlayerColor)]) -> Vec<EvalResult> {
+        (**self).evaluate_batch(positions)
+    }
+}
+
+#[derive(Clone, Copy, Default, Debug)]
 pub struct SimpleEvaluator {}
 
+impl SimpleEvaluator {
+    pub fn new() -> Self {
+        SimpleEvaluator {}
+    }
+}
+
 impl Evaluator for SimpleEvaluator {
-    fn evaluate(board: &Squares, color: &PlayerColor) -> EvalResult {
+    fn evaluate(&mut self, board: &Squares, color: &PlayerColor) -> EvalResult {
         simple_evaluate(board, color)
     }
 }
@@ -75,4 +124,34 @@ mod tests {
         let result3 = simple_evaluate(&squares, &PlayerColor::White);
         assert_eq!(result3.value, -42);
     }
+
+    #[test]
+    fn test_evaluate_batch() {
+        let board = BitBoard::new_initial()
+            .apply_move(&Move::new_position(PlayerColor::Black, Position(2, 3)))
+            .unwrap();
+        let positions = [
+            (*board.squares(), PlayerColor::Black),
+            (*board.squares(), PlayerColor::White),
+        ];
+        let mut evaluator = SimpleEvaluator::new();
+        let results = evaluator.evaluate_batch(&positions);
+        assert_eq!(results.len(), 2);
+        for ((squares, color), result) in positions.iter().zip(results) {
+            assert_eq!(result, evaluator.evaluate(squares, color));
+        }
+
+        // 参照やBoxでも同じように評価できる
+        fn evaluate_all<E: Evaluator>(
+            mut evaluator: E,
+            positions: &[(Squares, PlayerColor)],
+        ) -> Vec<EvalResult> {
+            evaluator.evaluate_batch(positions)
+        }
+        let boxed: Box<dyn Evaluator> = Box::new(SimpleEvaluator::new());
+        assert_eq!(
+            evaluate_all(&mut evaluator, &positions),
+            evaluate_all(boxed, &positions)
+        );
+    }
 }
@@ -0,0 +1,336 @@
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::Arc;
+use std::time::{Duration, Instant};
+
+use crate::{
+    board::{BitBoard, Board},
+    PlayerColor,
+};
+
+use super::mtdf::mtdf;
+use super::nega_alpha::{NegaAlpha, NegaAlphaEvaluationFunction};
+use super::search_result::SearchResult;
+
+/// 探索を外部から止めるためのフラグ
+///
+/// クローンしたフラグは同じ状態を共有するので、別スレッドに渡して[`StopFlag::stop`]を呼べる。
+#[derive(Clone, Default, Debug)]
+pub struct StopFlag(Arc<AtomicBool>);
+
+impl StopFlag {
+    pub fn new() -> Self {
+        Default::default()
+    }
+
+    pub fn stop(&self) {
+        self.0.store(true, Ordering::Relaxed);
+    }
+
+    pub fn is_stopped(&self) -> bool {
+        self.0.load(Ordering::Relaxed)
+    }
+
+    pub fn reset(&self) {
+        self.0.store(false, Ordering::Relaxed);
+    }
+}
+
+/// 探索の打ち切り条件
+///
+/// 時間は毎ノード確認すると遅いので、一定ノード数ごとに確認する。
+#[derive(Clone, Debug)]
+pub struct SearchControl {
+    stop_flag: StopFlag,
+    deadline: Option<Instant>,
+    node_limit: Option<u64>,
+}
+
+impl SearchControl {
+    const TIME_CHECK_INTERVAL: u64 = 1024;
+
+    pub fn new(stop_flag: StopFlag, deadline: Option<Instant>, node_limit: Option<u64>) -> Self {
+        SearchControl {
+            stop_flag,
+            deadline,
+            node_limit,
+        }
+    }
+
+    /// `nodes`ノード探索した時点で探索を止めるべきか
+    pub fn should_stop(&self, nodes: u64) -> bool {
+        if let Some(limit) = self.node_limit {
+            if nodes >= limit {
+                return true;
+            }
+        }
+        if nodes % Self::TIME_CHECK_INTERVAL != 0 {
+            return false;
+        }
+        if self.stop_flag.is_stopped() {
+            return true;
+        }
+        match self.deadline {
+            Some(deadline) => Instant::now() >= deadline,
+            None => false,
+        }
+    }
+}
+
+/// 反復深化の制限
+#[derive(Clone, Copy, Debug)]
+pub struct SearchLimits {
+    /// 最大の探索深さ
+    pub max_depth: usize,
+    /// 探索時間
+    pub time: Option<Duration>,
+    /// 探索ノード数
+    pub nodes: Option<u64>,
+}
+
+impl SearchLimits {
+    pub fn depth(max_depth: usize) -> Self {
+        SearchLimits {
+            max_depth,
+            time: None,
+            nodes: None,
+        }
+    }
+
+    pub fn time(time: Duration) -> Self {
+        SearchLimits {
+            max_depth: usize::MAX,
+            time: Some(time),
+            nodes: None,
+        }
+    }
+}
+
+/// 反復深化の各反復でのルートの探索方法
+#[derive(Clone, Copy, PartialEq, Eq, Debug)]
+pub enum RootSearch {
+    /// 全幅の窓でのアルファベータ探索
+    AlphaBeta,
+    /// 前の反復の評価値を予想値にしたMTD(f)
+    Mtdf,
+}
+
+/// [`NegaAlpha`]を深さ1から順に探索する反復深化
+///
+/// 前の反復の最善手から探索するので、置換表を持つ[`NegaAlpha`]を使うと効率がよい。
+pub struct IterativeDeepening<E>
+where
+    E: NegaAlphaEvaluationFunction,
+{
+    nega_alpha: NegaAlpha<E>,
+    stop_flag: StopFlag,
+    root_search: RootSearch,
+}
+
+impl<E> IterativeDeepening<E>
+where
+    E: NegaAlphaEvaluationFunction,
+{
+    pub fn new(nega_alpha: NegaAlpha<E>) -> Self {
+        IterativeDeepening {
+            nega_alpha,
+            stop_flag: StopFlag::new(),
+            root_search: RootSearch::AlphaBeta,
+        }
+    }
+
+    pub fn with_root_search(mut self, root_search: RootSearch) -> Self {
+        self.root_search = root_search;
+        self
+    }
+
+    pub fn root_search(&self) -> RootSearch {
+        self.root_search
+    }
+
+    /// 探索を止めるためのフラグ
+    ///
+    /// 探索の前に止めた場合も、次の探索はすぐに打ち切る。フラグは探索が終わると元に戻る。
+    pub fn stop_flag(&self) -> StopFlag {
+        self.stop_flag.clone()
+    }
+
+    pub fn nega_alpha(&self) -> &NegaAlpha<E> {
+        &self.nega_alpha
+    }
+
+    pub fn nega_alpha_mut(&mut self) -> &mut NegaAlpha<E> {
+        &mut self.nega_alpha
+    }
+
+    /// 制限に達するまで深さを1ずつ増やして探索する
+    ///
+    /// 結果の最善手・評価値・読み筋・深さは最後に完了した反復のもの(1回も完了しなければ最善手は`None`)。
+    /// ノード数と時間は中断した反復も含む。
+    pub fn search(
+        &mut self,
+        board: &BitBoard,
+        color: PlayerColor,
+        limits: &SearchLimits,
+    ) -> SearchResult {
+        let start = Instant::now();
+        let deadline = limits.time.map(|time| start + time);
+        self.nega_alpha.set_control(Some(SearchControl::new(
+            self.stop_flag.clone(),
+            deadline,
+            limits.nodes,
+        )));
+        self.nega_alpha.reset_nodes();
+
+        let mut result = SearchResult {
+            best_move: None,
+            score: 0,
+            pv: Vec::new(),
+            depth: 0,
+            nodes: 0,
+            elapsed: Duration::ZERO,
+        };
+        // パスは連続しないので、空きマスの2倍より長い手順はない
+        let max_depth = limits.max_depth.min(board.empty_count() as usize * 2 + 1);
+        for depth in 1..=max_depth {
+            let iteration = match self.root_search {
+                RootSearch::AlphaBeta => {
+                    self.nega_alpha
+                        .search_root(board, color, depth, result.best_move)
+                }
+                RootSearch::Mtdf => {
+                    let guess = result.score;
+                    mtdf(
+                        &mut self.nega_alpha,
+                        board,
+                        color,
+                        depth,
+                        guess,
+                        result.best_move,
+                    )
+                    .0
+                }
+            };
+            if self.nega_alpha.is_aborted() {
+                break;
+            }
+            result.best_move = iteration.best_move;
+            result.score = iteration.score;
+            result.pv = iteration.pv;
+            result.depth = depth;
+
+            if let Some(deadline) = deadline {
+                if Instant::now() >= deadline {
+                    break;
+                }
+            }
+        }
+
+        self.nega_alpha.set_control(None);
+        self.stop_flag.reset();
+        result.nodes = self.nega_alpha.nodes();
+        result.elapsed = start.elapsed();
+        result
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::ai::{ReplacementPolicy, SimpleNegaAlphaEvaluationFunction, TranspositionTable};
+
+    fn new_search() -> IterativeDeepening<SimpleNegaAlphaEvaluationFunction> {
+        let table = TranspositionTable::new(4, ReplacementPolicy::DepthPreferred);
+        IterativeDeepening::new(NegaAlpha::with_transposition_table(
+            SimpleNegaAlphaEvaluationFunction::new(),
+            table,
+        ))
+    }
+
+    #[test]
+    fn test_depth_limit() {
+        let board = BitBoard::new_initial();
+        let mut search = new_search();
+        let result = search.search(&board, PlayerColor::Black, &SearchLimits::depth(5));
+        assert_eq!(result.depth, 5);
+        assert!(board.apply_move(&result.best_move.unwrap()).is_some());
+
+        // 固定深さの探索と同じ値になる
+        let mut nega_alpha = NegaAlpha::new(SimpleNegaAlphaEvaluationFunction::new());
+        let expected = nega_alpha.search_root(&board, PlayerColor::Black, 5, None);
+        assert_eq!(result.score, expected.score);
+        assert_eq!(result.pv.first(), result.best_move.as_ref());
+    }
+
+    #[test]
+    fn test_mtdf() {
+        let board = BitBoard::new_initial();
+        let mut search = new_search();
+        let expected = search.search(&board, PlayerColor::Black, &SearchLimits::depth(6));
+
+        let mut search = new_search().with_root_search(RootSearch::Mtdf);
+        let result = search.search(&board, PlayerColor::Black, &SearchLimits::depth(6));
+        assert_eq!(result.depth, 6);
+        assert_eq!(result.score, expected.score);
+        assert!(board.apply_move(&result.best_move.unwrap()).is_some());
+    }
+
+    #[test]
+    fn test_node_limit() {
+        let board = BitBoard::new_initial();
+        let mut search = new_search();
+        let limits = SearchLimits {
+            max_depth: 64,
+            time: None,
+            nodes: Some(5000),
+        };
+        let result = search.search(&board, PlayerColor::Black, &limits);
+        assert!(result.depth >= 1);
+        assert!(result.depth < 64);
+        assert!(result.nodes <= 5000);
+        assert!(result.best_move.is_some());
+    }
+
+    #[test]
+    fn test_time_limit() {
+        let board = BitBoard::new_initial();
+        let mut search = new_search();
+        let result = search.search(
+            &board,
+            PlayerColor::Black,
+            &SearchLimits::time(Duration::from_millis(50)),
+        );
+        assert!(result.best_move.is_some());
+        assert!(result.elapsed < Duration::from_secs(5));
+    }
+
+    #[test]
+    fn test_stop_flag() {
+        let board = BitBoard::new_initial();
+        let mut search = new_search();
+        let stop_flag = search.stop_flag();
+        let handle = std::thread::spawn(move || {
+            std::thread::sleep(Duration::from_millis(50));
+            stop_flag.stop();
+        });
+        let result = search.search(&board, PlayerColor::Black, &SearchLimits::depth(64));
+        handle.join().unwrap();
+        assert!(result.depth < 64);
+        assert!(result.elapsed < Duration::from_secs(5));
+    }
+
+    #[test]
+    fn test_stop_before_search() {
+        // 探索の前に止めた指示も取りこぼさない
+        let board = BitBoard::new_initial();
+        let mut search = new_search();
+        let stop_flag = search.stop_flag();
+        stop_flag.stop();
+        let result = search.search(&board, PlayerColor::Black, &SearchLimits::depth(64));
+        assert!(result.depth < 64);
+        assert!(result.elapsed < Duration::from_secs(5));
+        // 探索が終わればフラグは元に戻る
+        assert!(!stop_flag.is_stopped());
+        let result = search.search(&board, PlayerColor::Black, &SearchLimits::depth(3));
+        assert_eq!(result.depth, 3);
+    }
+}
@@ -0,0 +1,236 @@
+use std::sync::Arc;
+use std::time::{Duration, Instant};
+
+use crate::{
+    board::{BitBoard, Board},
+    PlayerColor,
+};
+
+use super::iterative_deepening::{SearchControl, SearchLimits, StopFlag};
+use super::move_ordering::MoveOrdering;
+use super::nega_alpha::{NegaAlpha, NegaAlphaEvaluationFunction};
+use super::search_result::SearchResult;
+use super::transposition_table::SharedTranspositionTable;
+
+/// 置換表を共有して複数スレッドで探索するLazy SMP
+///
+/// 各スレッドは同じ局面を独立に反復深化し、置換表を通して結果を共有する。
+/// 奇数番目の補助スレッドは1手深く探索して、主スレッドより先に置換表を埋める。
+/// 結果は主スレッドの反復深化のもの。
+pub struct LazySmp<E>
+where
+    E: NegaAlphaEvaluationFunction + Clone + Send,
+{
+    eval: E,
+    table: Arc<SharedTranspositionTable>,
+    ordering: MoveOrdering,
+    threads: usize,
+    stop_flag: StopFlag,
+    thread_nodes: Vec<u64>,
+}
+
+impl<E> LazySmp<E>
+where
+    E: NegaAlphaEvaluationFunction + Clone + Send,
+{
+    /// 1スレッドで探索する
+    ///
+    /// 評価関数は各スレッドでクローンして使う。
+    pub fn new(eval: E, table: SharedTranspositionTable) -> Self {
+        LazySmp {
+            eval,
+            table: Arc::new(table),
+            ordering: MoveOrdering::all(),
+            threads: 1,
+            stop_flag: StopFlag::new(),
+            thread_nodes: Vec::new(),
+        }
+    }
+
+    /// 探索するスレッド数を設定する(最低1)
+    pub fn with_threads(mut self, threads: usize) -> Self {
+        self.threads = threads.max(1);
+        self
+    }
+
+    pub fn threads(&self) -> usize {
+        self.threads
+    }
+
+    pub fn with_move_ordering(mut self, ordering: MoveOrdering) -> Self {
+        self.ordering = ordering;
+        self
+    }
+
+    pub fn transposition_table(&self) -> &SharedTranspositionTable {
+        &self.table
+    }
+
+    /// 探索を止めるためのフラグ
+    ///
+    /// 探索の前に止めた場合も、次の探索はすぐに打ち切る。フラグは探索が終わると元に戻る。
+    pub fn stop_flag(&self) -> StopFlag {
+        self.stop_flag.clone()
+    }
+
+    /// 直前の探索で各スレッドが探索したノード数(先頭が主スレッド)
+    pub fn thread_nodes(&self) -> &[u64] {
+        &self.thread_nodes
+    }
+
+    /// 制限に達するまで全スレッドで反復深化する
+    ///
+    /// ノード数の制限は各スレッドに適用する。
+    /// 結果のノード数は全スレッドの合計。
+    pub fn search(
+        &mut self,
+        board: &BitBoard,
+        color: PlayerColor,
+        limits: &SearchLimits,
+    ) -> SearchResult {
+        let start = Instant::now();
+        let deadline = limits.time.map(|time| start + time);
+        self.table.new_search();
+
+        let mut results = std::thread::scope(|s| {
+            let helpers = (1..self.threads)
+                .map(|id| {
+                    let mut worker = self.worker(deadline, limits.nodes);
+                    s.spawn(move || iterate(&mut worker, board, color, limits.max_depth, id % 2))
+                })
+                .collect::<Vec<_>>();
+
+            let mut worker = self.worker(deadline, limits.nodes);
+            let result = iterate(&mut worker, board, color, limits.max_depth, 0);
+            // 主スレッドが終われば補助スレッドの探索は不要
+            self.stop_flag.stop();
+            let mut results = vec![result];
+            results.extend(helpers.into_iter().map(|h| h.join().unwrap()));
+            results
+        });
+        self.stop_flag.reset();
+
+        self.thread_nodes = results.iter().map(|r| r.nodes).collect();
+        let mut result = results.swap_remove(0);
+        result.nodes = self.thread_nodes.iter().sum();
+        result.elapsed = start.elapsed();
+        result
+    }
+
+    fn worker(&self, deadline: Option<Instant>, node_limit: Option<u64>) -> NegaAlpha<E> {
+        let mut nega_alpha =
+            NegaAlpha::with_shared_transposition_table(self.eval.clone(), self.table.clone())
+                .with_move_ordering(self.ordering);
+        nega_alpha.set_control(Some(SearchControl::new(
+            self.stop_flag.clone(),
+            deadline,
+            node_limit,
+        )));
+        nega_alpha
+    }
+}
+
+/// 1スレッド分の反復深化
+///
+/// 深さ`1 + offset`から`max_depth + offset`まで探索し、最後に完了した反復の結果を返す。
+fn iterate<E>(
+    nega_alpha: &mut NegaAlpha<E>,
+    board: &BitBoard,
+    color: PlayerColor,
+    max_depth: usize,
+    offset: usize,
+) -> SearchResult
+where
+    E: NegaAlphaEvaluationFunction,
+{
+    let mut result = SearchResult {
+        best_move: None,
+        score: 0,
+        pv: Vec::new(),
+        depth: 0,
+        nodes: 0,
+        elapsed: Duration::ZERO,
+    };
+    // パスは連続しないので、空きマスの2倍より長い手順はない
+    let max_depth = max_depth.min(board.empty_count() as usize * 2 + 1);
+    for depth in 1..=max_depth {
+        let iteration = nega_alpha.search_root(board, color, depth + offset, result.best_move);
+        if nega_alpha.is_aborted() {
+            break;
+        }
+        result.best_move = iteration.best_move;
+        result.score = iteration.score;
+        result.pv = iteration.pv;
+        result.depth = depth + offset;
+    }
+    result.nodes = nega_alpha.nodes();
+    result
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::ai::{
+        IterativeDeepening, ReplacementPolicy, SimpleNegaAlphaEvaluationFunction,
+        TranspositionTable,
+    };
+
+    fn new_search(threads: usize) -> LazySmp<SimpleNegaAlphaEvaluationFunction> {
+        let table = SharedTranspositionTable::new(4, ReplacementPolicy::DepthPreferred);
+        LazySmp::new(SimpleNegaAlphaEvaluationFunction::new(), table).with_threads(threads)
+    }
+
+    #[test]
+    fn test_threads() {
+        let board = BitBoard::new_initial();
+        let table = TranspositionTable::new(4, ReplacementPolicy::DepthPreferred);
+        let mut single = IterativeDeepening::new(
+            NegaAlpha::with_transposition_table(SimpleNegaAlphaEvaluationFunction::new(), table)
+                .with_move_ordering(MoveOrdering::all()),
+        );
+        let expected = single.search(&board, PlayerColor::Black, &SearchLimits::depth(6));
+
+        for threads in [1, 2, 4] {
+            let mut search = new_search(threads);
+            let result = search.search(&board, PlayerColor::Black, &SearchLimits::depth(6));
+            assert_eq!(result.depth, 6);
+            // 複数スレッドでは他のスレッドのより深い探索の結果を使うことがあるので値は一致しない
+            if threads == 1 {
+                assert_eq!(result.score, expected.score);
+            }
+            assert!(board.apply_move(&result.best_move.unwrap()).is_some());
+            assert_eq!(search.thread_nodes().len(), threads);
+            assert_eq!(result.nodes, search.thread_nodes().iter().sum::<u64>());
+        }
+    }
+
+    #[test]
+    fn test_time_limit() {
+        let board = BitBoard::new_initial();
+        let mut search = new_search(4);
+        let result = search.search(
+            &board,
+            PlayerColor::Black,
+            &SearchLimits::time(Duration::from_millis(50)),
+        );
+        assert!(result.best_move.is_some());
+        assert!(result.elapsed < Duration::from_secs(5));
+    }
+    #[test]
+    fn test_stop_flag() {
+        let board = BitBoard::new_initial();
+        let mut search = new_search(2);
+        // 主スレッドが補助スレッドを止めた後も、次の探索は最後まで読む
+        let result = search.search(&board, PlayerColor::Black, &SearchLimits::depth(4));
+        assert_eq!(result.depth, 4);
+        let result = search.search(&board, PlayerColor::Black, &SearchLimits::depth(4));
+        assert_eq!(result.depth, 4);
+
+        // 探索の前に止めた指示も取りこぼさない
+        let stop_flag = search.stop_flag();
+        stop_flag.stop();
+        let result = search.search(&board, PlayerColor::Black, &SearchLimits::depth(64));
+        assert!(result.depth < 64);
+        assert!(!stop_flag.is_stopped());
+    }
+}
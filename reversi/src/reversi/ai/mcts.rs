@@ -0,0 +1,372 @@
+use std::time::{Duration, Instant};
+
+use crate::{
+    board::{BitBoard, Board},
+    position_to_index, Move, PlayerColor,
+};
+
+use super::move_ordering::SQUARE_PRIORS;
+use super::random::XorShift;
+use super::search_position::SearchPosition;
+
+/// UCTの探索項の係数の既定値(√2)
+pub const DEFAULT_EXPLORATION: f64 = std::f64::consts::SQRT_2;
+
+/// ヒューリスティックなプレイアウトで、マスの良さに加える乱数の幅
+const HEURISTIC_NOISE: u64 = 64;
+
+/// 制限時間を確認する間隔(反復回数-1)
+const TIME_CHECK_MASK: u64 = 63;
+
+/// プレイアウトでの手の選び方
+#[derive(Clone, Copy, PartialEq, Eq, Debug)]
+pub enum Playout {
+    /// 合法手から一様に選ぶ
+    Random,
+    /// 隅やX打ちなどマスごとの良さに乱数を加えて、最も良い手を選ぶ
+    Heuristic,
+}
+
+/// MCTSの制限
+#[derive(Clone, Copy, Debug)]
+pub struct MctsLimits {
+    /// 最大の反復回数
+    pub iterations: u64,
+    /// 探索時間
+    pub time: Option<Duration>,
+}
+
+impl MctsLimits {
+    pub fn iterations(iterations: u64) -> Self {
+        MctsLimits {
+            iterations,
+            time: None,
+        }
+    }
+
+    pub fn time(time: Duration) -> Self {
+        MctsLimits {
+            iterations: u64::MAX,
+            time: Some(time),
+        }
+    }
+}
+
+/// ルートの手ごとの統計
+#[derive(Clone, Copy, PartialEq, Debug)]
+pub struct MctsMoveStats {
+    pub move_: Move,
+    /// 訪問回数
+    pub visits: u64,
+    /// 手を指した側から見た勝率(引き分けは0.5勝)
+    pub win_rate: f64,
+}
+
+/// MCTSの結果
+#[derive(Clone, Debug)]
+pub struct MctsResult {
+    /// 最も訪問回数の多い手(終局している場合は`None`)
+    pub best_move: Option<Move>,
+    /// 最善手の勝率
+    pub win_rate: f64,
+    /// ルートの手ごとの統計(訪問回数の多い順)
+    pub moves: Vec<MctsMoveStats>,
+    /// 反復回数
+    pub iterations: u64,
+    /// 木のノード数
+    pub nodes: usize,
+    pub elapsed: Duration,
+}
+
+struct MctsNode {
+    /// このノードに至る手(ルートは`None`)
+    move_: Option<Move>,
+    children: Vec<usize>,
+    /// まだ展開していない手
+    untried: Vec<Move>,
+    visits: u64,
+    /// このノードに至る手を指した側の勝ち数
+    wins: f64,
+}
+
+impl MctsNode {
+    fn new(move_: Option<Move>, untried: Vec<Move>) -> Self {
+        MctsNode {
+            move_,
+            children: Vec::new(),
+            untried,
+            visits: 0,
+            wins: 0.0,
+        }
+    }
+
+    fn win_rate(&self) -> f64 {
+        if self.visits == 0 {
+            0.0
+        } else {
+            self.wins / self.visits as f64
+        }
+    }
+}
+
+/// UCTによるモンテカルロ木探索
+///
+/// 勝敗だけを評価に使い、評価関数は使わない。
+/// 木は探索ごとに作り直す。
+pub struct Mcts {
+    exploration: f64,
+    playout: Playout,
+    rng: XorShift,
+    nodes: Vec<MctsNode>,
+}
+
+impl Mcts {
+    pub fn new(playout: Playout, seed: u64) -> Self {
+        Mcts {
+            exploration: DEFAULT_EXPLORATION,
+            playout,
+            rng: XorShift::new(seed),
+            nodes: Vec::new(),
+        }
+    }
+
+    /// UCTの探索項の係数を設定する(大きいほど訪問回数の少ない手を試す)
+    pub fn with_exploration(mut self, exploration: f64) -> Self {
+        self.exploration = exploration;
+        self
+    }
+
+    pub fn exploration(&self) -> f64 {
+        self.exploration
+    }
+
+    pub fn playout(&self) -> Playout {
+        self.playout
+    }
+
+    /// 制限に達するまで選択・展開・プレイアウト・逆伝播を繰り返す
+    pub fn search(
+        &mut self,
+        board: &BitBoard,
+        color: PlayerColor,
+        limits: &MctsLimits,
+    ) -> MctsResult {
+        let start = Instant::now();
+        let deadline = limits.time.map(|time| start + time);
+        let mut position = SearchPosition::new(board.clone(), color);
+        self.nodes.clear();
+        self.nodes
+            .push(MctsNode::new(None, untried_moves(&position)));
+
+        let mut iterations = 0;
+        while iterations < limits.iterations {
+            // 時間は毎回確認すると遅いので一定回数ごとに確認する
+            if iterations & TIME_CHECK_MASK == 0 {
+                if let Some(deadline) = deadline {
+                    if Instant::now() >= deadline {
+                        break;
+                    }
+                }
+            }
+            self.iterate(&mut position);
+            iterations += 1;
+        }
+
+        let mut moves = self.nodes[0]
+            .children
+            .iter()
+            .map(|&child| {
+                let node = &self.nodes[child];
+                MctsMoveStats {
+                    move_: node.move_.unwrap(),
+                    visits: node.visits,
+                    win_rate: node.win_rate(),
+                }
+            })
+            .collect::<Vec<_>>();
+        moves.sort_by_key(|stats| std::cmp::Reverse(stats.visits));
+        MctsResult {
+            best_move: moves.first().map(|stats| stats.move_),
+            win_rate: moves.first().map_or(0.0, |stats| stats.win_rate),
+            moves,
+            iterations,
+            nodes: self.nodes.len(),
+            elapsed: start.elapsed(),
+        }
+    }
+
+    /// 1回分の選択・展開・プレイアウト・逆伝播
+    ///
+    /// `position`は終了後に元の局面に戻る。
+    fn iterate(&mut self, position: &mut SearchPosition) {
+        let root_ply = position.ply();
+
+        // 選択: 全ての手を展開したノードではUCTの値が最大の子に進む
+        let mut path = vec![0];
+        let mut current = 0;
+        while self.nodes[current].untried.is_empty() && !self.nodes[current].children.is_empty() {
+            current = self.select_child(current);
+            position.push(&self.nodes[current].move_.unwrap());
+            path.push(current);
+        }
+
+        // 展開: 未展開の手を1つ選んで子を作る
+        if !self.nodes[current].untried.is_empty() {
+            let untried = &mut self.nodes[current].untried;
+            let i = self.rng.below(untried.len() as u64) as usize;
+            let move_ = untried.swap_remove(i);
+            position.push(&move_);
+            let child = self.nodes.len();
+            self.nodes
+                .push(MctsNode::new(Some(move_), untried_moves(position)));
+            self.nodes[current].children.push(child);
+            path.push(child);
+        }
+
+        // プレイアウト: 終局まで指して勝者を決める
+        let leaf_color = position.color();
+        let leaf_ply = position.ply();
+        while !position.is_game_over() {
+            let move_ = self.playout_move(position);
+            position.push(&move_);
+        }
+        let winner = winner(position.board());
+        while position.ply() > leaf_ply {
+            position.pop();
+        }
+
+        // 逆伝播: 各ノードに至る手を指した側から見た勝ち数を加える
+        // 葉に至る手は葉の手番の相手が指したもの
+        let mut mover = leaf_color.opponent();
+        for &node in path.iter().rev() {
+            let node = &mut self.nodes[node];
+            node.visits += 1;
+            node.wins += match winner {
+                Some(color) if color == mover => 1.0,
+                Some(_) => 0.0,
+                None => 0.5,
+            };
+            mover = mover.opponent();
+        }
+        while position.ply() > root_ply {
+            position.pop();
+        }
+    }
+
+    /// UCTの値が最大の子
+    fn select_child(&self, parent: usize) -> usize {
+        let parent = &self.nodes[parent];
+        let log_visits = (parent.visits as f64).ln();
+        let uct = |child: usize| {
+            let node = &self.nodes[child];
+            node.win_rate() + self.exploration * (log_visits / node.visits as f64).sqrt()
+        };
+        *parent
+            .children
+            .iter()
+            .max_by(|&&a, &&b| uct(a).total_cmp(&uct(b)))
+            .unwrap()
+    }
+
+    fn playout_move(&mut self, position: &SearchPosition) -> Move {
+        let moves = position.next_moves();
+        match self.playout {
+            Playout::Random => moves[self.rng.below(moves.len() as u64) as usize],
+            Playout::Heuristic => *moves
+                .iter()
+                .max_by_key(|move_| {
+                    let prior = match move_ {
+                        Move::Position(_, position) => SQUARE_PRIORS[position_to_index(position)],
+                        Move::Pass(_) => 0,
+                    };
+                    prior as i64 + self.rng.below(HEURISTIC_NOISE) as i64
+                })
+                .unwrap(),
+        }
+    }
+}
+
+/// 展開する手の一覧(終局していれば空)
+fn untried_moves(position: &SearchPosition) -> Vec<Move> {
+    if position.is_game_over() {
+        Vec::new()
+    } else {
+        position.next_moves()
+    }
+}
+
+/// 勝者(引き分けなら`None`)
+fn winner(board: &BitBoard) -> Option<PlayerColor> {
+    let black = board.black_count();
+    let white = board.white_count();
+    match black.cmp(&white) {
+        std::cmp::Ordering::Greater => Some(PlayerColor::Black),
+        std::cmp::Ordering::Less => Some(PlayerColor::White),
+        std::cmp::Ordering::Equal => None,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{Position, Square, Squares, BOARD_SIZE};
+
+    #[test]
+    fn test_iterations() {
+        let board = BitBoard::new_initial();
+        for playout in [Playout::Random, Playout::Heuristic] {
+            let mut mcts = Mcts::new(playout, 1);
+            let result = mcts.search(&board, PlayerColor::Black, &MctsLimits::iterations(500));
+            assert_eq!(result.iterations, 500);
+            assert!(board.apply_move(&result.best_move.unwrap()).is_some());
+            assert_eq!(result.moves.len(), 4);
+            assert_eq!(result.moves.iter().map(|m| m.visits).sum::<u64>(), 500);
+            assert!(result.moves.windows(2).all(|w| w[0].visits >= w[1].visits));
+            assert!((0.0..=1.0).contains(&result.win_rate));
+            assert_eq!(result.nodes, 501);
+        }
+    }
+
+    #[test]
+    fn test_same_seed_same_result() {
+        let board = BitBoard::new_initial();
+        let limits = MctsLimits::iterations(300);
+        let a = Mcts::new(Playout::Random, 7).search(&board, PlayerColor::Black, &limits);
+        let b = Mcts::new(Playout::Random, 7).search(&board, PlayerColor::Black, &limits);
+        assert_eq!(a.moves, b.moves);
+    }
+
+    #[test]
+    fn test_time_limit() {
+        let board = BitBoard::new_initial();
+        let mut mcts = Mcts::new(Playout::Random, 1);
+        let result = mcts.search(
+            &board,
+            PlayerColor::Black,
+            &MctsLimits::time(Duration::from_millis(50)),
+        );
+        assert!(result.iterations > 0);
+        assert!(result.best_move.is_some());
+        assert!(result.elapsed < Duration::from_secs(5));
+    }
+
+    #[test]
+    fn test_finds_winning_move() {
+        // 黒がh1に置けば白石が全て黒になって勝つ
+        let mut squares: Squares = [Square::Empty; BOARD_SIZE * BOARD_SIZE];
+        squares[0] = Square::Black;
+        for square in squares.iter_mut().take(7).skip(1) {
+            *square = Square::White;
+        }
+        squares[9] = Square::White;
+        squares[18] = Square::Black;
+        let board = BitBoard::new(&squares, 0);
+        let mut mcts = Mcts::new(Playout::Random, 1);
+        let result = mcts.search(&board, PlayerColor::Black, &MctsLimits::iterations(2000));
+        assert_eq!(
+            result.best_move,
+            Some(Move::new_position(PlayerColor::Black, Position(0, 7)))
+        );
+        assert!(result.win_rate > 0.9);
+    }
+}
@@ -0,0 +1,337 @@
+use crate::{
+    board::{flip_data, movable_position},
+    position_to_index, Move, PlayerColor, BOARD_SIZE,
+};
+
+use super::search_position::SearchPosition;
+
+const SQUARE_COUNT: usize = BOARD_SIZE * BOARD_SIZE;
+
+/// マスごとの事前の良さ(隅は良く、隅の隣は悪い)
+#[rustfmt::skip]
+pub(crate) const SQUARE_PRIORS: [i32; SQUARE_COUNT] = [
+     64, -32,  8,  4,  4,  8, -32,  64,
+    -32, -64, -8, -4, -4, -8, -64, -32,
+      8,  -8,  4,  0,  0,  4,  -8,   8,
+      4,  -4,  0,  0,  0,  0,  -4,   4,
+      4,  -4,  0,  0,  0,  0,  -4,   4,
+      8,  -8,  4,  0,  0,  4,  -8,   8,
+    -32, -64, -8, -4, -4, -8, -64, -32,
+     64, -32,  8,  4,  4,  8, -32,  64,
+];
+
+/// 相手の着手可能数1つあたりの重み
+const MOBILITY_WEIGHT: i32 = 16;
+/// 浅い探索の評価値1あたりの重み
+const SHALLOW_SEARCH_WEIGHT: i32 = 8;
+/// ヒストリーの値の上限(並べ替えでの重みは最大64)
+const HISTORY_MAX: u32 = 1 << 16;
+
+/// 手の並べ替えに使う情報
+///
+/// 有効にした情報の重み付きの和で並べ替える。
+/// ただし置換表の最善手とキラー手は常に先頭に置く。
+#[derive(Clone, Copy, PartialEq, Eq, Debug)]
+pub struct MoveOrdering {
+    /// 置換表に保存された最善手
+    pub transposition_table: bool,
+    /// 着手後の相手の着手可能数(少ないほど良い)
+    pub mobility: bool,
+    /// 隅やX打ちなどマスごとの事前の良さ
+    pub square_prior: bool,
+    /// 同じ手数でbeta cutを起こした手
+    pub killer: bool,
+    /// これまでにbeta cutを起こした回数(深い探索ほど重い)
+    pub history: bool,
+    /// 浅い探索の深さ(0なら使わない)
+    pub shallow_search_depth: usize,
+}
+
+impl MoveOrdering {
+    /// 並べ替えをしない(合法手の生成順)
+    pub fn none() -> Self {
+        MoveOrdering {
+            transposition_table: false,
+            mobility: false,
+            square_prior: false,
+            killer: false,
+            history: false,
+            shallow_search_depth: 0,
+        }
+    }
+
+    /// 全ての情報を使う
+    pub fn all() -> Self {
+        MoveOrdering {
+            transposition_table: true,
+            mobility: true,
+            square_prior: true,
+            killer: true,
+            history: true,
+            shallow_search_depth: 2,
+        }
+    }
+
+    /// 残り深さ`depth`のノードで浅い探索を使うか
+    ///
+    /// 浅い探索のノード数に見合うだけ枝刈りが増えるよう、十分深いノードでのみ使う。
+    pub fn use_shallow_search(&self, depth: usize) -> bool {
+        self.shallow_search_depth > 0 && depth >= self.shallow_search_depth + 4
+    }
+}
+
+impl Default for MoveOrdering {
+    /// 置換表の最善手だけを使う
+    fn default() -> Self {
+        MoveOrdering {
+            transposition_table: true,
+            ..MoveOrdering::none()
+        }
+    }
+}
+
+/// 探索中に手の並べ替えを行う
+///
+/// キラー手とヒストリーは探索をまたいで保持する。
+#[derive(Clone, Debug)]
+pub struct MoveOrderer {
+    ordering: MoveOrdering,
+    killers: Vec<[Option<Move>; 2]>,
+    history: [[u32; SQUARE_COUNT]; 2],
+}
+
+impl MoveOrderer {
+    pub fn new(ordering: MoveOrdering) -> Self {
+        MoveOrderer {
+            ordering,
+            killers: Vec::new(),
+            history: [[0; SQUARE_COUNT]; 2],
+        }
+    }
+
+    pub fn ordering(&self) -> &MoveOrdering {
+        &self.ordering
+    }
+
+    /// キラー手とヒストリーを消去する
+    pub fn clear(&mut self) {
+        self.killers.clear();
+        self.history = [[0; SQUARE_COUNT]; 2];
+    }
+
+    /// 新しい探索を始める
+    ///
+    /// 古い探索のヒストリーの影響を弱めるため半分にする。
+    pub fn new_search(&mut self) {
+        self.history.iter_mut().flatten().for_each(|h| *h /= 2);
+    }
+
+    /// `moves`を良さそうな順に並べ替える
+    ///
+    /// `shallow_scores`は`moves`と同じ順の浅い探索の評価値。
+    pub fn order(
+        &self,
+        position: &SearchPosition,
+        moves: &mut [Move],
+        tt_move: Option<Move>,
+        shallow_scores: Option<&[i32]>,
+    ) {
+        if moves.len() < 2 {
+            return;
+        }
+        let mut scored = moves
+            .iter()
+            .enumerate()
+            .map(|(i, move_)| {
+                let shallow_score = shallow_scores.map(|scores| scores[i]);
+                (self.score(position, move_, tt_move, shallow_score), *move_)
+            })
+            .collect::<Vec<_>>();
+        // 同じ値なら生成順を保つ
+        scored.sort_by_key(|(score, _)| std::cmp::Reverse(*score));
+        for (move_, (_, scored_move)) in moves.iter_mut().zip(scored) {
+            *move_ = scored_move;
+        }
+    }
+
+    /// beta cutを起こした手を記録する
+    pub fn update_cutoff(&mut self, move_: &Move, ply: usize, depth: usize) {
+        let (color, index) = match move_ {
+            Move::Position(color, position) => (*color, position_to_index(position)),
+            Move::Pass(_) => return,
+        };
+        if self.ordering.killer {
+            if self.killers.len() <= ply {
+                self.killers.resize(ply + 1, [None, None]);
+            }
+            let killers = &mut self.killers[ply];
+            if killers[0] != Some(*move_) {
+                killers[1] = killers[0];
+                killers[0] = Some(*move_);
+            }
+        }
+        if self.ordering.history {
+            let h = &mut self.history[color_index(color)][index];
+            *h = (*h + (depth * depth) as u32).min(HISTORY_MAX);
+        }
+    }
+
+    fn score(
+        &self,
+        position: &SearchPosition,
+        move_: &Move,
+        tt_move: Option<Move>,
+        shallow_score: Option<i32>,
+    ) -> i32 {
+        if self.ordering.transposition_table && tt_move == Some(*move_) {
+            return i32::MAX;
+        }
+        if self.ordering.killer {
+            if let Some(killers) = self.killers.get(position.ply()) {
+                if killers[0] == Some(*move_) {
+                    return i32::MAX - 1;
+                }
+                if killers[1] == Some(*move_) {
+                    return i32::MAX - 2;
+                }
+            }
+        }
+
+        let (color, index) = match move_ {
+            Move::Position(color, position) => (*color, position_to_index(position)),
+            Move::Pass(_) => return 0,
+        };
+        let mut score = 0;
+        if let Some(shallow_score) = shallow_score {
+            score += shallow_score * SHALLOW_SEARCH_WEIGHT;
+        }
+        if self.ordering.mobility {
+            score -= opponent_mobility(position, color, index) as i32 * MOBILITY_WEIGHT;
+        }
+        if self.ordering.square_prior {
+            score += SQUARE_PRIORS[index];
+        }
+        if self.ordering.history {
+            score += (self.history[color_index(color)][index] >> 10) as i32;
+        }
+        score
+    }
+}
+
+fn color_index(color: PlayerColor) -> usize {
+    match color {
+        PlayerColor::Black => 0,
+        PlayerColor::White => 1,
+    }
+}
+
+/// `color`が`index`に着手した後の相手の着手可能数
+fn opponent_mobility(position: &SearchPosition, color: PlayerColor, index: usize) -> u32 {
+    let board = position.board();
+    let (player, opponent) = match color {
+        PlayerColor::Black => (board.black(), board.white()),
+        PlayerColor::White => (board.white(), board.black()),
+    };
+    let bit = 1 << index;
+    let flips = flip_data(player, opponent, bit);
+    movable_position(opponent ^ flips, player | flips | bit).count_ones()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::board::BitBoard;
+    use crate::{Position, Square};
+
+    fn new_position(moves: &[Move]) -> SearchPosition {
+        let mut position = SearchPosition::new(BitBoard::new_initial(), PlayerColor::Black);
+        for m in moves {
+            assert!(position.push(m));
+        }
+        position
+    }
+
+    #[test]
+    fn test_none_keeps_order() {
+        let position = new_position(&[]);
+        let mut moves = position.next_moves();
+        let expected = moves.clone();
+        let orderer = MoveOrderer::new(MoveOrdering::none());
+        orderer.order(&position, &mut moves, Some(expected[2]), None);
+        assert_eq!(moves, expected);
+    }
+
+    #[test]
+    fn test_tt_move_and_killers_first() {
+        let position = new_position(&[]);
+        let mut moves = position.next_moves();
+        let tt_move = moves[3];
+        let killer = moves[1];
+
+        let mut orderer = MoveOrderer::new(MoveOrdering::all());
+        orderer.update_cutoff(&killer, 0, 4);
+        orderer.order(&position, &mut moves, Some(tt_move), None);
+        assert_eq!(moves[0], tt_move);
+        assert_eq!(moves[1], killer);
+
+        // キラー手は手数ごとに記録する
+        let mut orderer = MoveOrderer::new(MoveOrdering {
+            killer: true,
+            ..MoveOrdering::none()
+        });
+        orderer.update_cutoff(&killer, 1, 4);
+        let mut moves = position.next_moves();
+        orderer.order(&position, &mut moves, None, None);
+        assert_eq!(moves, position.next_moves());
+    }
+
+    #[test]
+    fn test_square_prior() {
+        // 黒はb2(X打ち)とf4に置ける
+        let mut squares = [Square::Empty; SQUARE_COUNT];
+        squares[position_to_index(&Position(3, 3))] = Square::Black;
+        squares[position_to_index(&Position(2, 2))] = Square::White;
+        squares[position_to_index(&Position(3, 4))] = Square::White;
+        let board = BitBoard::new(&squares, 0);
+        let position = SearchPosition::new(board, PlayerColor::Black);
+        let mut moves = position.next_moves();
+        assert_eq!(
+            moves,
+            vec![
+                Move::new_position(PlayerColor::Black, Position(1, 1)),
+                Move::new_position(PlayerColor::Black, Position(3, 5)),
+            ]
+        );
+
+        let orderer = MoveOrderer::new(MoveOrdering {
+            square_prior: true,
+            ..MoveOrdering::none()
+        });
+        orderer.order(&position, &mut moves, None, None);
+        assert_eq!(
+            moves[0],
+            Move::new_position(PlayerColor::Black, Position(3, 5))
+        );
+    }
+
+    #[test]
+    fn test_mobility() {
+        let position = new_position(&[Move::new_position(PlayerColor::Black, Position(2, 3))]);
+        let mut moves = position.next_moves();
+        let orderer = MoveOrderer::new(MoveOrdering {
+            mobility: true,
+            ..MoveOrdering::none()
+        });
+        orderer.order(&position, &mut moves, None, None);
+
+        let mobilities = moves
+            .iter()
+            .map(|m| {
+                let mut p = position.clone();
+                p.push(m);
+                p.next_moves().len()
+            })
+            .collect::<Vec<_>>();
+        assert!(mobilities.windows(2).all(|w| w[0] <= w[1]));
+    }
+}
@@ -0,0 +1,197 @@
+use std::time::Instant;
+
+use crate::{
+    board::{BitBoard, Board},
+    Move, PlayerColor,
+};
+
+use super::nega_alpha::{NegaAlpha, NegaAlphaEvaluationFunction};
+use super::search_result::SearchResult;
+
+/// MTD(f)
+///
+/// null windowの探索を繰り返して評価値の上限と下限を狭めていく。
+/// 同じ局面を何度も探索するので、置換表を持つ[`NegaAlpha`]を使う。
+pub struct Mtdf<E>
+where
+    E: NegaAlphaEvaluationFunction,
+{
+    nega_alpha: NegaAlpha<E>,
+    passes: usize,
+}
+
+impl<E> Mtdf<E>
+where
+    E: NegaAlphaEvaluationFunction,
+{
+    pub fn new(nega_alpha: NegaAlpha<E>) -> Self {
+        Mtdf {
+            nega_alpha,
+            passes: 0,
+        }
+    }
+
+    pub fn nega_alpha(&self) -> &NegaAlpha<E> {
+        &self.nega_alpha
+    }
+
+    pub fn nega_alpha_mut(&mut self) -> &mut NegaAlpha<E> {
+        &mut self.nega_alpha
+    }
+
+    /// 直前の探索でnull window探索を行った回数
+    pub fn passes(&self) -> usize {
+        self.passes
+    }
+
+    /// 評価値の予想`first_guess`から探索を始める
+    ///
+    /// 予想が真の値に近いほど探索の回数が少なくて済む。
+    pub fn search(
+        &mut self,
+        board: &BitBoard,
+        color: PlayerColor,
+        depth: usize,
+        first_guess: i32,
+    ) -> SearchResult {
+        let (result, passes) = mtdf(&mut self.nega_alpha, board, color, depth, first_guess, None);
+        self.passes = passes;
+        result
+    }
+}
+
+/// MTD(f)でルートを探索し、結果とnull window探索の回数を返す
+///
+/// 探索が打ち切られた場合、結果は意味を持たないので[`NegaAlpha::is_aborted`]で確認すること。
+pub(crate) fn mtdf<E>(
+    nega_alpha: &mut NegaAlpha<E>,
+    board: &BitBoard,
+    color: PlayerColor,
+    depth: usize,
+    first_guess: i32,
+    first_move: Option<Move>,
+) -> (SearchResult, usize)
+where
+    E: NegaAlphaEvaluationFunction,
+{
+    let start = Instant::now();
+    let nodes = nega_alpha.nodes();
+    let depth = depth.max(1);
+
+    let mut score = first_guess;
+    let mut lower = i32::MIN + 1;
+    let mut upper = i32::MAX;
+    let mut best_move = first_move;
+    let mut passes = 0;
+    while lower < upper {
+        let beta = if score == lower { score + 1 } else { score };
+        let result = nega_alpha.search_root_window(board, color, depth, beta - 1, beta, best_move);
+        passes += 1;
+        if nega_alpha.is_aborted() {
+            break;
+        }
+        score = result.score;
+        if score < beta {
+            upper = score;
+        } else {
+            // 下限が上がったときの手が最善手
+            lower = score;
+            best_move = result.best_move;
+        }
+    }
+
+    // 最善手の後は置換表から読み筋を復元する
+    let pv = match best_move {
+        Some(move_) => {
+            let mut next = board.clone();
+            let mut pv = vec![move_];
+            if next.make_move(&move_).is_some() {
+                pv.extend(nega_alpha.principal_variation(&next, color.opponent(), depth - 1));
+            }
+            pv
+        }
+        None => Vec::new(),
+    };
+    let result = SearchResult {
+        best_move,
+        score,
+        pv,
+        depth,
+        nodes: nega_alpha.nodes() - nodes,
+        elapsed: start.elapsed(),
+    };
+    (result, passes)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::ai::probcut::tests::test_positions;
+    use crate::ai::{
+        NegaScout, ReplacementPolicy, SimpleNegaAlphaEvaluationFunction,
+        SimpleNegaScoutEvaluationFunction, TranspositionTable,
+    };
+    use crate::Position;
+
+    fn new_mtdf() -> Mtdf<SimpleNegaAlphaEvaluationFunction> {
+        let table = TranspositionTable::new(4, ReplacementPolicy::DepthPreferred);
+        Mtdf::new(NegaAlpha::with_transposition_table(
+            SimpleNegaAlphaEvaluationFunction::new(),
+            table,
+        ))
+    }
+
+    #[test]
+    fn test_same_score_as_nega_scout() {
+        let mut mtdf = new_mtdf();
+        let mut nega_scout = NegaScout::new(SimpleNegaScoutEvaluationFunction::new());
+
+        let mut board = BitBoard::new_initial();
+        for m in [
+            Move::new_position(PlayerColor::Black, Position(2, 3)),
+            Move::new_position(PlayerColor::White, Position(2, 2)),
+            Move::new_position(PlayerColor::Black, Position(3, 2)),
+        ] {
+            let color = board.turn();
+            for depth in 1..=5 {
+                let expected = nega_scout.search_board(&board, color, depth);
+                // 予想が外れていても同じ値に収束する
+                for guess in [0, expected.score, 100] {
+                    let result = mtdf.search(&board, color, depth, guess);
+                    assert_eq!(result.score, expected.score, "depth {}", depth);
+                    assert!(board.apply_move(&result.best_move.unwrap()).is_some());
+                    assert_eq!(result.pv.first(), result.best_move.as_ref());
+                    assert!(mtdf.passes() >= 1);
+                }
+            }
+            board.make_move(&m).unwrap();
+        }
+    }
+
+    #[test]
+    fn test_midgame_positions() {
+        let mut mtdf = new_mtdf();
+        let mut nega_scout = NegaScout::new(SimpleNegaScoutEvaluationFunction::new());
+        for (board, color) in test_positions() {
+            let expected = nega_scout.search_board(&board, color, 5);
+            let result = mtdf.search(&board, color, 5, 0);
+            assert_eq!(result.score, expected.score);
+            assert!(board.apply_move(&result.best_move.unwrap()).is_some());
+            assert!(mtdf.passes() >= 1);
+        }
+    }
+
+    #[test]
+    fn test_pv_is_legal() {
+        let mut mtdf = new_mtdf();
+        let board = BitBoard::new_initial();
+        let result = mtdf.search(&board, PlayerColor::Black, 6, 0);
+        assert!(!result.pv.is_empty());
+        assert!(result.pv.len() <= 6);
+
+        let mut b = board.clone();
+        for m in &result.pv {
+            assert!(b.make_move(m).is_some());
+        }
+    }
+}
@@ -1,9 +1,21 @@
+use std::sync::Arc;
+use std::time::Instant;
+
 use crate::{
     board::{BitBoard, Board},
-    Move, PlayerColor, Square, Squares,
+    Move, PlayerColor,
 };
 
+use super::evaluator::{simple_evaluate, Evaluator};
+use super::iterative_deepening::SearchControl;
+use super::move_ordering::{MoveOrderer, MoveOrdering};
 use super::node::Node;
+use super::probcut::ProbCut;
+use super::search_position::SearchPosition;
+use super::search_result::{update_pv, SearchResult};
+use super::transposition_table::{
+    Bound, SharedTranspositionTable, TranspositionEntry, TranspositionTable,
+};
 
 pub struct NegaAlphaNode {
     pub board: BitBoard,
@@ -64,9 +76,11 @@ impl Node for NegaAlphaNode {
 }
 
 pub trait NegaAlphaEvaluationFunction {
-    fn evaluate(&mut self, node: &NegaAlphaNode) -> i32;
+    /// `color`から見た盤面の評価値
+    fn evaluate(&mut self, board: &BitBoard, color: &PlayerColor) -> i32;
 }
 
+#[derive(Clone)]
 pub struct SimpleNegaAlphaEvaluationFunction {}
 
 impl SimpleNegaAlphaEvaluationFunction {
@@ -75,40 +89,93 @@ impl SimpleNegaAlphaEvaluationFunction {
     }
 }
 
+impl Default for SimpleNegaAlphaEvaluationFunction {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
 impl NegaAlphaEvaluationFunction for SimpleNegaAlphaEvaluationFunction {
-    fn evaluate(&mut self, node: &NegaAlphaNode) -> i32 {
-        simple_evaluate(&node.board.squares(), &node.color)
+    fn evaluate(&mut self, board: &BitBoard, color: &PlayerColor) -> i32 {
+        simple_evaluate(board.squares(), color).value
     }
 }
 
-fn simple_evaluate(board: &Squares, color: &PlayerColor) -> i32 {
-    let weight_table: [i32; 64] = [
-        30, -12, 0, -1, -1, 0, -12, 30, //
-        -12, -15, -3, -3, -3, -3, -15, -12, //
-        0, -3, 0, -1, -1, 0, -3, 0, //
-        -1, -3, -1, -1, -1, -1, -3, -1, //
-        -1, -3, -1, -1, -1, -1, -3, -1, //
-        0, -3, 0, -1, -1, 0, -3, 0, //
-        -12, -15, -3, -3, -3, -3, -15, -12, //
-        30, -12, 0, -1, -1, 0, -12, 30, //
-    ];
-    let value = board
-        .iter()
-        .zip(weight_table.iter())
-        .fold(0, |v, (s, w)| -> i32 {
-            let color = match color {
-                PlayerColor::Black => Square::Black,
-                PlayerColor::White => Square::White,
-            };
-            if *s == Square::Empty {
-                v
-            } else if *s == color {
-                v + *w
-            } else {
-                v - *w
-            }
-        });
-    value
+/// [`Evaluator`]をNegaAlpha用の評価関数として使うためのアダプタ
+///
+/// 評価関数を借りて使う場合は`&mut E`を渡す。
+#[derive(Clone, Default)]
+pub struct EvaluatorNegaAlphaEvaluationFunction<E>
+where
+    E: Evaluator,
+{
+    evaluator: E,
+}
+
+impl<E> EvaluatorNegaAlphaEvaluationFunction<E>
+where
+    E: Evaluator,
+{
+    pub fn new(evaluator: E) -> Self {
+        EvaluatorNegaAlphaEvaluationFunction { evaluator }
+    }
+
+    pub fn evaluator(&self) -> &E {
+        &self.evaluator
+    }
+
+    pub fn evaluator_mut(&mut self) -> &mut E {
+        &mut self.evaluator
+    }
+
+    pub fn into_evaluator(self) -> E {
+        self.evaluator
+    }
+}
+
+impl<E> NegaAlphaEvaluationFunction for EvaluatorNegaAlphaEvaluationFunction<E>
+where
+    E: Evaluator,
+{
+    fn evaluate(&mut self, board: &BitBoard, color: &PlayerColor) -> i32 {
+        self.evaluator.evaluate(board.squares(), color).value
+    }
+}
+
+/// NegaAlphaが使う置換表
+enum Table {
+    Local(TranspositionTable),
+    Shared(Arc<SharedTranspositionTable>),
+}
+
+impl Table {
+    fn probe(&mut self, hash: u64) -> Option<TranspositionEntry> {
+        match self {
+            Table::Local(table) => table.probe(hash).copied(),
+            Table::Shared(table) => table.probe(hash),
+        }
+    }
+
+    fn get(&self, hash: u64) -> Option<TranspositionEntry> {
+        match self {
+            Table::Local(table) => table.get(hash).copied(),
+            Table::Shared(table) => table.probe(hash),
+        }
+    }
+
+    fn store(
+        &mut self,
+        hash: u64,
+        depth: usize,
+        bound: Bound,
+        score: i32,
+        best_move: Option<&Move>,
+    ) {
+        match self {
+            Table::Local(table) => table.store(hash, depth, bound, score, best_move),
+            Table::Shared(table) => table.store(hash, depth, bound, score, best_move),
+        }
+    }
 }
 
 pub struct NegaAlpha<E>
@@ -116,6 +183,12 @@ where
     E: NegaAlphaEvaluationFunction,
 {
     eval: E,
+    transposition_table: Option<Table>,
+    control: Option<SearchControl>,
+    orderer: MoveOrderer,
+    probcut: Option<ProbCut>,
+    nodes: u64,
+    aborted: bool,
 }
 
 impl<E> NegaAlpha<E>
@@ -123,50 +196,542 @@ where
     E: NegaAlphaEvaluationFunction,
 {
     pub fn new(eval: E) -> Self {
-        NegaAlpha { eval }
+        NegaAlpha {
+            eval,
+            transposition_table: None,
+            control: None,
+            orderer: MoveOrderer::new(MoveOrdering::default()),
+            probcut: None,
+            nodes: 0,
+            aborted: false,
+        }
+    }
+
+    /// 置換表を使って探索する
+    pub fn with_transposition_table(eval: E, table: TranspositionTable) -> Self {
+        NegaAlpha {
+            eval,
+            transposition_table: Some(Table::Local(table)),
+            control: None,
+            orderer: MoveOrderer::new(MoveOrdering::default()),
+            probcut: None,
+            nodes: 0,
+            aborted: false,
+        }
+    }
+
+    /// 他のスレッドと共有する置換表を使って探索する
+    ///
+    /// 共有する置換表の[`SharedTranspositionTable::new_search`]は探索を始める側で呼ぶこと。
+    pub fn with_shared_transposition_table(eval: E, table: Arc<SharedTranspositionTable>) -> Self {
+        NegaAlpha {
+            eval,
+            transposition_table: Some(Table::Shared(table)),
+            control: None,
+            orderer: MoveOrderer::new(MoveOrdering::default()),
+            probcut: None,
+            nodes: 0,
+            aborted: false,
+        }
+    }
+
+    /// 手の並べ替え方を設定する(ノードを作らない探索のみ有効)
+    pub fn with_move_ordering(mut self, ordering: MoveOrdering) -> Self {
+        self.orderer = MoveOrderer::new(ordering);
+        self
+    }
+
+    /// Multi-ProbCutで枝刈りする(ノードを作らない探索のみ有効)
+    pub fn with_probcut(mut self, probcut: ProbCut) -> Self {
+        self.probcut = Some(probcut);
+        self
+    }
+
+    pub fn probcut(&self) -> Option<&ProbCut> {
+        self.probcut.as_ref()
+    }
+
+    pub fn probcut_mut(&mut self) -> Option<&mut ProbCut> {
+        self.probcut.as_mut()
+    }
+
+    pub fn move_orderer(&self) -> &MoveOrderer {
+        &self.orderer
+    }
+
+    pub fn move_orderer_mut(&mut self) -> &mut MoveOrderer {
+        &mut self.orderer
+    }
+
+    pub fn transposition_table(&self) -> Option<&TranspositionTable> {
+        match &self.transposition_table {
+            Some(Table::Local(table)) => Some(table),
+            _ => None,
+        }
+    }
+
+    pub fn transposition_table_mut(&mut self) -> Option<&mut TranspositionTable> {
+        match &mut self.transposition_table {
+            Some(Table::Local(table)) => Some(table),
+            _ => None,
+        }
+    }
+
+    pub fn shared_transposition_table(&self) -> Option<&Arc<SharedTranspositionTable>> {
+        match &self.transposition_table {
+            Some(Table::Shared(table)) => Some(table),
+            _ => None,
+        }
     }
 
-    pub fn search(&mut self, node: &mut NegaAlphaNode, depth: usize) -> i32 {
-        Self::nega_alpha(node, depth, i32::MIN + 1, i32::MAX, &mut self.eval)
+    /// 探索の打ち切り条件を設定する
+    ///
+    /// 打ち切られた探索の戻り値は意味を持たないので、[`NegaAlpha::is_aborted`]で確認すること。
+    pub fn set_control(&mut self, control: Option<SearchControl>) {
+        self.control = control;
+    }
+
+    /// 直前の探索が打ち切られたか
+    pub fn is_aborted(&self) -> bool {
+        self.aborted
+    }
+
+    /// これまでに探索したノード数
+    pub fn nodes(&self) -> u64 {
+        self.nodes
+    }
+
+    pub fn reset_nodes(&mut self) {
+        self.nodes = 0;
+    }
+
+    pub fn search(&mut self, node: &mut NegaAlphaNode, depth: usize) -> SearchResult {
+        let start = Instant::now();
+        let nodes = self.nodes;
+        self.begin_search();
+        let mut pv = Vec::new();
+        let score = self.nega_alpha(node, depth, 0, i32::MIN + 1, i32::MAX, &mut pv);
+        Self::result(score, pv, depth, self.nodes - nodes, start)
     }
 
     fn nega_alpha(
+        &mut self,
         node: &mut NegaAlphaNode,
         depth: usize,
+        ply: usize,
         alpha: i32,
         beta: i32,
-        eval: &mut E,
-    ) -> i32
-    where
-        E: NegaAlphaEvaluationFunction,
-    {
+        pv: &mut Vec<Move>,
+    ) -> i32 {
+        if self.count_node() {
+            return 0;
+        }
+
         if node.board.is_game_over() || depth == 0 {
-            let value = eval.evaluate(node);
+            let value = self.eval.evaluate(&node.board, &node.color);
             node.value = Some(value);
             value
         } else {
+            // ルートでは子ノードの値が必要なので置換表で打ち切らない
+            let hash = node.board.hash();
+            let (cutoff, tt_move) = self.probe(hash, node.color, depth, alpha, beta, ply > 0);
+            if let Some(value) = cutoff {
+                node.value = Some(value);
+                return value;
+            }
+
             node.expand();
+            if let Some(tt_move) = tt_move {
+                move_to_front(node.children_mut(), |child| child.last_move == tt_move);
+            }
 
             let mut alpha = alpha;
-            for child in node.children_mut().iter_mut() {
-                let v = -Self::nega_alpha(child, depth - 1, -beta, -alpha, eval);
+            let alpha_orig = alpha;
+            let mut best_move = None;
+            for child in node.children.iter_mut() {
+                let mut child_pv = Vec::new();
+                let v = -self.nega_alpha(child, depth - 1, ply + 1, -beta, -alpha, &mut child_pv);
+                if self.aborted {
+                    return 0;
+                }
                 if v > alpha {
                     alpha = v;
+                    best_move = Some(child.last_move);
+                    update_pv(pv, child.last_move, &child_pv);
                 }
                 if alpha >= beta {
                     break;
                 }
             }
 
+            self.store(hash, depth, alpha_orig, beta, alpha, best_move);
             node.value = Some(alpha);
             alpha
         }
     }
+
+    /// ノードを作らずに、`board`を複製した局面を書き換えながら探索する
+    pub fn search_board(
+        &mut self,
+        board: &BitBoard,
+        color: PlayerColor,
+        depth: usize,
+    ) -> SearchResult {
+        let mut position = SearchPosition::new(board.clone(), color);
+        self.search_position(&mut position, depth)
+    }
+
+    /// 現在の経路だけを保持して探索する
+    ///
+    /// `position`は探索後に元の局面に戻る。
+    pub fn search_position(&mut self, position: &mut SearchPosition, depth: usize) -> SearchResult {
+        let start = Instant::now();
+        let nodes = self.nodes;
+        self.begin_search();
+        let mut pv = Vec::new();
+        let score = self.nega_alpha_position(position, depth, i32::MIN + 1, i32::MAX, &mut pv);
+        Self::result(score, pv, depth, self.nodes - nodes, start)
+    }
+
+    /// ルートの評価値と最善手を求める
+    ///
+    /// 必ずいずれかの合法手を最善手として返す。
+    /// `first_move`があれば最初に探索し、なければ置換表の最善手から探索する。
+    pub fn search_root(
+        &mut self,
+        board: &BitBoard,
+        color: PlayerColor,
+        depth: usize,
+        first_move: Option<Move>,
+    ) -> SearchResult {
+        self.search_root_window(board, color, depth, i32::MIN + 1, i32::MAX, first_move)
+    }
+
+    /// 探索窓(alpha, beta)でルートを探索する
+    ///
+    /// 結果の値が窓の外なら、真の値はその値以下(alpha以下の場合)または以上(beta以上の場合)。
+    pub fn search_root_window(
+        &mut self,
+        board: &BitBoard,
+        color: PlayerColor,
+        depth: usize,
+        alpha: i32,
+        beta: i32,
+        first_move: Option<Move>,
+    ) -> SearchResult {
+        let start = Instant::now();
+        let nodes = self.nodes;
+        self.begin_search();
+        self.count_node();
+        let depth = depth.max(1);
+        let mut position = SearchPosition::new(board.clone(), color);
+        let hash = board.hash();
+        let (_, tt_move) = self.probe(hash, color, depth, alpha, beta, false);
+        let mut moves = position.next_moves();
+        self.order_moves(&mut position, &mut moves, tt_move, depth);
+        for first in [tt_move, first_move].iter().flatten() {
+            move_to_front(&mut moves, |move_| move_ == first);
+        }
+
+        let mut alpha = alpha;
+        let alpha_orig = alpha;
+        let mut best = i32::MIN + 1;
+        let mut best_move = None;
+        let mut pv = Vec::new();
+        for move_ in moves {
+            position.push(&move_);
+            let mut child_pv = Vec::new();
+            let v =
+                -self.nega_alpha_position(&mut position, depth - 1, -beta, -alpha, &mut child_pv);
+            position.pop();
+            if self.aborted {
+                break;
+            }
+            if v > best || best_move.is_none() {
+                best = v;
+                best_move = Some(move_);
+                update_pv(&mut pv, move_, &child_pv);
+            }
+            alpha = alpha.max(best);
+            if alpha >= beta {
+                self.orderer.update_cutoff(&move_, 0, depth);
+                break;
+            }
+        }
+
+        if !self.aborted {
+            self.store(hash, depth, alpha_orig, beta, best, best_move);
+        }
+        Self::result(best, pv, depth, self.nodes - nodes, start)
+    }
+
+    /// 置換表の最善手をたどって読み筋を求める(最大`max_len`手)
+    pub fn principal_variation(
+        &self,
+        board: &BitBoard,
+        color: PlayerColor,
+        max_len: usize,
+    ) -> Vec<Move> {
+        let table = match &self.transposition_table {
+            Some(table) => table,
+            None => return Vec::new(),
+        };
+        let mut position = SearchPosition::new(board.clone(), color);
+        let mut pv = Vec::new();
+        while pv.len() < max_len && !position.is_game_over() {
+            let move_ = match table.get(position.board().hash()) {
+                Some(entry) => entry.best_move(position.color()),
+                None => None,
+            };
+            match move_ {
+                Some(move_) if position.next_moves().contains(&move_) => {
+                    position.push(&move_);
+                    pv.push(move_);
+                }
+                _ => break,
+            }
+        }
+        pv
+    }
+
+    /// fail-softで探索する(窓の外の値も真の値の上限・下限として使える)
+    fn nega_alpha_position(
+        &mut self,
+        position: &mut SearchPosition,
+        depth: usize,
+        alpha: i32,
+        beta: i32,
+        pv: &mut Vec<Move>,
+    ) -> i32 {
+        if self.count_node() {
+            return 0;
+        }
+
+        if position.is_game_over() || depth == 0 {
+            return self.eval.evaluate(position.board(), &position.color());
+        }
+
+        let hash = position.board().hash();
+        let (cutoff, tt_move) = self.probe(hash, position.color(), depth, alpha, beta, true);
+        if let Some(value) = cutoff {
+            return value;
+        }
+
+        if let Some(value) = self.probcut_position(position, depth, alpha, beta) {
+            return value;
+        }
+
+        let mut moves = position.next_moves();
+        self.order_moves(position, &mut moves, tt_move, depth);
+
+        let mut alpha = alpha;
+        let alpha_orig = alpha;
+        let mut best = i32::MIN + 1;
+        let mut best_move = None;
+        for move_ in moves {
+            position.push(&move_);
+            let mut child_pv = Vec::new();
+            let v = -self.nega_alpha_position(position, depth - 1, -beta, -alpha, &mut child_pv);
+            position.pop();
+            if self.aborted {
+                return 0;
+            }
+            if v > best {
+                best = v;
+                if v > alpha {
+                    alpha = v;
+                    best_move = Some(move_);
+                    update_pv(pv, move_, &child_pv);
+                }
+            }
+            if alpha >= beta {
+                self.orderer.update_cutoff(&move_, position.ply(), depth);
+                break;
+            }
+        }
+
+        self.store(hash, depth, alpha_orig, beta, best, best_move);
+        best
+    }
+
+    /// 浅い探索の結果から、深い探索の値が窓の外になると予測できれば窓の端の値を返す
+    fn probcut_position(
+        &mut self,
+        position: &mut SearchPosition,
+        depth: usize,
+        alpha: i32,
+        beta: i32,
+    ) -> Option<i32> {
+        let probcut = self.probcut.as_ref()?;
+        let (r, t) = probcut.regression(depth, position.board().empty_count())?;
+        // 窓が無限の場合は予測できない
+        let limit = (i32::MAX / 2) as f64;
+
+        if beta < i32::MAX {
+            // 浅い探索でこの値以上なら、深い探索もbeta以上の可能性が高い
+            let bound = ((t * r.sigma + beta as f64 - r.b) / r.a).ceil();
+            if bound.abs() < limit {
+                let bound = bound as i32;
+                let v = self.nega_alpha_position(
+                    position,
+                    r.shallow_depth,
+                    bound - 1,
+                    bound,
+                    &mut Vec::new(),
+                );
+                if self.aborted {
+                    return None;
+                }
+                if v >= bound {
+                    return Some(beta);
+                }
+            }
+        }
+
+        if alpha > i32::MIN + 1 {
+            let bound = ((-t * r.sigma + alpha as f64 - r.b) / r.a).floor();
+            if bound.abs() < limit {
+                let bound = bound as i32;
+                let v = self.nega_alpha_position(
+                    position,
+                    r.shallow_depth,
+                    bound,
+                    bound + 1,
+                    &mut Vec::new(),
+                );
+                if self.aborted {
+                    return None;
+                }
+                if v <= bound {
+                    return Some(alpha);
+                }
+            }
+        }
+        None
+    }
+
+    /// 残り深さ`depth`のノードで手を並べ替える
+    fn order_moves(
+        &mut self,
+        position: &mut SearchPosition,
+        moves: &mut [Move],
+        tt_move: Option<Move>,
+        depth: usize,
+    ) {
+        let ordering = *self.orderer.ordering();
+        let shallow_scores = if moves.len() > 1 && ordering.use_shallow_search(depth) {
+            let mut scores = Vec::with_capacity(moves.len());
+            for move_ in moves.iter() {
+                position.push(move_);
+                let v = -self.nega_alpha_position(
+                    position,
+                    ordering.shallow_search_depth - 1,
+                    i32::MIN + 1,
+                    i32::MAX,
+                    &mut Vec::new(),
+                );
+                position.pop();
+                scores.push(v);
+            }
+            Some(scores)
+        } else {
+            None
+        };
+        self.orderer
+            .order(position, moves, tt_move, shallow_scores.as_deref());
+    }
+
+    fn result(score: i32, pv: Vec<Move>, depth: usize, nodes: u64, start: Instant) -> SearchResult {
+        SearchResult {
+            best_move: pv.first().copied(),
+            score,
+            pv,
+            depth,
+            nodes,
+            elapsed: start.elapsed(),
+        }
+    }
+
+    fn begin_search(&mut self) {
+        self.aborted = false;
+        self.orderer.new_search();
+        if let Some(Table::Local(table)) = &mut self.transposition_table {
+            table.new_search();
+        }
+    }
+
+    /// ノード数を数え、打ち切り条件を満たしていれば探索を中断する
+    fn count_node(&mut self) -> bool {
+        self.nodes += 1;
+        if !self.aborted {
+            if let Some(control) = &self.control {
+                self.aborted = control.should_stop(self.nodes);
+            }
+        }
+        self.aborted
+    }
+
+    /// 置換表を参照する
+    ///
+    /// 探索を打ち切れるならその値と、置換表に保存されていた最善手を返す。
+    fn probe(
+        &mut self,
+        hash: u64,
+        color: PlayerColor,
+        depth: usize,
+        alpha: i32,
+        beta: i32,
+        allow_cutoff: bool,
+    ) -> (Option<i32>, Option<Move>) {
+        let entry = match &mut self.transposition_table {
+            Some(table) => table.probe(hash),
+            None => None,
+        };
+        match entry {
+            Some(entry) => {
+                let cutoff = if allow_cutoff {
+                    entry.cutoff(depth, alpha, beta)
+                } else {
+                    None
+                };
+                (cutoff, entry.best_move(color))
+            }
+            None => (None, None),
+        }
+    }
+
+    fn store(
+        &mut self,
+        hash: u64,
+        depth: usize,
+        alpha: i32,
+        beta: i32,
+        value: i32,
+        best_move: Option<Move>,
+    ) {
+        if let Some(table) = &mut self.transposition_table {
+            let bound = if value <= alpha {
+                Bound::Upper
+            } else if value >= beta {
+                Bound::Lower
+            } else {
+                Bound::Exact
+            };
+            table.store(hash, depth, bound, value, best_move.as_ref());
+        }
+    }
+}
+
+/// 条件に合う最初の要素を先頭に移動する(他の要素の順序は変えない)
+fn move_to_front<T>(items: &mut [T], matches: impl Fn(&T) -> bool) {
+    if let Some(i) = items.iter().position(matches) {
+        items[..=i].rotate_right(1);
+    }
 }
 
 #[cfg(test)]
 mod tests {
-    use super::super::nega_max::*;
+    use super::super::transposition_table::ReplacementPolicy;
     use super::*;
 
     struct TestEvaluationFunction {
@@ -174,7 +739,7 @@ mod tests {
     }
 
     impl NegaAlphaEvaluationFunction for TestEvaluationFunction {
-        fn evaluate(&mut self, node: &NegaAlphaNode) -> i32 {
+        fn evaluate(&mut self, _board: &BitBoard, _color: &PlayerColor) -> i32 {
             self.param += 1;
             if self.param > 10 {
                 self.param = 0;
@@ -189,25 +754,9 @@ mod tests {
         }
     }
 
-    struct TestEvaluationFunction2 {
-        param: i32,
-    }
-
-    impl NegaMaxEvaluationFunction for TestEvaluationFunction2 {
-        fn evaluate(&mut self, node: &NegaMaxNode) -> i32 {
-            self.param += 1;
-            if self.param > 10 {
-                self.param = 0;
-            }
-            self.param
-        }
-    }
-
     #[test]
     fn test_nega_max() {
-        let mut nega_alpha = NegaAlpha {
-            eval: TestEvaluationFunction { param: 0 },
-        };
+        let mut nega_alpha = NegaAlpha::new(TestEvaluationFunction { param: 0 });
 
         let mut root = NegaAlphaNode {
             board: BitBoard::new_initial(),
@@ -223,4 +772,117 @@ mod tests {
         // println!("searched_nodes: {}", root.searched_nodes());
         // println!("value: {}", root.value().unwrap());
     }
+
+    #[test]
+    fn test_search_board() {
+        let mut nega_alpha = NegaAlpha::new(SimpleNegaAlphaEvaluationFunction::new());
+        let mut root = NegaAlphaNode::new(
+            BitBoard::new_initial(),
+            PlayerColor::Black,
+            0,
+            Move::new_pass(PlayerColor::White),
+        );
+        let expected = nega_alpha.search(&mut root, 4);
+        assert_eq!(expected.best_move, root.best_move());
+        assert_eq!(expected.pv.len(), 4);
+        assert!(expected.nodes > 0);
+
+        let board = BitBoard::new_initial();
+        let result = nega_alpha.search_board(&board, PlayerColor::Black, 4);
+        assert_eq!(result.score, expected.score);
+        assert_eq!(result.pv, expected.pv);
+        assert_eq!(result.nodes, expected.nodes);
+
+        // 読み筋は合法手の並びで、末端の評価値が探索の値になる
+        let mut b = board.clone();
+        let mut color = PlayerColor::Black;
+        for m in &result.pv {
+            b.make_move(m).unwrap();
+            color = color.opponent();
+        }
+        let mut eval = SimpleNegaAlphaEvaluationFunction::new();
+        let leaf = eval.evaluate(&b, &color);
+        assert_eq!(leaf, result.score);
+        assert!(board.squares() == BitBoard::new_initial().squares());
+        assert_eq!(board.depth(), 0);
+    }
+
+    #[test]
+    fn test_move_ordering() {
+        let board = BitBoard::new_initial();
+        let mut plain = NegaAlpha::new(SimpleNegaAlphaEvaluationFunction::new())
+            .with_move_ordering(MoveOrdering::none());
+        let expected = plain.search_board(&board, PlayerColor::Black, 6);
+
+        // 並べ替えても評価値は変わらず、探索ノード数は減る
+        let mut ordered = NegaAlpha::new(SimpleNegaAlphaEvaluationFunction::new())
+            .with_move_ordering(MoveOrdering::all());
+        let result = ordered.search_board(&board, PlayerColor::Black, 6);
+        assert_eq!(result.score, expected.score);
+        assert!(result.nodes < expected.nodes);
+
+        for ordering in [
+            MoveOrdering {
+                mobility: true,
+                ..MoveOrdering::none()
+            },
+            MoveOrdering {
+                square_prior: true,
+                ..MoveOrdering::none()
+            },
+            MoveOrdering {
+                killer: true,
+                history: true,
+                ..MoveOrdering::none()
+            },
+            MoveOrdering {
+                shallow_search_depth: 1,
+                ..MoveOrdering::none()
+            },
+        ] {
+            let mut nega_alpha = NegaAlpha::new(SimpleNegaAlphaEvaluationFunction::new())
+                .with_move_ordering(ordering);
+            let result = nega_alpha.search_board(&board, PlayerColor::Black, 6);
+            assert_eq!(result.score, expected.score, "{:?}", ordering);
+        }
+    }
+
+    #[test]
+    fn test_transposition_table() {
+        let table = TranspositionTable::new(4, ReplacementPolicy::DepthPreferred);
+        let mut nega_alpha =
+            NegaAlpha::with_transposition_table(SimpleNegaAlphaEvaluationFunction::new(), table);
+        let mut plain = NegaAlpha::new(SimpleNegaAlphaEvaluationFunction::new());
+
+        let board = BitBoard::new_initial();
+        let expected = plain.search_board(&board, PlayerColor::Black, 6);
+        let result = nega_alpha.search_board(&board, PlayerColor::Black, 6);
+        assert_eq!(result.score, expected.score);
+        assert!(result.nodes < expected.nodes);
+
+        let stats = *nega_alpha.transposition_table().unwrap().stats();
+        assert!(stats.stores > 0);
+        assert!(stats.hits > 0);
+        assert!(stats.hits <= stats.probes);
+
+        // ノードを作る探索でも同じ値で、最善手も変わらない
+        nega_alpha.transposition_table_mut().unwrap().clear();
+        let mut root = NegaAlphaNode::new(
+            BitBoard::new_initial(),
+            PlayerColor::Black,
+            0,
+            Move::new_pass(PlayerColor::White),
+        );
+        let mut plain_root = NegaAlphaNode::new(
+            BitBoard::new_initial(),
+            PlayerColor::Black,
+            0,
+            Move::new_pass(PlayerColor::White),
+        );
+        let result = nega_alpha.search(&mut root, 5);
+        let expected = plain.search(&mut plain_root, 5);
+        assert_eq!(result.score, expected.score);
+        assert_eq!(result.best_move, expected.best_move);
+        assert_eq!(root.best_move(), plain_root.best_move());
+    }
 }
@@ -1,9 +1,14 @@
+use std::time::Instant;
+
 use crate::{
     board::{BitBoard, Board},
-    Move, PlayerColor, Square, Squares,
+    Move, PlayerColor,
 };
 
+use super::evaluator::{simple_evaluate, Evaluator};
 use super::node::Node;
+use super::search_position::SearchPosition;
+use super::search_result::{update_pv, SearchResult};
 
 pub struct NegaMaxNode {
     pub board: BitBoard,
@@ -76,16 +81,19 @@ impl NegaMaxNode {
             child.to_string_impl(str, depth + 1);
         }
     }
+}
 
-    pub fn to_string(&self) -> String {
+impl std::fmt::Display for NegaMaxNode {
+    fn fmt(&self, f: &mut std::fmt::Formatter) -> std::fmt::Result {
         let mut str = String::new();
         self.to_string_impl(&mut str, 0);
-        str
+        f.write_str(&str)
     }
 }
 
 pub trait NegaMaxEvaluationFunction {
-    fn evaluate(&mut self, node: &NegaMaxNode) -> i32;
+    /// `color`から見た盤面の評価値
+    fn evaluate(&mut self, board: &BitBoard, color: &PlayerColor) -> i32;
 }
 
 pub struct SimpleNegaMaxEvaluationFunction {}
@@ -96,40 +104,57 @@ impl SimpleNegaMaxEvaluationFunction {
     }
 }
 
+impl Default for SimpleNegaMaxEvaluationFunction {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
 impl NegaMaxEvaluationFunction for SimpleNegaMaxEvaluationFunction {
-    fn evaluate(&mut self, node: &NegaMaxNode) -> i32 {
-        simple_evaluate(&node.board.squares(), &node.color)
+    fn evaluate(&mut self, board: &BitBoard, color: &PlayerColor) -> i32 {
+        simple_evaluate(board.squares(), color).value
     }
 }
 
-fn simple_evaluate(board: &Squares, color: &PlayerColor) -> i32 {
-    let weight_table: [i32; 64] = [
-        30, -12, 0, -1, -1, 0, -12, 30, //
-        -12, -15, -3, -3, -3, -3, -15, -12, //
-        0, -3, 0, -1, -1, 0, -3, 0, //
-        -1, -3, -1, -1, -1, -1, -3, -1, //
-        -1, -3, -1, -1, -1, -1, -3, -1, //
-        0, -3, 0, -1, -1, 0, -3, 0, //
-        -12, -15, -3, -3, -3, -3, -15, -12, //
-        30, -12, 0, -1, -1, 0, -12, 30, //
-    ];
-    let value = board
-        .iter()
-        .zip(weight_table.iter())
-        .fold(0, |v, (s, w)| -> i32 {
-            let color = match color {
-                PlayerColor::Black => Square::Black,
-                PlayerColor::White => Square::White,
-            };
-            if *s == Square::Empty {
-                v
-            } else if *s == color {
-                v + *w
-            } else {
-                v - *w
-            }
-        });
-    value
+/// [`Evaluator`]をNegaMax用の評価関数として使うためのアダプタ
+///
+/// 評価関数を借りて使う場合は`&mut E`を渡す。
+#[derive(Clone, Default)]
+pub struct EvaluatorNegaMaxEvaluationFunction<E>
+where
+    E: Evaluator,
+{
+    evaluator: E,
+}
+
+impl<E> EvaluatorNegaMaxEvaluationFunction<E>
+where
+    E: Evaluator,
+{
+    pub fn new(evaluator: E) -> Self {
+        EvaluatorNegaMaxEvaluationFunction { evaluator }
+    }
+
+    pub fn evaluator(&self) -> &E {
+        &self.evaluator
+    }
+
+    pub fn evaluator_mut(&mut self) -> &mut E {
+        &mut self.evaluator
+    }
+
+    pub fn into_evaluator(self) -> E {
+        self.evaluator
+    }
+}
+
+impl<E> NegaMaxEvaluationFunction for EvaluatorNegaMaxEvaluationFunction<E>
+where
+    E: Evaluator,
+{
+    fn evaluate(&mut self, board: &BitBoard, color: &PlayerColor) -> i32 {
+        self.evaluator.evaluate(board.squares(), color).value
+    }
 }
 
 pub struct NegaMax<E>
@@ -137,6 +162,7 @@ where
     E: NegaMaxEvaluationFunction,
 {
     eval: E,
+    nodes: u64,
 }
 
 impl<E> NegaMax<E>
@@ -144,34 +170,96 @@ where
     E: NegaMaxEvaluationFunction,
 {
     pub fn new(eval: E) -> Self {
-        NegaMax { eval }
+        NegaMax { eval, nodes: 0 }
     }
 
-    pub fn search(&mut self, node: &mut NegaMaxNode, depth: usize) -> i32 {
-        Self::nega_max(node, depth, &mut self.eval)
+    pub fn search(&mut self, node: &mut NegaMaxNode, depth: usize) -> SearchResult {
+        let start = Instant::now();
+        self.nodes = 0;
+        let mut pv = Vec::new();
+        let score = self.nega_max(node, depth, &mut pv);
+        self.result(score, pv, depth, start)
     }
 
-    fn nega_max(node: &mut NegaMaxNode, depth: usize, eval: &mut E) -> i32
-    where
-        E: NegaMaxEvaluationFunction,
-    {
+    fn nega_max(&mut self, node: &mut NegaMaxNode, depth: usize, pv: &mut Vec<Move>) -> i32 {
+        self.nodes += 1;
         if node.board.is_game_over() || depth == 0 {
-            let value = eval.evaluate(&node);
+            let value = self.eval.evaluate(&node.board, &node.color);
             node.value = Some(value);
             value
         } else {
             node.expand();
 
-            let vs: Vec<i32> = node
-                .children
-                .iter_mut()
-                .map(|child| -Self::nega_max(child, depth - 1, eval))
-                .collect();
+            let mut best = i32::MIN + 1;
+            for child in node.children.iter_mut() {
+                let mut child_pv = Vec::new();
+                let v = -self.nega_max(child, depth - 1, &mut child_pv);
+                if v > best || pv.is_empty() {
+                    best = v;
+                    update_pv(pv, child.last_move, &child_pv);
+                }
+            }
+
+            node.value = Some(best);
+            best
+        }
+    }
+
+    /// ノードを作らずに、`board`を複製した局面を書き換えながら探索する
+    pub fn search_board(
+        &mut self,
+        board: &BitBoard,
+        color: PlayerColor,
+        depth: usize,
+    ) -> SearchResult {
+        let mut position = SearchPosition::new(board.clone(), color);
+        self.search_position(&mut position, depth)
+    }
+
+    /// 現在の経路だけを保持して探索する
+    ///
+    /// `position`は探索後に元の局面に戻る。
+    pub fn search_position(&mut self, position: &mut SearchPosition, depth: usize) -> SearchResult {
+        let start = Instant::now();
+        self.nodes = 0;
+        let mut pv = Vec::new();
+        let score = self.nega_max_position(position, depth, &mut pv);
+        self.result(score, pv, depth, start)
+    }
+
+    fn nega_max_position(
+        &mut self,
+        position: &mut SearchPosition,
+        depth: usize,
+        pv: &mut Vec<Move>,
+    ) -> i32 {
+        self.nodes += 1;
+        if position.is_game_over() || depth == 0 {
+            return self.eval.evaluate(position.board(), &position.color());
+        }
 
-            let v = vs.iter().max_by(|a, b| a.cmp(b)).expect("no children");
+        let mut best = i32::MIN + 1;
+        for move_ in position.next_moves() {
+            position.push(&move_);
+            let mut child_pv = Vec::new();
+            let v = -self.nega_max_position(position, depth - 1, &mut child_pv);
+            position.pop();
+            if v > best || pv.is_empty() {
+                best = v;
+                update_pv(pv, move_, &child_pv);
+            }
+        }
+        best
+    }
 
-            node.value = Some(*v);
-            *v
+    fn result(&self, score: i32, pv: Vec<Move>, depth: usize, start: Instant) -> SearchResult {
+        SearchResult {
+            best_move: pv.first().copied(),
+            score,
+            pv,
+            depth,
+            nodes: self.nodes,
+            elapsed: start.elapsed(),
         }
     }
 }
@@ -185,7 +273,7 @@ mod tests {
     }
 
     impl NegaMaxEvaluationFunction for TestEvaluationFunction {
-        fn evaluate(&mut self, node: &NegaMaxNode) -> i32 {
+        fn evaluate(&mut self, _board: &BitBoard, _color: &PlayerColor) -> i32 {
             self.param += 1;
             if self.param > 10 {
                 self.param = 0;
@@ -202,9 +290,7 @@ mod tests {
 
     #[test]
     fn test_nega_max() {
-        let mut nega_max = NegaMax {
-            eval: TestEvaluationFunction { param: 0 },
-        };
+        let mut nega_max = NegaMax::new(TestEvaluationFunction { param: 0 });
 
         let mut root = NegaMaxNode {
             board: BitBoard::new_initial(),
@@ -216,10 +302,33 @@ mod tests {
         };
 
         nega_max.search(&mut root, 2);
-        print!("{}", root.to_string());
+        print!("{}", root);
         // println!("node_count: {}", root.node_count());
         // println!("searched_nodes: {}", root.searched_nodes());
         // println!("value: {}", root.value().unwrap());
         // assert_eq!(root.value, Some(10));
     }
+
+    #[test]
+    fn test_search_board() {
+        let mut nega_max = NegaMax::new(SimpleNegaMaxEvaluationFunction::new());
+        let mut root = NegaMaxNode::new(
+            BitBoard::new_initial(),
+            PlayerColor::Black,
+            0,
+            Move::new_pass(PlayerColor::White),
+        );
+        let expected = nega_max.search(&mut root, 4);
+        assert_eq!(expected.best_move, root.best_move());
+        assert_eq!(expected.pv.len(), 4);
+        assert_eq!(expected.nodes as usize, root.node_count());
+
+        let board = BitBoard::new_initial();
+        let result = nega_max.search_board(&board, PlayerColor::Black, 4);
+        assert_eq!(result.score, expected.score);
+        assert_eq!(result.pv, expected.pv);
+        assert_eq!(result.nodes, expected.nodes);
+        assert!(board.squares() == BitBoard::new_initial().squares());
+        assert_eq!(board.depth(), 0);
+    }
 }
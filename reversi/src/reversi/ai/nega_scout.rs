@@ -0,0 +1,408 @@
+use std::time::Instant;
+
+use crate::{
+    board::{BitBoard, Board},
+    Move, PlayerColor,
+};
+
+use super::evaluator::{simple_evaluate, Evaluator};
+use super::move_ordering::{MoveOrderer, MoveOrdering};
+use super::node::Node;
+use super::search_position::SearchPosition;
+use super::search_result::{update_pv, SearchResult};
+
+pub struct NegaScoutNode {
+    pub board: BitBoard,
+    pub color: PlayerColor,
+    pub move_count: u8,
+    pub last_move: Move,
+    pub value: Option<i32>,
+    pub children: Vec<NegaScoutNode>,
+}
+
+impl Node for NegaScoutNode {
+    fn new(board: BitBoard, color: PlayerColor, move_count: u8, last_move: Move) -> Self {
+        NegaScoutNode {
+            board,
+            color,
+            move_count,
+            last_move,
+            value: None,
+            children: Vec::new(),
+        }
+    }
+
+    fn board(&self) -> &BitBoard {
+        &self.board
+    }
+
+    fn color(&self) -> &PlayerColor {
+        &self.color
+    }
+
+    fn move_count(&self) -> &u8 {
+        &self.move_count
+    }
+
+    fn children(&self) -> &[Self] {
+        &self.children
+    }
+
+    fn children_mut(&mut self) -> &mut Vec<Self> {
+        &mut self.children
+    }
+
+    fn set_children(&mut self, children: Vec<Self>) {
+        self.children = children;
+    }
+
+    fn value(&self) -> &Option<i32> {
+        &self.value
+    }
+
+    fn value_mut(&mut self) -> &mut Option<i32> {
+        &mut self.value
+    }
+
+    fn last_move(&self) -> &Move {
+        &self.last_move
+    }
+}
+
+pub trait NegaScoutEvaluationFunction {
+    /// `color`から見た盤面の評価値
+    fn evaluate(&mut self, board: &BitBoard, color: &PlayerColor) -> i32;
+}
+
+#[derive(Default)]
+pub struct SimpleNegaScoutEvaluationFunction {}
+
+impl SimpleNegaScoutEvaluationFunction {
+    pub fn new() -> Self {
+        SimpleNegaScoutEvaluationFunction {}
+    }
+}
+
+impl NegaScoutEvaluationFunction for SimpleNegaScoutEvaluationFunction {
+    fn evaluate(&mut self, board: &BitBoard, color: &PlayerColor) -> i32 {
+        simple_evaluate(board.squares(), color).value
+    }
+}
+
+/// [`Evaluator`]をNegaScout用の評価関数として使うためのアダプタ
+///
+/// 評価関数を借りて使う場合は`&mut E`を渡す。
+#[derive(Clone, Default)]
+pub struct EvaluatorNegaScoutEvaluationFunction<E>
+where
+    E: Evaluator,
+{
+    evaluator: E,
+}
+
+impl<E> EvaluatorNegaScoutEvaluationFunction<E>
+where
+    E: Evaluator,
+{
+    pub fn new(evaluator: E) -> Self {
+        EvaluatorNegaScoutEvaluationFunction { evaluator }
+    }
+
+    pub fn evaluator(&self) -> &E {
+        &self.evaluator
+    }
+
+    pub fn evaluator_mut(&mut self) -> &mut E {
+        &mut self.evaluator
+    }
+
+    pub fn into_evaluator(self) -> E {
+        self.evaluator
+    }
+}
+
+impl<E> NegaScoutEvaluationFunction for EvaluatorNegaScoutEvaluationFunction<E>
+where
+    E: Evaluator,
+{
+    fn evaluate(&mut self, board: &BitBoard, color: &PlayerColor) -> i32 {
+        self.evaluator.evaluate(board.squares(), color).value
+    }
+}
+
+/// NegaScout(Principal Variation Search)
+///
+/// 最初の手だけを通常の窓で探索し、残りの手はnull windowで最初の手より良いかだけを調べる。
+/// 良い手が見つかったら通常の窓で探索し直す。手の並びが良いほどNegaAlphaより速い。
+pub struct NegaScout<E>
+where
+    E: NegaScoutEvaluationFunction,
+{
+    eval: E,
+    orderer: MoveOrderer,
+    nodes: u64,
+    re_searches: u64,
+}
+
+impl<E> NegaScout<E>
+where
+    E: NegaScoutEvaluationFunction,
+{
+    pub fn new(eval: E) -> Self {
+        NegaScout {
+            eval,
+            orderer: MoveOrderer::new(MoveOrdering::none()),
+            nodes: 0,
+            re_searches: 0,
+        }
+    }
+
+    /// 手の並べ替え方を設定する(ノードを作らない探索のみ有効)
+    ///
+    /// 置換表は使わないので[`MoveOrdering::transposition_table`]は無視する。
+    pub fn with_move_ordering(mut self, ordering: MoveOrdering) -> Self {
+        self.orderer = MoveOrderer::new(ordering);
+        self
+    }
+
+    pub fn move_orderer(&self) -> &MoveOrderer {
+        &self.orderer
+    }
+
+    /// 直前の探索で探索し直した回数
+    pub fn re_searches(&self) -> u64 {
+        self.re_searches
+    }
+
+    pub fn search(&mut self, node: &mut NegaScoutNode, depth: usize) -> SearchResult {
+        let start = Instant::now();
+        self.begin_search();
+        let mut pv = Vec::new();
+        let score = self.nega_scout(node, depth, i32::MIN + 1, i32::MAX, &mut pv);
+        self.result(score, pv, depth, start)
+    }
+
+    fn nega_scout(
+        &mut self,
+        node: &mut NegaScoutNode,
+        depth: usize,
+        alpha: i32,
+        beta: i32,
+        pv: &mut Vec<Move>,
+    ) -> i32 {
+        self.nodes += 1;
+        if node.board.is_game_over() || depth == 0 {
+            let value = self.eval.evaluate(&node.board, &node.color);
+            node.value = Some(value);
+            return value;
+        }
+
+        // 探索し直すときは展開済みの子ノードを使う
+        if node.children.is_empty() {
+            node.expand();
+        }
+
+        let mut alpha = alpha;
+        for (i, child) in node.children.iter_mut().enumerate() {
+            let mut child_pv = Vec::new();
+            let v = if i == 0 {
+                -self.nega_scout(child, depth - 1, -beta, -alpha, &mut child_pv)
+            } else {
+                let v = -self.nega_scout(child, depth - 1, -alpha - 1, -alpha, &mut child_pv);
+                if alpha < v && v < beta {
+                    self.re_searches += 1;
+                    child_pv.clear();
+                    -self.nega_scout(child, depth - 1, -beta, -alpha, &mut child_pv)
+                } else {
+                    v
+                }
+            };
+            if v > alpha {
+                alpha = v;
+                update_pv(pv, child.last_move, &child_pv);
+            }
+            if alpha >= beta {
+                break;
+            }
+        }
+
+        node.value = Some(alpha);
+        alpha
+    }
+
+    /// ノードを作らずに、`board`を複製した局面を書き換えながら探索する
+    pub fn search_board(
+        &mut self,
+        board: &BitBoard,
+        color: PlayerColor,
+        depth: usize,
+    ) -> SearchResult {
+        let mut position = SearchPosition::new(board.clone(), color);
+        self.search_position(&mut position, depth)
+    }
+
+    /// 現在の経路だけを保持して探索する
+    ///
+    /// `position`は探索後に元の局面に戻る。
+    pub fn search_position(&mut self, position: &mut SearchPosition, depth: usize) -> SearchResult {
+        let start = Instant::now();
+        self.begin_search();
+        let mut pv = Vec::new();
+        let score = self.nega_scout_position(position, depth, i32::MIN + 1, i32::MAX, &mut pv);
+        self.result(score, pv, depth, start)
+    }
+
+    fn nega_scout_position(
+        &mut self,
+        position: &mut SearchPosition,
+        depth: usize,
+        alpha: i32,
+        beta: i32,
+        pv: &mut Vec<Move>,
+    ) -> i32 {
+        self.nodes += 1;
+        if position.is_game_over() || depth == 0 {
+            return self.eval.evaluate(position.board(), &position.color());
+        }
+
+        let mut moves = position.next_moves();
+        self.order_moves(position, &mut moves, depth);
+
+        let mut alpha = alpha;
+        for (i, move_) in moves.into_iter().enumerate() {
+            position.push(&move_);
+            let mut child_pv = Vec::new();
+            let v = if i == 0 {
+                -self.nega_scout_position(position, depth - 1, -beta, -alpha, &mut child_pv)
+            } else {
+                let v = -self.nega_scout_position(
+                    position,
+                    depth - 1,
+                    -alpha - 1,
+                    -alpha,
+                    &mut child_pv,
+                );
+                if alpha < v && v < beta {
+                    self.re_searches += 1;
+                    child_pv.clear();
+                    -self.nega_scout_position(position, depth - 1, -beta, -alpha, &mut child_pv)
+                } else {
+                    v
+                }
+            };
+            position.pop();
+            if v > alpha {
+                alpha = v;
+                update_pv(pv, move_, &child_pv);
+            }
+            if alpha >= beta {
+                self.orderer.update_cutoff(&move_, position.ply(), depth);
+                break;
+            }
+        }
+        alpha
+    }
+
+    /// 残り深さ`depth`のノードで手を並べ替える
+    fn order_moves(&mut self, position: &mut SearchPosition, moves: &mut [Move], depth: usize) {
+        let ordering = *self.orderer.ordering();
+        let shallow_scores = if moves.len() > 1 && ordering.use_shallow_search(depth) {
+            let mut scores = Vec::with_capacity(moves.len());
+            for move_ in moves.iter() {
+                position.push(move_);
+                let v = -self.nega_scout_position(
+                    position,
+                    ordering.shallow_search_depth - 1,
+                    i32::MIN + 1,
+                    i32::MAX,
+                    &mut Vec::new(),
+                );
+                position.pop();
+                scores.push(v);
+            }
+            Some(scores)
+        } else {
+            None
+        };
+        self.orderer
+            .order(position, moves, None, shallow_scores.as_deref());
+    }
+
+    fn begin_search(&mut self) {
+        self.orderer.new_search();
+        self.nodes = 0;
+        self.re_searches = 0;
+    }
+
+    fn result(&self, score: i32, pv: Vec<Move>, depth: usize, start: Instant) -> SearchResult {
+        SearchResult {
+            best_move: pv.first().copied(),
+            score,
+            pv,
+            depth,
+            nodes: self.nodes,
+            elapsed: start.elapsed(),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::super::nega_alpha::*;
+    use super::*;
+    use crate::Position;
+
+    fn new_root(board: BitBoard, color: PlayerColor) -> NegaScoutNode {
+        NegaScoutNode::new(board, color, 0, Move::new_pass(color.opponent()))
+    }
+
+    #[test]
+    fn test_same_score_as_nega_alpha() {
+        let mut nega_scout = NegaScout::new(SimpleNegaScoutEvaluationFunction::new());
+        let mut nega_alpha = NegaAlpha::new(SimpleNegaAlphaEvaluationFunction::new());
+
+        let mut board = BitBoard::new_initial();
+        for m in [
+            Move::new_position(PlayerColor::Black, Position(2, 3)),
+            Move::new_position(PlayerColor::White, Position(2, 2)),
+            Move::new_position(PlayerColor::Black, Position(3, 2)),
+        ] {
+            for depth in 1..=5 {
+                let color = board.turn();
+                let expected = nega_alpha.search_board(&board, color, depth);
+                let result = nega_scout.search_board(&board, color, depth);
+                assert_eq!(result.score, expected.score, "depth {}", depth);
+                assert_eq!(result.pv.len(), depth);
+            }
+            board.make_move(&m).unwrap();
+        }
+    }
+
+    #[test]
+    fn test_move_ordering() {
+        let board = BitBoard::new_initial();
+        let mut plain = NegaScout::new(SimpleNegaScoutEvaluationFunction::new());
+        let expected = plain.search_board(&board, PlayerColor::Black, 6);
+
+        let mut ordered = NegaScout::new(SimpleNegaScoutEvaluationFunction::new())
+            .with_move_ordering(MoveOrdering::all());
+        let result = ordered.search_board(&board, PlayerColor::Black, 6);
+        assert_eq!(result.score, expected.score);
+        assert!(result.nodes < expected.nodes);
+    }
+
+    #[test]
+    fn test_search_tree() {
+        let mut nega_scout = NegaScout::new(SimpleNegaScoutEvaluationFunction::new());
+        let mut root = new_root(BitBoard::new_initial(), PlayerColor::Black);
+        let expected = nega_scout.search(&mut root, 4);
+        assert_eq!(expected.best_move, root.best_move());
+        assert!(expected.nodes > 0);
+
+        let board = BitBoard::new_initial();
+        let result = nega_scout.search_board(&board, PlayerColor::Black, 4);
+        assert_eq!(result.score, expected.score);
+        assert_eq!(result.pv, expected.pv);
+        assert!(board.squares() == BitBoard::new_initial().squares());
+    }
+}
@@ -3,6 +3,24 @@ use crate::{
     Move, PlayerColor,
 };
 
+/// 盤面で`color`が指せる手の一覧
+///
+/// 置ける場所がなければパスのみを返す。
+pub fn next_moves<B>(board: &B, color: &PlayerColor) -> Vec<Move>
+where
+    B: Board,
+{
+    let positions = board.get_movable_positions(color);
+    if positions.is_empty() {
+        vec![Move::new_pass(*color)]
+    } else {
+        positions
+            .iter()
+            .map(|position| Move::new_position(*color, *position))
+            .collect::<Vec<_>>()
+    }
+}
+
 pub trait Node: Sized {
     fn new(board: BitBoard, color: PlayerColor, move_count: u8, last_move: Move) -> Self;
 
@@ -18,20 +36,13 @@ pub trait Node: Sized {
     fn last_move(&self) -> &Move;
 
     fn expand(&mut self) {
-        let positions = self.board().get_movable_positions(self.color());
-        let moves = if positions.is_empty() {
-            vec![Move::new_pass(*self.color())]
-        } else {
-            positions
-                .iter()
-                .map(|position| Move::new_position(*self.color(), *position))
-                .collect::<Vec<_>>()
-        };
+        let moves = next_moves(self.board(), self.color());
 
         let children = moves
             .iter()
             .map(|move_| {
-                let next_board = self.board().apply_move(move_).unwrap();
+                let mut next_board = self.board().duplicate();
+                next_board.make_move(move_).unwrap();
                 Self::new(
                     next_board,
                     self.color().opponent(),
@@ -57,22 +68,41 @@ pub trait Node: Sized {
             .fold(1, |acc, child| acc + child.searched_nodes())
     }
 
+    /// 探索済みの子ノードの手を、このノードの手番にとって良い順に並べる
+    ///
+    /// 未探索の子ノードは含めない。
     fn candidate(&self) -> Option<Vec<Move>> {
-        if self.children().is_empty() {
+        let mut children = self
+            .children()
+            .iter()
+            .filter_map(|child| child.value().map(|value| (-value, *child.last_move())))
+            .collect::<Vec<_>>();
+        if children.is_empty() {
             None
         } else {
-            let mut children = self
-                .children()
-                .iter()
-                .map(|child| (child.value(), child.last_move()))
-                .collect::<Vec<_>>();
-            children.sort_by(|a, b| b.0.cmp(a.0));
-            Some(
-                children
-                    .iter()
-                    .map(|(_, &action)| action)
-                    .collect::<Vec<_>>(),
-            )
+            children.sort_by_key(|(value, _)| std::cmp::Reverse(*value));
+            Some(children.iter().map(|(_, action)| *action).collect())
+        }
+    }
+
+    /// 探索済みの子ノードのうち、このノードの手番にとって最善の手
+    ///
+    /// 子ノードの評価値は子ノードの手番(相手)から見た値なので符号を反転して比較する。
+    /// 同じ値の場合は先に展開された手を優先する。
+    fn best_move(&self) -> Option<Move> {
+        let mut best: Option<(i32, Move)> = None;
+        for child in self.children() {
+            if let Some(value) = child.value() {
+                let v = -value;
+                let is_better = match best {
+                    Some((best_value, _)) => v > best_value,
+                    None => true,
+                };
+                if is_better {
+                    best = Some((v, *child.last_move()));
+                }
+            }
         }
+        best.map(|(_, move_)| move_)
     }
 }
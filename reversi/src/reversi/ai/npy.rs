@@ -0,0 +1,571 @@
+use std::convert::{TryFrom, TryInto};
+use std::io::{Error, ErrorKind};
+use std::path::Path;
+
+use crate::{
+    board::{BitBoard, Board},
+    game::GameResult,
+    position_to_index, Move, PlayerColor, BOARD_SIZE,
+};
+
+use super::alpha_zero::{mask_planes, PLANE_COUNT};
+use super::self_play::SelfPlaySample;
+
+const SQUARE_COUNT: usize = BOARD_SIZE * BOARD_SIZE;
+
+/// `.npy`ファイルの先頭の識別子
+const NPY_MAGIC: &[u8; 6] = b"\x93NUMPY";
+
+/// ヘッダの終わりをこの倍数にそろえる
+const NPY_HEADER_ALIGNMENT: usize = 64;
+
+/// `.npz`(zip)の署名
+const ZIP_LOCAL_FILE_SIGNATURE: u32 = 0x0403_4b50;
+const ZIP_CENTRAL_DIRECTORY_SIGNATURE: u32 = 0x0201_4b50;
+const ZIP_END_OF_CENTRAL_DIRECTORY_SIGNATURE: u32 = 0x0605_4b50;
+
+/// zipの展開に必要なバージョン(2.0)
+const ZIP_VERSION: u16 = 20;
+
+/// zipの更新日付(1980年1月1日)
+const ZIP_DATE: u16 = (1 << 5) | 1;
+
+/// `.npy`の要素
+#[derive(Clone, PartialEq, Debug)]
+pub enum NpyData {
+    U8(Vec<u8>),
+    F32(Vec<f32>),
+}
+
+impl NpyData {
+    pub fn len(&self) -> usize {
+        match self {
+            NpyData::U8(data) => data.len(),
+            NpyData::F32(data) => data.len(),
+        }
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.len() == 0
+    }
+
+    /// NumPyの型の表記(`dtype.str`)
+    pub fn descr(&self) -> &'static str {
+        match self {
+            NpyData::U8(_) => "|u1",
+            NpyData::F32(_) => "<f4",
+        }
+    }
+}
+
+/// NumPyの配列(C順)
+///
+/// `.npy`形式(バージョン1.0)で読み書きする。
+#[derive(Clone, PartialEq, Debug)]
+pub struct NpyArray {
+    shape: Vec<usize>,
+    data: NpyData,
+}
+
+impl NpyArray {
+    /// 要素数が形に合わなければパニックする
+    pub fn new(shape: Vec<usize>, data: NpyData) -> Self {
+        assert_eq!(
+            shape.iter().product::<usize>(),
+            data.len(),
+            "shape {:?} does not match {} elements",
+            shape,
+            data.len()
+        );
+        NpyArray { shape, data }
+    }
+
+    pub fn shape(&self) -> &[usize] {
+        &self.shape
+    }
+
+    pub fn data(&self) -> &NpyData {
+        &self.data
+    }
+
+    /// ヘッダの辞書(`{'descr': '<f4', 'fortran_order': False, 'shape': (2, 3), }`)
+    fn header(&self) -> String {
+        let shape = match self.shape.as_slice() {
+            [n] => format!("({},)", n),
+            shape => format!(
+                "({})",
+                shape
+                    .iter()
+                    .map(|n| n.to_string())
+                    .collect::<Vec<_>>()
+                    .join(", ")
+            ),
+        };
+        format!(
+            "{{'descr': '{}', 'fortran_order': False, 'shape': {}, }}",
+            self.data.descr(),
+            shape
+        )
+    }
+
+    pub fn to_bytes(&self) -> Vec<u8> {
+        // 識別子、バージョン、ヘッダ長、ヘッダの後が揃うように空白で埋めて改行で終える
+        let mut header = self.header();
+        let unpadded = NPY_MAGIC.len() + 4 + header.len() + 1;
+        let padded = unpadded.div_ceil(NPY_HEADER_ALIGNMENT) * NPY_HEADER_ALIGNMENT;
+        header.push_str(&" ".repeat(padded - unpadded));
+        header.push('\n');
+
+        let mut bytes = Vec::with_capacity(padded + self.data.len() * 4);
+        bytes.extend_from_slice(NPY_MAGIC);
+        bytes.extend_from_slice(&[1, 0]);
+        bytes.extend_from_slice(&(header.len() as u16).to_le_bytes());
+        bytes.extend_from_slice(header.as_bytes());
+        match &self.data {
+            NpyData::U8(data) => bytes.extend_from_slice(data),
+            NpyData::F32(data) => {
+                for x in data {
+                    bytes.extend_from_slice(&x.to_le_bytes());
+                }
+            }
+        }
+        bytes
+    }
+
+    /// [`NpyArray::to_bytes`]の形式から読み込む
+    ///
+    /// 要素は`|u1`と`<f4`、C順の配列だけ読める。
+    pub fn from_bytes(bytes: &[u8]) -> std::io::Result<Self> {
+        let invalid = |message: String| Error::new(ErrorKind::InvalidData, message);
+        if bytes.len() < 10 || &bytes[0..6] != NPY_MAGIC {
+            return Err(invalid("not a npy file".to_string()));
+        }
+        if bytes[6] != 1 {
+            return Err(invalid(format!(
+                "unsupported npy version: {}.{}",
+                bytes[6], bytes[7]
+            )));
+        }
+        let header_len = u16::from_le_bytes([bytes[8], bytes[9]]) as usize;
+        let header = bytes
+            .get(10..10 + header_len)
+            .and_then(|header| std::str::from_utf8(header).ok())
+            .ok_or_else(|| invalid("invalid npy header".to_string()))?;
+        let data = &bytes[10 + header_len..];
+
+        let value = |key: &str| -> std::io::Result<&str> {
+            let start = header
+                .find(&format!("'{}':", key))
+                .ok_or_else(|| invalid(format!("npy header has no {}", key)))?;
+            Ok(header[start + key.len() + 3..].trim_start())
+        };
+        if !value("fortran_order")?.starts_with("False") {
+            return Err(invalid("fortran order is not supported".to_string()));
+        }
+        let shape = value("shape")?;
+        let shape = shape[..shape.find(')').unwrap_or(0)]
+            .trim_start_matches('(')
+            .split(',')
+            .map(str::trim)
+            .filter(|n| !n.is_empty())
+            .map(|n| n.parse::<usize>())
+            .collect::<Result<Vec<_>, _>>()
+            .map_err(|_| invalid(format!("invalid npy shape: {}", header)))?;
+        let count = shape.iter().product::<usize>();
+
+        let descr = value("descr")?;
+        let data = if descr.starts_with("'|u1'") {
+            if data.len() != count {
+                return Err(invalid("npy data size mismatch".to_string()));
+            }
+            NpyData::U8(data.to_vec())
+        } else if descr.starts_with("'<f4'") {
+            if data.len() != count * 4 {
+                return Err(invalid("npy data size mismatch".to_string()));
+            }
+            NpyData::F32(
+                data.chunks_exact(4)
+                    .map(|chunk| f32::from_le_bytes(chunk.try_into().unwrap()))
+                    .collect(),
+            )
+        } else {
+            return Err(invalid(format!("unsupported npy dtype: {}", descr)));
+        };
+        Ok(NpyArray { shape, data })
+    }
+
+    pub fn save<P: AsRef<Path>>(&self, path: P) -> std::io::Result<()> {
+        std::fs::write(path, self.to_bytes())
+    }
+
+    pub fn load<P: AsRef<Path>>(path: P) -> std::io::Result<Self> {
+        Self::from_bytes(&std::fs::read(path)?)
+    }
+}
+
+/// zipのCRC-32
+fn crc32(bytes: &[u8]) -> u32 {
+    let mut crc = !0u32;
+    for byte in bytes {
+        crc ^= *byte as u32;
+        for _ in 0..8 {
+            crc = if crc & 1 != 0 {
+                (crc >> 1) ^ 0xedb8_8320
+            } else {
+                crc >> 1
+            };
+        }
+    }
+    !crc
+}
+
+/// 名前を付けた配列を`.npz`形式(無圧縮のzip)にする
+///
+/// `numpy.load`では`名前`で配列を取り出せる(ファイル名は`名前.npy`)。
+pub fn npz_to_bytes(arrays: &[(&str, &NpyArray)]) -> std::io::Result<Vec<u8>> {
+    let too_large = || Error::new(ErrorKind::InvalidInput, "npz is too large");
+    let mut bytes = Vec::new();
+    let mut central_directory = Vec::new();
+    for (name, array) in arrays {
+        let name = format!("{}.npy", name);
+        let data = array.to_bytes();
+        let size = u32::try_from(data.len()).map_err(|_| too_large())?;
+        let offset = u32::try_from(bytes.len()).map_err(|_| too_large())?;
+        let crc = crc32(&data);
+
+        // 展開に必要なバージョン、フラグ、圧縮方式(無圧縮)、時刻、日付、CRC、圧縮後と元のサイズ、名前の長さ、拡張の長さ
+        let mut common = Vec::new();
+        for value in [ZIP_VERSION, 0, 0, 0, ZIP_DATE] {
+            common.extend_from_slice(&value.to_le_bytes());
+        }
+        for value in [crc, size, size] {
+            common.extend_from_slice(&value.to_le_bytes());
+        }
+        common.extend_from_slice(&(name.len() as u16).to_le_bytes());
+        common.extend_from_slice(&0u16.to_le_bytes());
+
+        bytes.extend_from_slice(&ZIP_LOCAL_FILE_SIGNATURE.to_le_bytes());
+        bytes.extend_from_slice(&common);
+        bytes.extend_from_slice(name.as_bytes());
+        bytes.extend_from_slice(&data);
+
+        // 作成したバージョン、共通部分、コメントの長さ、ディスク番号、内部属性、外部属性、ローカルヘッダの位置
+        central_directory.extend_from_slice(&ZIP_CENTRAL_DIRECTORY_SIGNATURE.to_le_bytes());
+        central_directory.extend_from_slice(&ZIP_VERSION.to_le_bytes());
+        central_directory.extend_from_slice(&common);
+        central_directory.extend_from_slice(&[0; 10]);
+        central_directory.extend_from_slice(&offset.to_le_bytes());
+        central_directory.extend_from_slice(name.as_bytes());
+    }
+
+    let offset = u32::try_from(bytes.len()).map_err(|_| too_large())?;
+    let size = central_directory.len() as u32;
+    bytes.extend_from_slice(&central_directory);
+    bytes.extend_from_slice(&ZIP_END_OF_CENTRAL_DIRECTORY_SIGNATURE.to_le_bytes());
+    bytes.extend_from_slice(&[0; 4]);
+    for _ in 0..2 {
+        bytes.extend_from_slice(&(arrays.len() as u16).to_le_bytes());
+    }
+    bytes.extend_from_slice(&size.to_le_bytes());
+    bytes.extend_from_slice(&offset.to_le_bytes());
+    bytes.extend_from_slice(&0u16.to_le_bytes());
+    Ok(bytes)
+}
+
+/// [`npz_to_bytes`]の形式(無圧縮のzip)から配列を読み込む
+pub fn npz_from_bytes(bytes: &[u8]) -> std::io::Result<Vec<(String, NpyArray)>> {
+    let invalid = |message: &str| Error::new(ErrorKind::InvalidData, message.to_string());
+    let u16_at = |i: usize| {
+        bytes
+            .get(i..i + 2)
+            .map(|b| u16::from_le_bytes(b.try_into().unwrap()) as usize)
+            .ok_or_else(|| invalid("truncated npz"))
+    };
+    let u32_at = |i: usize| {
+        bytes
+            .get(i..i + 4)
+            .map(|b| u32::from_le_bytes(b.try_into().unwrap()))
+            .ok_or_else(|| invalid("truncated npz"))
+    };
+
+    let mut arrays = Vec::new();
+    let mut position = 0;
+    while u32_at(position)? == ZIP_LOCAL_FILE_SIGNATURE {
+        if u16_at(position + 8)? != 0 {
+            return Err(invalid("compressed npz is not supported"));
+        }
+        let crc = u32_at(position + 14)?;
+        let size = u32_at(position + 18)? as usize;
+        let name_len = u16_at(position + 26)?;
+        let extra_len = u16_at(position + 28)?;
+        let name_start = position + 30;
+        let data_start = name_start + name_len + extra_len;
+        let (name, data) = match (
+            bytes.get(name_start..name_start + name_len),
+            bytes.get(data_start..data_start + size),
+        ) {
+            (Some(name), Some(data)) => (name, data),
+            _ => return Err(invalid("truncated npz")),
+        };
+        if crc32(data) != crc {
+            return Err(invalid("npz checksum mismatch"));
+        }
+        let name = String::from_utf8_lossy(name);
+        let name = name.strip_suffix(".npy").unwrap_or(&name).to_string();
+        arrays.push((name, NpyArray::from_bytes(data)?));
+        position = data_start + size;
+    }
+    Ok(arrays)
+}
+
+pub fn save_npz<P: AsRef<Path>>(path: P, arrays: &[(&str, &NpyArray)]) -> std::io::Result<()> {
+    std::fs::write(path, npz_to_bytes(arrays)?)
+}
+
+pub fn load_npz<P: AsRef<Path>>(path: P) -> std::io::Result<Vec<(String, NpyArray)>> {
+    npz_from_bytes(&std::fs::read(path)?)
+}
+
+/// 学習用の配列
+///
+/// `boards`は`(局面数, 8, 8, 3)`で、面は手番側の石、相手の石、着手可能位置([`mask_planes`])。
+/// `policies`は`(局面数, 64)`、`values`は手番側から見た対局結果(勝ち1、引き分け0、負け-1)で`(局面数, 1)`。
+/// いずれもfloat32で、`python/alpha_zero_network.py`の`AlphaZeroResNet`の入力と出力の形に合わせている。
+#[derive(Clone, PartialEq, Debug, Default)]
+pub struct TrainingArrays {
+    boards: Vec<f32>,
+    policies: Vec<f32>,
+    values: Vec<f32>,
+}
+
+impl TrainingArrays {
+    pub fn new() -> Self {
+        Default::default()
+    }
+
+    /// 局面数
+    pub fn len(&self) -> usize {
+        self.values.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.values.is_empty()
+    }
+
+    /// 手番側と相手の石のビットボード、方策、結果を追加する
+    pub fn push(&mut self, player: u64, opponent: u64, policy: &[f32], value: f32) {
+        assert_eq!(policy.len(), SQUARE_COUNT);
+        self.boards.extend(mask_planes(player, opponent));
+        self.policies.extend_from_slice(policy);
+        self.values.push(value);
+    }
+
+    /// 対局結果の各局面を追加する
+    ///
+    /// 方策は実際に指した手を1にする。パスしかできない局面は除く。
+    pub fn add_game_result<T>(&mut self, result: &GameResult<T>)
+    where
+        T: Board,
+    {
+        let last = BitBoard::new(&result.state.board, result.state.depth);
+        let black_value = match last.black().count_ones().cmp(&last.white().count_ones()) {
+            std::cmp::Ordering::Greater => 1.0,
+            std::cmp::Ordering::Less => -1.0,
+            std::cmp::Ordering::Equal => 0.0,
+        };
+        for (board, move_) in result.history.iter().zip(&result.game_record) {
+            if let Move::Position(color, position) = move_ {
+                let board = BitBoard::new(board.squares(), board.depth());
+                let (player, opponent, value) = match color {
+                    PlayerColor::Black => (board.black(), board.white(), black_value),
+                    PlayerColor::White => (board.white(), board.black(), -black_value),
+                };
+                let mut policy = [0.0; SQUARE_COUNT];
+                policy[position_to_index(position)] = 1.0;
+                self.push(player, opponent, &policy, value);
+            }
+        }
+    }
+
+    /// 自己対局の局面を追加する
+    pub fn add_samples(&mut self, samples: &[SelfPlaySample]) {
+        for sample in samples {
+            let board = BitBoard::new(&sample.board, 0);
+            let (player, opponent) = match sample.color {
+                PlayerColor::Black => (board.black(), board.white()),
+                PlayerColor::White => (board.white(), board.black()),
+            };
+            self.push(player, opponent, &sample.policy, sample.outcome);
+        }
+    }
+
+    /// `boards`、`policies`、`values`の順の配列
+    pub fn arrays(&self) -> [(&'static str, NpyArray); 3] {
+        let n = self.len();
+        [
+            (
+                "boards",
+                NpyArray::new(
+                    vec![n, BOARD_SIZE, BOARD_SIZE, PLANE_COUNT],
+                    NpyData::F32(self.boards.clone()),
+                ),
+            ),
+            (
+                "policies",
+                NpyArray::new(vec![n, SQUARE_COUNT], NpyData::F32(self.policies.clone())),
+            ),
+            (
+                "values",
+                NpyArray::new(vec![n, 1], NpyData::F32(self.values.clone())),
+            ),
+        ]
+    }
+
+    /// `boards`、`policies`、`values`の3つの配列を1つの`.npz`に書き出す
+    pub fn save_npz<P: AsRef<Path>>(&self, path: P) -> std::io::Result<()> {
+        let arrays = self.arrays();
+        let named = arrays
+            .iter()
+            .map(|(name, array)| (*name, array))
+            .collect::<Vec<_>>();
+        save_npz(path, &named)
+    }
+
+    /// `directory`に`boards.npy`、`policies.npy`、`values.npy`を書き出す
+    pub fn save_npy<P: AsRef<Path>>(&self, directory: P) -> std::io::Result<()> {
+        std::fs::create_dir_all(&directory)?;
+        for (name, array) in self.arrays() {
+            array.save(directory.as_ref().join(format!("{}.npy", name)))?;
+        }
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::ai::board_planes;
+    use crate::game::play_game;
+    use crate::player::AiPlayer;
+
+    #[test]
+    fn test_npy_header() {
+        let array = NpyArray::new(vec![2, 3], NpyData::F32(vec![0.0, 1.0, 2.0, 3.0, 4.0, 5.5]));
+        let bytes = array.to_bytes();
+        assert_eq!(&bytes[0..8], b"\x93NUMPY\x01\x00");
+        let header_len = u16::from_le_bytes([bytes[8], bytes[9]]) as usize;
+        assert_eq!((10 + header_len) % NPY_HEADER_ALIGNMENT, 0);
+        let header = std::str::from_utf8(&bytes[10..10 + header_len]).unwrap();
+        assert!(header.starts_with("{'descr': '<f4', 'fortran_order': False, 'shape': (2, 3), }"));
+        assert!(header.ends_with(" \n"));
+        assert_eq!(bytes.len(), 10 + header_len + 6 * 4);
+        assert_eq!(&bytes[bytes.len() - 4..], &5.5f32.to_le_bytes());
+        assert_eq!(NpyArray::from_bytes(&bytes).unwrap(), array);
+
+        let array = NpyArray::new(vec![3], NpyData::U8(vec![1, 2, 255]));
+        let bytes = array.to_bytes();
+        assert!(std::str::from_utf8(&bytes[10..bytes.len() - 3])
+            .unwrap()
+            .starts_with("{'descr': '|u1', 'fortran_order': False, 'shape': (3,), }"));
+        assert_eq!(&bytes[bytes.len() - 3..], &[1, 2, 255]);
+        assert_eq!(NpyArray::from_bytes(&bytes).unwrap(), array);
+
+        assert!(NpyArray::from_bytes(&bytes[..bytes.len() - 1]).is_err());
+    }
+
+    #[test]
+    fn test_crc32() {
+        assert_eq!(crc32(b""), 0);
+        assert_eq!(crc32(b"123456789"), 0xcbf4_3926);
+    }
+
+    #[test]
+    fn test_npz_round_trip() {
+        let a = NpyArray::new(vec![2, 2], NpyData::F32(vec![1.0, -2.0, 3.0, 0.5]));
+        let b = NpyArray::new(vec![0], NpyData::U8(vec![]));
+        let bytes = npz_to_bytes(&[("a", &a), ("b", &b)]).unwrap();
+        assert_eq!(&bytes[0..4], &ZIP_LOCAL_FILE_SIGNATURE.to_le_bytes());
+
+        // 終端のレコードが中央ディレクトリを指し、各エントリは46バイトと名前からなる
+        let end = &bytes[bytes.len() - 22..];
+        assert_eq!(
+            &end[0..4],
+            &ZIP_END_OF_CENTRAL_DIRECTORY_SIGNATURE.to_le_bytes()
+        );
+        let field = |i: usize| u32::from_le_bytes(end[i..i + 4].try_into().unwrap()) as usize;
+        let (size, offset) = (field(12), field(16));
+        assert_eq!(offset + size, bytes.len() - 22);
+        assert_eq!(size, 2 * (46 + "a.npy".len()));
+        assert_eq!(
+            &bytes[offset..offset + 4],
+            &ZIP_CENTRAL_DIRECTORY_SIGNATURE.to_le_bytes()
+        );
+        assert_eq!(
+            &bytes[offset + 51..offset + 55],
+            &ZIP_CENTRAL_DIRECTORY_SIGNATURE.to_le_bytes()
+        );
+
+        assert_eq!(
+            npz_from_bytes(&bytes).unwrap(),
+            vec![("a".to_string(), a), ("b".to_string(), b)]
+        );
+
+        let mut corrupted = bytes.clone();
+        corrupted[100] ^= 1;
+        assert!(npz_from_bytes(&corrupted).is_err());
+    }
+
+    #[test]
+    fn test_training_arrays() {
+        let board = BitBoard::new_initial();
+        let result = play_game(
+            &board,
+            Box::new(AiPlayer::new(1)),
+            Box::new(AiPlayer::new(1)),
+        );
+        let mut arrays = TrainingArrays::new();
+        arrays.add_game_result(&result);
+        let positions = result
+            .game_record
+            .iter()
+            .filter(|m| matches!(m, Move::Position(..)))
+            .count();
+        assert_eq!(arrays.len(), positions);
+
+        let path =
+            std::env::temp_dir().join(format!("training_arrays_test_{}.npz", std::process::id()));
+        arrays.save_npz(&path).unwrap();
+        let loaded = load_npz(&path).unwrap();
+        std::fs::remove_file(&path).unwrap();
+
+        let names = loaded
+            .iter()
+            .map(|(name, _)| name.as_str())
+            .collect::<Vec<_>>();
+        assert_eq!(names, ["boards", "policies", "values"]);
+        let (boards, policies, values) = (&loaded[0].1, &loaded[1].1, &loaded[2].1);
+        assert_eq!(boards.shape(), [positions, 8, 8, 3]);
+        assert_eq!(policies.shape(), [positions, 64]);
+        assert_eq!(values.shape(), [positions, 1]);
+
+        // 最初の局面は黒番の初期局面
+        let (boards, policies, values) = match (boards.data(), policies.data(), values.data()) {
+            (NpyData::F32(b), NpyData::F32(p), NpyData::F32(v)) => (b, p, v),
+            _ => panic!("dtype must be float32"),
+        };
+        let planes = board_planes(board.squares(), &PlayerColor::Black);
+        assert_eq!(&boards[..planes.len()], planes.as_slice());
+        let first_move = match result.game_record[0] {
+            Move::Position(_, position) => position_to_index(&position),
+            Move::Pass(_) => unreachable!(),
+        };
+        assert_eq!(policies[first_move], 1.0);
+        assert_eq!(policies[..SQUARE_COUNT].iter().sum::<f32>(), 1.0);
+        let expected = match result.state.black_count.cmp(&result.state.white_count) {
+            std::cmp::Ordering::Greater => 1.0,
+            std::cmp::Ordering::Less => -1.0,
+            std::cmp::Ordering::Equal => 0.0,
+        };
+        assert_eq!(values[0], expected);
+    }
+}
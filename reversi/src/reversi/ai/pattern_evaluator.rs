@@ -0,0 +1,644 @@
+use std::convert::TryInto;
+use std::io::{Error, ErrorKind};
+use std::path::Path;
+use std::sync::{Arc, OnceLock};
+
+use crate::board::{line_to_index, movable_position, Symmetry};
+use crate::reversi::common::*;
+
+use super::evaluator::{EvalResult, Evaluator};
+
+/// 評価値の石1個分の大きさ
+///
+/// 重みは最終石数差の単位で持ち、評価値はこれを掛けて整数にする。
+pub const DISC_VALUE: i32 = 100;
+
+/// 局面の段階の数の既定値
+pub const DEFAULT_PHASE_COUNT: usize = 15;
+
+/// 空きマス数の最大値(初期局面)
+const MAX_EMPTIES: u32 = 60;
+
+/// パターンのマス数の最大値
+const MAX_PATTERN_SIZE: usize = 10;
+
+/// 重みファイルの先頭の識別子
+const WEIGHT_FILE_MAGIC: &[u8; 4] = b"RVPW";
+
+/// 重みファイルの形式のバージョン
+///
+/// 2から、対称なパターンを逆向きに読んだ番号は[`canonical_index`]で同じ重みにまとめる。
+pub const WEIGHT_FILE_VERSION: u32 = 2;
+
+/// 重みファイルのヘッダの大きさ(識別子、バージョン、段階の数、1段階あたりの重みの数)
+const WEIGHT_FILE_HEADER_SIZE: usize = 16;
+
+/// 評価に使うパターン
+#[derive(Clone, Copy, PartialEq, Eq, Debug)]
+pub struct Pattern {
+    pub name: &'static str,
+    /// マスの番号(`r * 8 + c`)。先頭のマスがパターンの番号の最下位の桁になる
+    pub squares: &'static [usize],
+}
+
+impl Pattern {
+    /// パターンの状態の数(3のマス数乗)
+    pub const fn state_count(&self) -> usize {
+        3_usize.pow(self.squares.len() as u32)
+    }
+}
+
+/// パターンの一覧
+///
+/// 盤面上では対称変換で移した位置にも現れ、重みは共有する。
+pub const PATTERNS: [Pattern; 11] = [
+    // 辺とX打ちの2マス
+    Pattern {
+        name: "edge_2x",
+        squares: &[0, 1, 2, 3, 4, 5, 6, 7, 9, 14],
+    },
+    Pattern {
+        name: "corner_3x3",
+        squares: &[0, 1, 2, 8, 9, 10, 16, 17, 18],
+    },
+    Pattern {
+        name: "corner_2x5",
+        squares: &[0, 1, 2, 3, 4, 8, 9, 10, 11, 12],
+    },
+    Pattern {
+        name: "line2",
+        squares: &[8, 9, 10, 11, 12, 13, 14, 15],
+    },
+    Pattern {
+        name: "line3",
+        squares: &[16, 17, 18, 19, 20, 21, 22, 23],
+    },
+    Pattern {
+        name: "line4",
+        squares: &[24, 25, 26, 27, 28, 29, 30, 31],
+    },
+    Pattern {
+        name: "diagonal8",
+        squares: &[0, 9, 18, 27, 36, 45, 54, 63],
+    },
+    Pattern {
+        name: "diagonal7",
+        squares: &[1, 10, 19, 28, 37, 46, 55],
+    },
+    Pattern {
+        name: "diagonal6",
+        squares: &[2, 11, 20, 29, 38, 47],
+    },
+    Pattern {
+        name: "diagonal5",
+        squares: &[3, 12, 21, 30, 39],
+    },
+    Pattern {
+        name: "diagonal4",
+        squares: &[4, 13, 22, 31],
+    },
+];
+
+/// 盤面上のパターンの位置の数([`pattern_instances`]の長さ)
+pub const PATTERN_INSTANCE_COUNT: usize = 46;
+
+/// 1段階あたりのパターンの重みの数
+const PATTERN_WEIGHT_COUNT: usize = pattern_offset(PATTERNS.len());
+
+/// 着手可能数の差の重みの位置
+pub const MOBILITY_WEIGHT: usize = PATTERN_WEIGHT_COUNT;
+
+/// 偶数理論の重みの位置
+pub const PARITY_WEIGHT: usize = PATTERN_WEIGHT_COUNT + 1;
+
+/// 定数項の位置
+pub const BIAS_WEIGHT: usize = PATTERN_WEIGHT_COUNT + 2;
+
+/// 1段階あたりの重みの数
+pub const WEIGHTS_PER_PHASE: usize = PATTERN_WEIGHT_COUNT + 3;
+
+/// `pattern`番目のパターンの重みの開始位置
+pub const fn pattern_offset(pattern: usize) -> usize {
+    let mut offset = 0;
+    let mut i = 0;
+    while i < pattern {
+        offset += PATTERNS[i].state_count();
+        i += 1;
+    }
+    offset
+}
+
+/// 盤面上のパターンの位置
+#[derive(Clone, PartialEq, Eq, Debug)]
+pub struct PatternInstance {
+    /// [`PATTERNS`]の番号
+    pub pattern: usize,
+    /// [`Pattern::squares`]を対称変換で移したマス(並びも対応する)
+    pub squares: Vec<usize>,
+}
+
+/// 全てのパターンの盤面上の位置
+///
+/// 各パターンを8通りの対称変換で移し、マスの集合が同じになるものは1つにまとめる。
+/// まとめた位置を逆向きに読んだ番号は[`canonical_index`]で同じ番号にする。
+pub fn pattern_instances() -> &'static [PatternInstance] {
+    static INSTANCES: OnceLock<Vec<PatternInstance>> = OnceLock::new();
+    INSTANCES.get_or_init(|| {
+        let mut instances: Vec<PatternInstance> = Vec::new();
+        for (i, pattern) in PATTERNS.iter().enumerate() {
+            for symmetry in Symmetry::ALL {
+                let squares = pattern
+                    .squares
+                    .iter()
+                    .map(|&s| {
+                        position_to_index(&symmetry.transform_position(&index_to_position(s)))
+                    })
+                    .collect::<Vec<_>>();
+                if !instances
+                    .iter()
+                    .any(|instance| square_mask(&instance.squares) == square_mask(&squares))
+                {
+                    instances.push(PatternInstance {
+                        pattern: i,
+                        squares,
+                    });
+                }
+            }
+        }
+        instances
+    })
+}
+
+fn square_mask(squares: &[usize]) -> u64 {
+    squares.iter().fold(0, |mask, &s| mask | 1 << s)
+}
+
+/// 重みの位置`index`を、パターンのマスの集合を変えない対称変換で読み替えた位置のうち最小のものにする
+///
+/// 例えば行のパターンは左右を反転した番号と同じ重みを使う。
+/// これで評価関数は盤面の8通りの対称変換で変わらない。
+pub fn canonical_index(index: usize) -> usize {
+    static CANONICAL: OnceLock<Vec<u32>> = OnceLock::new();
+    let canonical = CANONICAL.get_or_init(|| {
+        let mut canonical = Vec::with_capacity(PATTERN_WEIGHT_COUNT);
+        for pattern in &PATTERNS {
+            let permutations = stabilizer_permutations(pattern);
+            let offset = canonical.len();
+            for state in 0..pattern.state_count() {
+                let min = permutations
+                    .iter()
+                    .map(|permutation| permute_index(state, permutation))
+                    .min()
+                    .unwrap();
+                canonical.push((offset + min) as u32);
+            }
+        }
+        canonical
+    });
+    canonical[index] as usize
+}
+
+/// パターンのマスの集合を変えない対称変換による、マスの並びの置換(恒等置換を含む)
+///
+/// `permutation[i]`は`i`番目のマスが移るマスの並びの位置。
+fn stabilizer_permutations(pattern: &Pattern) -> Vec<Vec<usize>> {
+    let mut permutations = Vec::new();
+    for symmetry in Symmetry::ALL {
+        let permutation = pattern
+            .squares
+            .iter()
+            .map(|&s| {
+                let t = position_to_index(&symmetry.transform_position(&index_to_position(s)));
+                pattern.squares.iter().position(|&x| x == t)
+            })
+            .collect::<Option<Vec<_>>>();
+        if let Some(permutation) = permutation {
+            if !permutations.contains(&permutation) {
+                permutations.push(permutation);
+            }
+        }
+    }
+    permutations
+}
+
+/// パターンの番号`state`の各桁を`permutation`で並べ替えた番号
+fn permute_index(state: usize, permutation: &[usize]) -> usize {
+    let mut rest = state;
+    let mut index = 0;
+    for &to in permutation {
+        index += rest % 3 * 3_usize.pow(to as u32);
+        rest /= 3;
+    }
+    index
+}
+
+/// 評価に使う特徴
+#[derive(Clone, PartialEq, Eq, Debug)]
+pub struct PatternFeatures {
+    pub empties: u32,
+    /// 各パターンの位置の状態に対応する重みの位置([`canonical_index`]で揃え、昇順に並べる)
+    ///
+    /// 昇順に並べるので、対称変換した盤面の特徴は元の盤面の特徴と等しい。
+    pub indices: [usize; PATTERN_INSTANCE_COUNT],
+    /// 手番側と相手の着手可能数の差
+    pub mobility: i32,
+    /// 空きマスが奇数なら1(手番側が最後に打てる)、偶数なら0
+    pub parity: i32,
+}
+
+impl PatternFeatures {
+    /// `color`の手番として盤面の特徴を求める
+    ///
+    /// パターンの番号は手番側の石を黒、相手の石を白とみなして求める。
+    pub fn new(board: &Squares, color: &PlayerColor) -> Self {
+        let (own, opponent) = match color {
+            PlayerColor::Black => (Square::Black, Square::White),
+            PlayerColor::White => (Square::White, Square::Black),
+        };
+
+        let mut player_data: u64 = 0;
+        let mut opponent_data: u64 = 0;
+        let mut squares = [Square::Empty; BOARD_SIZE * BOARD_SIZE];
+        for (i, square) in board.iter().enumerate() {
+            if *square == own {
+                player_data |= 1 << i;
+                squares[i] = Square::Black;
+            } else if *square == opponent {
+                opponent_data |= 1 << i;
+                squares[i] = Square::White;
+            }
+        }
+
+        let mut indices = [0; PATTERN_INSTANCE_COUNT];
+        let mut line = [Square::Empty; MAX_PATTERN_SIZE];
+        for (index, instance) in indices.iter_mut().zip(pattern_instances()) {
+            let line = &mut line[..instance.squares.len()];
+            for (l, &s) in line.iter_mut().zip(&instance.squares) {
+                *l = squares[s];
+            }
+            *index = canonical_index(pattern_offset(instance.pattern) + line_to_index(line));
+        }
+        indices.sort_unstable();
+
+        let empties = (!(player_data | opponent_data)).count_ones();
+        let mobility = movable_position(player_data, opponent_data).count_ones() as i32
+            - movable_position(opponent_data, player_data).count_ones() as i32;
+        PatternFeatures {
+            empties,
+            indices,
+            mobility,
+            parity: (empties % 2) as i32,
+        }
+    }
+}
+
+/// パターン評価関数の重み
+///
+/// 空きマス数で局面を段階に分け、段階ごとに別の重みを持つ。
+/// 各段階の重みはパターンごとの状態の重み([`PATTERNS`]の順)、着手可能数、偶数理論、定数項の順に並ぶ。
+#[derive(Clone, PartialEq, Debug)]
+pub struct PatternWeights {
+    phase_count: usize,
+    weights: Vec<f32>,
+}
+
+impl PatternWeights {
+    /// 全ての重みが0の重みを作成(段階の数は最低1)
+    pub fn new(phase_count: usize) -> Self {
+        let phase_count = phase_count.max(1);
+        PatternWeights {
+            phase_count,
+            weights: vec![0.0; phase_count * WEIGHTS_PER_PHASE],
+        }
+    }
+
+    pub fn phase_count(&self) -> usize {
+        self.phase_count
+    }
+
+    /// 空きマス数`empties`の局面の段階
+    pub fn phase(&self, empties: u32) -> usize {
+        empties.min(MAX_EMPTIES) as usize * self.phase_count / (MAX_EMPTIES as usize + 1)
+    }
+
+    /// `phase`の段階の重み
+    pub fn weights(&self, phase: usize) -> &[f32] {
+        &self.weights[phase * WEIGHTS_PER_PHASE..(phase + 1) * WEIGHTS_PER_PHASE]
+    }
+
+    pub fn weights_mut(&mut self, phase: usize) -> &mut [f32] {
+        &mut self.weights[phase * WEIGHTS_PER_PHASE..(phase + 1) * WEIGHTS_PER_PHASE]
+    }
+
+    /// 手番側から見た最終石数差の予測
+    pub fn evaluate(&self, features: &PatternFeatures) -> f32 {
+        let weights = self.weights(self.phase(features.empties));
+        let patterns = features.indices.iter().map(|&i| weights[i]).sum::<f32>();
+        patterns
+            + weights[MOBILITY_WEIGHT] * features.mobility as f32
+            + weights[PARITY_WEIGHT] * features.parity as f32
+            + weights[BIAS_WEIGHT]
+    }
+
+    /// 重みファイルの形式にする
+    ///
+    /// ヘッダ(識別子`RVPW`、バージョン、段階の数、1段階あたりの重みの数)の後に全ての重みが続く。
+    /// 数値は全てリトルエンディアンで、ヘッダはu32、重みはf32。
+    pub fn to_bytes(&self) -> Vec<u8> {
+        let mut bytes = Vec::with_capacity(WEIGHT_FILE_HEADER_SIZE + self.weights.len() * 4);
+        bytes.extend_from_slice(WEIGHT_FILE_MAGIC);
+        bytes.extend_from_slice(&WEIGHT_FILE_VERSION.to_le_bytes());
+        bytes.extend_from_slice(&(self.phase_count as u32).to_le_bytes());
+        bytes.extend_from_slice(&(WEIGHTS_PER_PHASE as u32).to_le_bytes());
+        for weight in &self.weights {
+            bytes.extend_from_slice(&weight.to_le_bytes());
+        }
+        bytes
+    }
+
+    /// [`PatternWeights::to_bytes`]の形式から読み込む
+    pub fn from_bytes(bytes: &[u8]) -> std::io::Result<Self> {
+        let invalid = |message: String| Error::new(ErrorKind::InvalidData, message);
+        if bytes.len() < WEIGHT_FILE_HEADER_SIZE || &bytes[0..4] != WEIGHT_FILE_MAGIC {
+            return Err(invalid("not a pattern weight file".to_string()));
+        }
+        let header = |i: usize| u32::from_le_bytes(bytes[i..i + 4].try_into().unwrap()) as usize;
+        let version = header(4) as u32;
+        if version != WEIGHT_FILE_VERSION {
+            return Err(invalid(format!(
+                "unsupported pattern weight file version: {}",
+                version
+            )));
+        }
+        let phase_count = header(8);
+        let weights_per_phase = header(12);
+        if phase_count == 0 || weights_per_phase != WEIGHTS_PER_PHASE {
+            return Err(invalid(format!(
+                "invalid pattern weight layout: {} phases, {} weights per phase",
+                phase_count, weights_per_phase
+            )));
+        }
+        let data = &bytes[WEIGHT_FILE_HEADER_SIZE..];
+        if data.len() != phase_count * WEIGHTS_PER_PHASE * 4 {
+            return Err(invalid(format!(
+                "pattern weight file size mismatch: {} bytes of weights",
+                data.len()
+            )));
+        }
+
+        let weights = data
+            .chunks_exact(4)
+            .map(|chunk| f32::from_le_bytes(chunk.try_into().unwrap()))
+            .collect();
+        Ok(PatternWeights {
+            phase_count,
+            weights,
+        })
+    }
+
+    pub fn save<P: AsRef<Path>>(&self, path: P) -> std::io::Result<()> {
+        std::fs::write(path, self.to_bytes())
+    }
+
+    pub fn load<P: AsRef<Path>>(path: P) -> std::io::Result<Self> {
+        Self::from_bytes(&std::fs::read(path)?)
+    }
+}
+
+/// パターンと着手可能数、偶数理論による評価関数
+///
+/// 重みは複製しても共有するので、スレッドごとに複製しても重みの分のメモリは増えない。
+#[derive(Clone, Debug)]
+pub struct PatternEvaluator {
+    weights: Arc<PatternWeights>,
+}
+
+impl PatternEvaluator {
+    pub fn new(weights: PatternWeights) -> Self {
+        PatternEvaluator {
+            weights: Arc::new(weights),
+        }
+    }
+
+    /// 重みファイルから作成
+    pub fn load<P: AsRef<Path>>(path: P) -> std::io::Result<Self> {
+        Ok(PatternEvaluator::new(PatternWeights::load(path)?))
+    }
+
+    pub fn weights(&self) -> &PatternWeights {
+        &self.weights
+    }
+}
+
+impl Evaluator for PatternEvaluator {
+    fn evaluate(&mut self, board: &Squares, color: &PlayerColor) -> EvalResult {
+        let features = PatternFeatures::new(board, color);
+        let value = self.weights.evaluate(&features) * DISC_VALUE as f32;
+        EvalResult {
+            value: value.round() as i32,
+            policy: None,
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::ai::random::XorShift;
+    use crate::board::{BitBoard, Board};
+
+    /// 初期局面から決まった手順で進めた局面
+    fn test_board() -> BitBoard {
+        let mut board = BitBoard::new_initial();
+        for ply in 0..20 {
+            let color = board.turn();
+            let moves = board.get_movable_positions(&color);
+            let m = if moves.is_empty() {
+                Move::new_pass(color)
+            } else {
+                Move::new_position(color, moves[(ply * 5) % moves.len()])
+            };
+            board = board.apply_move(&m).unwrap();
+        }
+        board
+    }
+
+    fn random_weights(phase_count: usize, seed: u64) -> PatternWeights {
+        let mut rng = XorShift::new(seed);
+        let mut weights = PatternWeights::new(phase_count);
+        for phase in 0..phase_count {
+            for weight in weights.weights_mut(phase) {
+                *weight = rng.normal() as f32;
+            }
+        }
+        weights
+    }
+
+    #[test]
+    fn test_instances() {
+        let instances = pattern_instances();
+        assert_eq!(instances.len(), PATTERN_INSTANCE_COUNT);
+        // 2x5は8通り、対角線は2通り、それ以外は4通り
+        for (i, pattern) in PATTERNS.iter().enumerate() {
+            let count = instances.iter().filter(|x| x.pattern == i).count();
+            let expected = match pattern.name {
+                "corner_2x5" => 8,
+                "diagonal8" => 2,
+                _ => 4,
+            };
+            assert_eq!(count, expected, "{}", pattern.name);
+        }
+        assert!(instances
+            .iter()
+            .all(|x| x.squares.len() <= MAX_PATTERN_SIZE && x.squares.iter().all(|&s| s < 64)));
+        assert_eq!(
+            pattern_offset(PATTERNS.len()),
+            2 * 3_usize.pow(10)
+                + 3_usize.pow(9)
+                + 4 * 3_usize.pow(8)
+                + 3_usize.pow(7)
+                + 729
+                + 243
+                + 81
+        );
+    }
+
+    #[test]
+    fn test_index_compatible_with_indexer() {
+        let board = test_board();
+        let squares = board.squares();
+        let black = PatternFeatures::new(squares, &PlayerColor::Black);
+        let white = PatternFeatures::new(squares, &PlayerColor::White);
+        let swapped = squares.map(|s| match s {
+            Square::Black => Square::White,
+            Square::White => Square::Black,
+            Square::Empty => Square::Empty,
+        });
+
+        let indices = |squares: &Squares| {
+            let mut indices = pattern_instances()
+                .iter()
+                .map(|instance| {
+                    let line = instance
+                        .squares
+                        .iter()
+                        .map(|&s| squares[s])
+                        .collect::<Vec<_>>();
+                    canonical_index(pattern_offset(instance.pattern) + line_to_index(&line))
+                })
+                .collect::<Vec<_>>();
+            indices.sort_unstable();
+            indices
+        };
+        assert_eq!(black.indices.to_vec(), indices(squares));
+        assert_eq!(white.indices.to_vec(), indices(&swapped));
+        // 3行目はIndexerの行の番号と、左右を反転した番号の小さい方
+        let mut row = squares[16..24].to_vec();
+        let index = line_to_index(&row);
+        row.reverse();
+        let expected = pattern_offset(4) + index.min(line_to_index(&row));
+        assert!(black.indices.contains(&expected));
+
+        assert_eq!(black.empties, board.empty_count());
+        assert_eq!(black.mobility, -white.mobility);
+        assert_eq!(black.parity, (board.empty_count() % 2) as i32);
+    }
+
+    #[test]
+    fn test_symmetry() {
+        // 対称変換で移した位置も同じパターンの位置として含まれる
+        let instances = pattern_instances();
+        for symmetry in Symmetry::ALL {
+            for instance in instances {
+                let transformed = symmetry.transform_data(square_mask(&instance.squares));
+                assert!(instances.iter().any(
+                    |x| x.pattern == instance.pattern && square_mask(&x.squares) == transformed
+                ));
+            }
+        }
+
+        // 対称変換した盤面でも特徴と評価値は同じ
+        let board = test_board();
+        let mut evaluator = PatternEvaluator::new(random_weights(DEFAULT_PHASE_COUNT, 5));
+        for color in [PlayerColor::Black, PlayerColor::White] {
+            let expected = PatternFeatures::new(board.squares(), &color);
+            let value = evaluator.evaluate(board.squares(), &color);
+            for symmetry in Symmetry::ALL {
+                let transformed = board.transform(symmetry);
+                let features = PatternFeatures::new(transformed.squares(), &color);
+                assert_eq!(features, expected);
+                assert_eq!(evaluator.evaluate(transformed.squares(), &color), value);
+            }
+        }
+    }
+
+    #[test]
+    fn test_canonical_index() {
+        for (i, pattern) in PATTERNS.iter().enumerate() {
+            let offset = pattern_offset(i);
+            let mut line = vec![Square::Empty; pattern.squares.len()];
+            line[0] = Square::Black;
+            let index = offset + line_to_index(&line);
+            line.reverse();
+            let reversed = offset + line_to_index(&line);
+            // 1マス目だけ黒の番号は、まとめた番号の中で最小
+            assert_eq!(canonical_index(index), index, "{}", pattern.name);
+            match pattern.name {
+                // 行と対角線は逆向きに読んだ番号も同じ位置になる
+                "edge_2x" | "corner_3x3" | "corner_2x5" => {}
+                _ => assert_eq!(canonical_index(reversed), index, "{}", pattern.name),
+            }
+        }
+        // 2x5はマスの集合を変えない対称変換がない
+        let offset = pattern_offset(2);
+        assert!((offset..pattern_offset(3)).all(|i| canonical_index(i) == i));
+        // 番号は対称変換でまとめた中で最小
+        assert!(
+            (0..pattern_offset(PATTERNS.len())).all(|i| canonical_index(i) <= i
+                && canonical_index(canonical_index(i)) == canonical_index(i))
+        );
+    }
+
+    #[test]
+    fn test_evaluate() {
+        let board = BitBoard::new_initial();
+        let mut weights = PatternWeights::new(DEFAULT_PHASE_COUNT);
+        let phase = weights.phase(board.empty_count());
+        assert_eq!(phase, DEFAULT_PHASE_COUNT - 1);
+        assert_eq!(weights.phase(0), 0);
+        weights.weights_mut(phase)[BIAS_WEIGHT] = 1.5;
+        weights.weights_mut(phase)[MOBILITY_WEIGHT] = 2.0;
+        let mut evaluator = PatternEvaluator::new(weights);
+        // 初期局面は着手可能数が同じで空きマスは偶数
+        let result = evaluator.evaluate(board.squares(), &PlayerColor::Black);
+        assert_eq!(result.value, 150);
+        assert_eq!(result.policy, None);
+    }
+
+    #[test]
+    fn test_save_and_load() {
+        let weights = random_weights(3, 2);
+        let path =
+            std::env::temp_dir().join(format!("pattern_weights_test_{}.bin", std::process::id()));
+        weights.save(&path).unwrap();
+        let mut loaded = PatternEvaluator::load(&path).unwrap();
+        std::fs::remove_file(&path).unwrap();
+        assert_eq!(loaded.weights(), &weights);
+
+        let board = test_board();
+        let mut evaluator = PatternEvaluator::new(weights.clone());
+        assert_eq!(
+            loaded.evaluate(board.squares(), &PlayerColor::White),
+            evaluator.evaluate(board.squares(), &PlayerColor::White)
+        );
+
+        let bytes = weights.to_bytes();
+        assert!(PatternWeights::from_bytes(&bytes[..bytes.len() - 4]).is_err());
+        let mut wrong_version = bytes.clone();
+        wrong_version[4] = 99;
+        assert!(PatternWeights::from_bytes(&wrong_version).is_err());
+        let mut wrong_magic = bytes;
+        wrong_magic[0] = b'X';
+        assert!(PatternWeights::from_bytes(&wrong_magic).is_err());
+    }
+}
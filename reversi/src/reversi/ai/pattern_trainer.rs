@@ -0,0 +1,423 @@
+use std::io::{Error, ErrorKind};
+use std::path::Path;
+
+use crate::{
+    board::{BitBoard, Board},
+    game::GameResult,
+    Move, PlayerColor, Position, Squares,
+};
+
+use super::endgame::final_score;
+use super::pattern_evaluator::{
+    PatternFeatures, PatternWeights, BIAS_WEIGHT, DEFAULT_PHASE_COUNT, MOBILITY_WEIGHT,
+    PARITY_WEIGHT,
+};
+use super::random::XorShift;
+
+/// 学習用の局面
+#[derive(Clone, PartialEq, Debug)]
+pub struct TrainingSample {
+    pub board: Squares,
+    /// 手番
+    pub color: PlayerColor,
+    /// `color`から見た最終石数差(空きマスは勝った側に加算する)
+    pub score: f32,
+}
+
+impl TrainingSample {
+    /// 対局結果の各局面に最終石数差を付ける
+    ///
+    /// パスしかできない局面は除く。
+    pub fn from_game_result<T>(result: &GameResult<T>) -> Vec<TrainingSample>
+    where
+        T: Board,
+    {
+        let last = BitBoard::new(&result.state.board, result.state.depth);
+        let black_score = final_score(last.black(), last.white()) as f32;
+        result
+            .history
+            .iter()
+            .zip(&result.game_record)
+            .filter_map(|(board, move_)| match move_ {
+                Move::Position(color, _) => Some(TrainingSample {
+                    board: *board.squares(),
+                    color: *color,
+                    score: match color {
+                        PlayerColor::Black => black_score,
+                        PlayerColor::White => -black_score,
+                    },
+                }),
+                Move::Pass(_) => None,
+            })
+            .collect()
+    }
+
+    /// 棋譜を初期局面から再生して各局面を取り出す
+    ///
+    /// 棋譜は`f5d6c3`のように列(a-h)と行(1-8)を並べたもの。パスは書かなくてよい。
+    /// 終局していない棋譜はエラーにする。
+    pub fn from_transcript(transcript: &str) -> std::io::Result<Vec<TrainingSample>> {
+        let invalid = |message: &str| {
+            Error::new(
+                ErrorKind::InvalidData,
+                format!("{}: {}", message, transcript),
+            )
+        };
+
+        let mut board = BitBoard::new_initial();
+        let mut history = Vec::new();
+        let mut game_record = Vec::new();
+        let chars = transcript.trim().chars().collect::<Vec<_>>();
+        for square in chars.chunks(2) {
+            let position = match square {
+                [c, r] => parse_position(*c, *r).ok_or_else(|| invalid("invalid square"))?,
+                _ => return Err(invalid("invalid square")),
+            };
+            let mut color = board.turn();
+            if board.get_movable_positions(&color).is_empty() {
+                let pass = Move::new_pass(color);
+                let next = board
+                    .apply_move(&pass)
+                    .ok_or_else(|| invalid("illegal move"))?;
+                history.push(board);
+                game_record.push(pass);
+                board = next;
+                color = color.opponent();
+            }
+            let move_ = Move::new_position(color, position);
+            let next = board
+                .apply_move(&move_)
+                .ok_or_else(|| invalid("illegal move"))?;
+            history.push(board);
+            game_record.push(move_);
+            board = next;
+        }
+        if !board.is_game_over() {
+            return Err(invalid("game is not finished"));
+        }
+
+        let result = GameResult {
+            state: crate::game::GameState::new(&board),
+            history,
+            game_record,
+        };
+        Ok(TrainingSample::from_game_result(&result))
+    }
+}
+
+/// `f5`のような表記の位置
+fn parse_position(column: char, row: char) -> Option<Position> {
+    let column = "abcdefgh".find(column.to_ascii_lowercase())?;
+    let row = "12345678".find(row)?;
+    Some(Position(row, column))
+}
+
+/// 1行に1局の棋譜を書いたファイルから局面を読み込む
+///
+/// 空行と`#`で始まる行は無視する。
+pub fn load_transcripts<P: AsRef<Path>>(path: P) -> std::io::Result<Vec<TrainingSample>> {
+    let text = std::fs::read_to_string(path)?;
+    let mut samples = Vec::new();
+    for line in text.lines() {
+        let line = line.trim();
+        if line.is_empty() || line.starts_with('#') {
+            continue;
+        }
+        samples.extend(TrainingSample::from_transcript(line)?);
+    }
+    Ok(samples)
+}
+
+/// 学習の設定
+#[derive(Clone, Copy, PartialEq, Debug)]
+pub struct TrainerConfig {
+    /// 局面の段階の数
+    pub phase_count: usize,
+    /// 全ての局面を使う回数
+    pub epochs: usize,
+    pub learning_rate: f32,
+    /// L2正則化の係数(定数項には使わない)
+    pub regularization: f32,
+    /// 検証に使う局面の割合
+    pub validation_ratio: f64,
+    /// 局面の分割と順番を決める乱数の種
+    pub seed: u64,
+}
+
+impl Default for TrainerConfig {
+    fn default() -> Self {
+        TrainerConfig {
+            phase_count: DEFAULT_PHASE_COUNT,
+            epochs: 10,
+            learning_rate: 0.002,
+            regularization: 0.0001,
+            validation_ratio: 0.1,
+            seed: 0,
+        }
+    }
+}
+
+/// 1エポックの誤差(最終石数差の平均二乗誤差)
+#[derive(Clone, Copy, PartialEq, Debug)]
+pub struct EpochError {
+    pub epoch: usize,
+    /// 学習に使った局面の誤差(更新しながら求めたもの)
+    pub train_error: f64,
+    /// 検証用の局面の誤差(検証用の局面がなければ`None`)
+    pub validation_error: Option<f64>,
+}
+
+/// 最終石数差を目的変数にして、パターン評価関数の重みを確率的勾配降下法で学習する
+///
+/// 各局面は空きマス数で決まる段階の重みだけを更新する。
+pub struct PatternTrainer {
+    config: TrainerConfig,
+    weights: PatternWeights,
+}
+
+impl PatternTrainer {
+    /// 全ての重みを0から学習する
+    pub fn new(config: TrainerConfig) -> Self {
+        PatternTrainer::with_weights(PatternWeights::new(config.phase_count), config)
+    }
+
+    /// 既存の重みから学習を続ける(段階の数は重みのものを使う)
+    pub fn with_weights(weights: PatternWeights, config: TrainerConfig) -> Self {
+        PatternTrainer {
+            config: TrainerConfig {
+                phase_count: weights.phase_count(),
+                ..config
+            },
+            weights,
+        }
+    }
+
+    pub fn config(&self) -> &TrainerConfig {
+        &self.config
+    }
+
+    pub fn weights(&self) -> &PatternWeights {
+        &self.weights
+    }
+
+    pub fn into_weights(self) -> PatternWeights {
+        self.weights
+    }
+
+    /// 局面を学習用と検証用に分けて、設定したエポック数だけ学習する
+    ///
+    /// パターン評価関数の特徴は対称変換で変わらないので、対称変換した局面を加える必要はない。
+    pub fn train(&mut self, samples: &[TrainingSample]) -> Vec<EpochError> {
+        let mut rng = XorShift::new(self.config.seed);
+        let mut order = (0..samples.len()).collect::<Vec<_>>();
+        shuffle(&mut order, &mut rng);
+        let validation_count = (samples.len() as f64 * self.config.validation_ratio) as usize;
+        let (validation, train) = order.split_at(validation_count);
+        let validation = validation.iter().map(|&i| &samples[i]).collect::<Vec<_>>();
+
+        let mut updates = train.to_vec();
+
+        (0..self.config.epochs)
+            .map(|epoch| {
+                shuffle(&mut updates, &mut rng);
+                let mut squared_error = 0.0;
+                for &i in &updates {
+                    let sample = &samples[i];
+                    let error = self.update(
+                        &PatternFeatures::new(&sample.board, &sample.color),
+                        sample.score,
+                    );
+                    squared_error += (error * error) as f64;
+                }
+                EpochError {
+                    epoch,
+                    train_error: squared_error / updates.len().max(1) as f64,
+                    validation_error: if validation.is_empty() {
+                        None
+                    } else {
+                        Some(self.error(validation.iter().copied()))
+                    },
+                }
+            })
+            .collect()
+    }
+
+    /// 局面の平均二乗誤差
+    pub fn error<'a, I>(&self, samples: I) -> f64
+    where
+        I: IntoIterator<Item = &'a TrainingSample>,
+    {
+        let mut squared_error = 0.0;
+        let mut count = 0;
+        for sample in samples {
+            let features = PatternFeatures::new(&sample.board, &sample.color);
+            let error = (sample.score - self.weights.evaluate(&features)) as f64;
+            squared_error += error * error;
+            count += 1;
+        }
+        squared_error / count.max(1) as f64
+    }
+
+    /// 1局面分の更新をして、更新前の誤差を返す
+    fn update(&mut self, features: &PatternFeatures, score: f32) -> f32 {
+        let error = score - self.weights.evaluate(features);
+        let rate = self.config.learning_rate;
+        let lambda = self.config.regularization;
+        let phase = self.weights.phase(features.empties);
+        let weights = self.weights.weights_mut(phase);
+        for &i in features.indices.iter() {
+            weights[i] += rate * (error - lambda * weights[i]);
+        }
+        weights[MOBILITY_WEIGHT] +=
+            rate * (error * features.mobility as f32 - lambda * weights[MOBILITY_WEIGHT]);
+        weights[PARITY_WEIGHT] +=
+            rate * (error * features.parity as f32 - lambda * weights[PARITY_WEIGHT]);
+        weights[BIAS_WEIGHT] += rate * error;
+        error
+    }
+}
+
+/// Fisher-Yatesのシャッフル
+fn shuffle<T>(items: &mut [T], rng: &mut XorShift) {
+    for i in (1..items.len()).rev() {
+        let j = rng.below(i as u64 + 1) as usize;
+        items.swap(i, j);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::ai::{Evaluator, PatternEvaluator};
+    use crate::game::{play_game, GameState};
+    use crate::player::Player;
+
+    /// 局面ごとに決まった手を選ぶプレイヤー
+    struct SeededPlayer(usize);
+
+    impl Player for SeededPlayer {
+        fn take_action(&self, state: &GameState) -> Move {
+            let board = BitBoard::new(&state.board, state.depth);
+            let moves = board.get_movable_positions(&state.turn);
+            if moves.is_empty() {
+                Move::new_pass(state.turn)
+            } else {
+                let i = (self.0 * 7 + state.depth as usize * 3) % moves.len();
+                Move::new_position(state.turn, moves[i])
+            }
+        }
+    }
+
+    fn game_samples(games: usize) -> Vec<TrainingSample> {
+        (0..games)
+            .flat_map(|seed| {
+                let board = BitBoard::new_initial();
+                let result = play_game(
+                    &board,
+                    Box::new(SeededPlayer(seed)),
+                    Box::new(SeededPlayer(seed + 1)),
+                );
+                TrainingSample::from_game_result(&result)
+            })
+            .collect()
+    }
+
+    #[test]
+    fn test_from_transcript() {
+        // 10手で白が全て取る棋譜
+        let samples = TrainingSample::from_transcript("f5f6e6f4e3d2d3d6c4b4").unwrap();
+        assert_eq!(samples.len(), 10);
+        assert_eq!(samples[0].board, *BitBoard::new_initial().squares());
+        assert_eq!(samples[0].color, PlayerColor::Black);
+        assert_eq!(samples[0].score, -64.0);
+        assert_eq!(samples[1].color, PlayerColor::White);
+        assert_eq!(samples[1].score, 64.0);
+
+        // 対局結果から取り出した局面と同じ
+        let board = BitBoard::new_initial();
+        let result = play_game(&board, Box::new(SeededPlayer(0)), Box::new(SeededPlayer(1)));
+        let transcript = result
+            .game_record
+            .iter()
+            .filter_map(|move_| match move_ {
+                Move::Position(_, Position(r, c)) => {
+                    Some(format!("{}{}", (b'a' + *c as u8) as char, r + 1))
+                }
+                Move::Pass(_) => None,
+            })
+            .collect::<String>();
+        assert_eq!(
+            TrainingSample::from_transcript(&transcript).unwrap(),
+            TrainingSample::from_game_result(&result)
+        );
+
+        assert!(TrainingSample::from_transcript("f5f5").is_err());
+        assert!(TrainingSample::from_transcript("f5z9").is_err());
+        assert!(TrainingSample::from_transcript("f5d").is_err());
+        assert!(TrainingSample::from_transcript("f5d6").is_err());
+    }
+
+    #[test]
+    fn test_load_transcripts() {
+        let path =
+            std::env::temp_dir().join(format!("transcripts_test_{}.txt", std::process::id()));
+        std::fs::write(
+            &path,
+            "# games\nf5f6e6f4e3d2d3d6c4b4\n\nF5F6E6F4E3D2D3D6C4B4\n",
+        )
+        .unwrap();
+        let samples = load_transcripts(&path).unwrap();
+        std::fs::remove_file(&path).unwrap();
+        assert_eq!(samples.len(), 20);
+        assert_eq!(samples[..10], samples[10..]);
+    }
+
+    #[test]
+    fn test_train() {
+        let samples = game_samples(20);
+        let config = TrainerConfig {
+            phase_count: 4,
+            epochs: 5,
+            validation_ratio: 0.2,
+            ..TrainerConfig::default()
+        };
+        let mut trainer = PatternTrainer::new(config);
+        let initial_error = trainer.error(&samples);
+        let errors = trainer.train(&samples);
+        assert_eq!(errors.len(), 5);
+        assert!(errors.iter().all(|e| e.validation_error.is_some()));
+        assert!(errors[4].train_error < errors[0].train_error);
+        assert!(trainer.error(&samples) < initial_error);
+
+        // 学習した重みを評価関数で読み込める
+        let weights = trainer.into_weights();
+        let path =
+            std::env::temp_dir().join(format!("trained_weights_test_{}.bin", std::process::id()));
+        weights.save(&path).unwrap();
+        let mut evaluator = PatternEvaluator::load(&path).unwrap();
+        std::fs::remove_file(&path).unwrap();
+        let sample = &samples[0];
+        let expected = weights.evaluate(&PatternFeatures::new(&sample.board, &sample.color));
+        assert_eq!(
+            evaluator.evaluate(&sample.board, &sample.color).value,
+            (expected * crate::ai::DISC_VALUE as f32).round() as i32
+        );
+    }
+
+    #[test]
+    fn test_same_seed_same_weights() {
+        let samples = game_samples(4);
+        let config = TrainerConfig {
+            phase_count: 2,
+            epochs: 2,
+            validation_ratio: 0.0,
+            ..TrainerConfig::default()
+        };
+        let mut a = PatternTrainer::new(config);
+        let mut b = PatternTrainer::new(config);
+        let errors = a.train(&samples);
+        assert_eq!(errors, b.train(&samples));
+        assert_eq!(errors[0].validation_error, None);
+        assert_eq!(a.weights(), b.weights());
+    }
+}
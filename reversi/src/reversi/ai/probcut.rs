@@ -0,0 +1,361 @@
+use std::collections::BTreeMap;
+use std::io::{Error, ErrorKind};
+use std::ops::RangeInclusive;
+use std::path::Path;
+
+use crate::{
+    board::{BitBoard, Board},
+    PlayerColor,
+};
+
+use super::nega_alpha::{NegaAlpha, NegaAlphaEvaluationFunction};
+
+/// 空きマス数をいくつごとにまとめてパラメータを持つか
+const EMPTIES_PER_BUCKET: u32 = 10;
+
+/// Multi-ProbCutで枝刈りする確からしさ
+///
+/// 浅い探索から予測した値が、深い探索の値の信頼区間の外にあれば枝刈りする。
+/// 割合が小さいほど多く枝刈りする。
+#[derive(Clone, Copy, PartialEq, Eq, Hash, Debug)]
+pub enum Selectivity {
+    P73,
+    P87,
+    P95,
+    P98,
+    P99,
+    /// 枝刈りしない
+    NoCut,
+}
+
+impl Selectivity {
+    pub const ALL: [Selectivity; 6] = [
+        Selectivity::P73,
+        Selectivity::P87,
+        Selectivity::P95,
+        Selectivity::P98,
+        Selectivity::P99,
+        Selectivity::NoCut,
+    ];
+
+    /// 信頼区間の幅(標準偏差の何倍か)
+    pub fn t(&self) -> Option<f64> {
+        match self {
+            Selectivity::P73 => Some(1.1),
+            Selectivity::P87 => Some(1.5),
+            Selectivity::P95 => Some(2.0),
+            Selectivity::P98 => Some(2.6),
+            Selectivity::P99 => Some(3.3),
+            Selectivity::NoCut => None,
+        }
+    }
+
+    /// 信頼区間の割合(%)
+    pub fn percentile(&self) -> u32 {
+        match self {
+            Selectivity::P73 => 73,
+            Selectivity::P87 => 87,
+            Selectivity::P95 => 95,
+            Selectivity::P98 => 98,
+            Selectivity::P99 => 99,
+            Selectivity::NoCut => 100,
+        }
+    }
+}
+
+/// 深さ`depth`の探索の値を予測するための浅い探索の深さ
+///
+/// 手番による評価値の偏りを避けるため、深さの偶奇をそろえる。
+pub fn probcut_shallow_depth(depth: usize) -> usize {
+    depth - 2 * ((depth + 2) / 4)
+}
+
+/// 浅い探索の値`v`から深い探索の値を`a * v + b`と予測する回帰式
+#[derive(Clone, Copy, PartialEq, Debug)]
+pub struct ProbCutRegression {
+    pub shallow_depth: usize,
+    pub a: f64,
+    pub b: f64,
+    /// 予測の誤差の標準偏差
+    pub sigma: f64,
+}
+
+impl ProbCutRegression {
+    /// (浅い探索の値, 深い探索の値)の組から最小二乗法で求める
+    ///
+    /// 組が2つ未満か、浅い探索の値がすべて同じ場合は`None`を返す。
+    pub fn fit(shallow_depth: usize, samples: &[(i32, i32)]) -> Option<Self> {
+        if samples.len() < 2 {
+            return None;
+        }
+        let n = samples.len() as f64;
+        let mean_s = samples.iter().map(|(s, _)| *s as f64).sum::<f64>() / n;
+        let mean_d = samples.iter().map(|(_, d)| *d as f64).sum::<f64>() / n;
+        let (cov, var) = samples.iter().fold((0.0, 0.0), |(cov, var), (s, d)| {
+            let ds = *s as f64 - mean_s;
+            let dd = *d as f64 - mean_d;
+            (cov + ds * dd, var + ds * ds)
+        });
+        if var == 0.0 {
+            return None;
+        }
+        let a = cov / var;
+        let b = mean_d - a * mean_s;
+        let squared_error = samples
+            .iter()
+            .map(|(s, d)| {
+                let e = *d as f64 - (a * *s as f64 + b);
+                e * e
+            })
+            .sum::<f64>();
+        Some(ProbCutRegression {
+            shallow_depth,
+            a,
+            b,
+            sigma: (squared_error / n).sqrt(),
+        })
+    }
+}
+
+/// (深さ, 空きマス数)ごとの回帰式
+#[derive(Clone, Default, PartialEq, Debug)]
+pub struct ProbCutParameters {
+    regressions: BTreeMap<(usize, u32), ProbCutRegression>,
+}
+
+impl ProbCutParameters {
+    pub fn new() -> Self {
+        Default::default()
+    }
+
+    pub fn get(&self, depth: usize, empties: u32) -> Option<&ProbCutRegression> {
+        self.regressions.get(&(depth, empties / EMPTIES_PER_BUCKET))
+    }
+
+    pub fn insert(&mut self, depth: usize, empties: u32, regression: ProbCutRegression) {
+        self.regressions
+            .insert((depth, empties / EMPTIES_PER_BUCKET), regression);
+    }
+
+    pub fn len(&self) -> usize {
+        self.regressions.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.regressions.is_empty()
+    }
+
+    /// `positions`を深さ`depths`と浅い探索で探索して回帰式を求める
+    ///
+    /// `nega_alpha`は枝刈りをしない設定で渡すこと。
+    pub fn calibrate<E>(
+        nega_alpha: &mut NegaAlpha<E>,
+        positions: &[(BitBoard, PlayerColor)],
+        depths: RangeInclusive<usize>,
+    ) -> Self
+    where
+        E: NegaAlphaEvaluationFunction,
+    {
+        let mut samples: BTreeMap<(usize, u32), Vec<(i32, i32)>> = BTreeMap::new();
+        for (board, color) in positions {
+            let empties = board.empty_count();
+            for depth in depths.clone() {
+                let shallow_depth = probcut_shallow_depth(depth);
+                let shallow = nega_alpha.search_board(board, *color, shallow_depth);
+                let deep = nega_alpha.search_board(board, *color, depth);
+                samples
+                    .entry((depth, empties / EMPTIES_PER_BUCKET))
+                    .or_default()
+                    .push((shallow.score, deep.score));
+            }
+        }
+
+        let regressions = samples
+            .into_iter()
+            .filter_map(|((depth, bucket), samples)| {
+                let regression = ProbCutRegression::fit(probcut_shallow_depth(depth), &samples)?;
+                Some(((depth, bucket), regression))
+            })
+            .collect();
+        ProbCutParameters { regressions }
+    }
+
+    /// 1行に1つの回帰式を書いた文字列にする
+    ///
+    /// 各行は「深さ 空きマス数 浅い探索の深さ a b sigma」。空きマス数はまとめた範囲の最小値。
+    pub fn to_text(&self) -> String {
+        let mut text = String::from("# depth empties shallow_depth a b sigma\n");
+        for ((depth, bucket), r) in &self.regressions {
+            text.push_str(&format!(
+                "{} {} {} {} {} {}\n",
+                depth,
+                bucket * EMPTIES_PER_BUCKET,
+                r.shallow_depth,
+                r.a,
+                r.b,
+                r.sigma
+            ));
+        }
+        text
+    }
+
+    /// [`ProbCutParameters::to_text`]の形式から読み込む
+    pub fn from_text(text: &str) -> std::io::Result<Self> {
+        let invalid = |line: &str| {
+            Error::new(
+                ErrorKind::InvalidData,
+                format!("invalid probcut parameter: {}", line),
+            )
+        };
+
+        let mut parameters = ProbCutParameters::new();
+        for line in text.lines() {
+            let line = line.trim();
+            if line.is_empty() || line.starts_with('#') {
+                continue;
+            }
+            let fields = line.split_whitespace().collect::<Vec<_>>();
+            if fields.len() != 6 {
+                return Err(invalid(line));
+            }
+            let depth = fields[0].parse().map_err(|_| invalid(line))?;
+            let empties = fields[1].parse().map_err(|_| invalid(line))?;
+            let regression = ProbCutRegression {
+                shallow_depth: fields[2].parse().map_err(|_| invalid(line))?,
+                a: fields[3].parse().map_err(|_| invalid(line))?,
+                b: fields[4].parse().map_err(|_| invalid(line))?,
+                sigma: fields[5].parse().map_err(|_| invalid(line))?,
+            };
+            parameters.insert(depth, empties, regression);
+        }
+        Ok(parameters)
+    }
+
+    pub fn save<P: AsRef<Path>>(&self, path: P) -> std::io::Result<()> {
+        std::fs::write(path, self.to_text())
+    }
+
+    pub fn load<P: AsRef<Path>>(path: P) -> std::io::Result<Self> {
+        Self::from_text(&std::fs::read_to_string(path)?)
+    }
+}
+
+/// Multi-ProbCutの設定
+#[derive(Clone, PartialEq, Debug)]
+pub struct ProbCut {
+    pub parameters: ProbCutParameters,
+    pub selectivity: Selectivity,
+}
+
+impl ProbCut {
+    pub fn new(parameters: ProbCutParameters, selectivity: Selectivity) -> Self {
+        ProbCut {
+            parameters,
+            selectivity,
+        }
+    }
+
+    /// 残り深さ`depth`、空きマス`empties`のノードで使う回帰式と信頼区間の幅
+    pub fn regression(&self, depth: usize, empties: u32) -> Option<(ProbCutRegression, f64)> {
+        let t = self.selectivity.t()?;
+        let regression = self.parameters.get(depth, empties)?;
+        if regression.a <= 0.0 || regression.shallow_depth >= depth {
+            return None;
+        }
+        Some((*regression, t))
+    }
+}
+
+#[cfg(test)]
+pub(crate) mod tests {
+    use super::*;
+    use crate::ai::SimpleNegaAlphaEvaluationFunction;
+    use crate::Move;
+
+    /// 初期局面から決まった手順で進めた局面
+    pub(crate) fn test_positions() -> Vec<(BitBoard, PlayerColor)> {
+        let mut positions = Vec::new();
+        for seed in 0..6 {
+            let mut board = BitBoard::new_initial();
+            for ply in 0..(10 + seed * 2) {
+                let color = board.turn();
+                let moves = board.get_movable_positions(&color);
+                let m = if moves.is_empty() {
+                    Move::new_pass(color)
+                } else {
+                    Move::new_position(color, moves[(seed * 7 + ply * 3) % moves.len()])
+                };
+                board = board.apply_move(&m).unwrap();
+            }
+            let color = board.turn();
+            positions.push((board, color));
+        }
+        positions
+    }
+
+    #[test]
+    fn test_shallow_depth() {
+        for depth in 3..20 {
+            let shallow = probcut_shallow_depth(depth);
+            assert!(shallow >= 1);
+            assert!(shallow < depth);
+            assert_eq!(shallow % 2, depth % 2);
+        }
+    }
+
+    #[test]
+    fn test_fit() {
+        let samples = [(0, 1), (1, 3), (2, 5), (3, 7)];
+        let r = ProbCutRegression::fit(2, &samples).unwrap();
+        assert!((r.a - 2.0).abs() < 1e-9);
+        assert!((r.b - 1.0).abs() < 1e-9);
+        assert!(r.sigma.abs() < 1e-9);
+
+        let samples = [(0, 0), (0, 2), (2, 2), (2, 4)];
+        let r = ProbCutRegression::fit(2, &samples).unwrap();
+        assert!((r.a - 1.0).abs() < 1e-9);
+        assert!((r.b - 1.0).abs() < 1e-9);
+        assert!((r.sigma - 1.0).abs() < 1e-9);
+
+        assert!(ProbCutRegression::fit(2, &[(1, 1)]).is_none());
+        assert!(ProbCutRegression::fit(2, &[(1, 1), (1, 2)]).is_none());
+    }
+
+    #[test]
+    fn test_save_and_load() {
+        let mut nega_alpha = NegaAlpha::new(SimpleNegaAlphaEvaluationFunction::new());
+        let parameters = ProbCutParameters::calibrate(&mut nega_alpha, &test_positions(), 3..=4);
+        assert!(!parameters.is_empty());
+
+        let path = std::env::temp_dir().join(format!("probcut_test_{}.txt", std::process::id()));
+        parameters.save(&path).unwrap();
+        let loaded = ProbCutParameters::load(&path).unwrap();
+        std::fs::remove_file(&path).unwrap();
+        assert_eq!(loaded, parameters);
+
+        assert!(ProbCutParameters::from_text("3 10 1 1.0 0.0").is_err());
+        assert!(ProbCutParameters::from_text("3 10 1 x 0.0 1.0").is_err());
+    }
+
+    #[test]
+    fn test_search_with_probcut() {
+        let mut nega_alpha = NegaAlpha::new(SimpleNegaAlphaEvaluationFunction::new());
+        let parameters = ProbCutParameters::calibrate(&mut nega_alpha, &test_positions(), 3..=5);
+
+        let (board, color) = test_positions().swap_remove(0);
+        let expected = nega_alpha.search_board(&board, color, 6);
+
+        // 枝刈りしない設定なら同じ値
+        let mut exact = NegaAlpha::new(SimpleNegaAlphaEvaluationFunction::new())
+            .with_probcut(ProbCut::new(parameters.clone(), Selectivity::NoCut));
+        let result = exact.search_board(&board, color, 6);
+        assert_eq!(result.score, expected.score);
+        assert_eq!(result.nodes, expected.nodes);
+
+        let mut selective = NegaAlpha::new(SimpleNegaAlphaEvaluationFunction::new())
+            .with_probcut(ProbCut::new(parameters, Selectivity::P73));
+        let result = selective.search_board(&board, color, 6);
+        assert!(board.apply_move(&result.best_move.unwrap()).is_some());
+        assert!(result.nodes < expected.nodes);
+    }
+}
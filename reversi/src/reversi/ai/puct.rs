@@ -0,0 +1,690 @@
+use std::time::{Duration, Instant};
+
+use crate::{
+    board::{BitBoard, Board},
+    position_to_index, Move, PlayerColor, BOARD_SIZE,
+};
+
+use super::evaluator::{EvalResult, Evaluator};
+use super::mcts::MctsLimits;
+use super::node::next_moves;
+use super::random::XorShift;
+use super::search_position::SearchPosition;
+
+/// 評価値の既定の尺度(ネットワークの出力[-1, 1]を1000倍した整数を想定)
+pub const DEFAULT_VALUE_SCALE: i32 = 1000;
+
+/// 時間を確認する間隔(シミュレーション回数)
+const TIME_CHECK_INTERVAL: u64 = 64;
+
+/// PUCT探索の設定
+#[derive(Clone, Copy, PartialEq, Debug)]
+pub struct PuctConfig {
+    /// 事前確率による探索項の係数
+    pub c_puct: f64,
+    /// この評価値を勝ち(1)、符号を反転した値を負け(-1)とみなす
+    pub value_scale: i32,
+    /// ルートの事前確率に混ぜるディリクレノイズのパラメータ
+    pub dirichlet_alpha: f64,
+    /// ディリクレノイズを混ぜる割合(0ならノイズなし)
+    pub dirichlet_epsilon: f64,
+    /// 手を選ぶときの温度(0なら最も訪問回数の多い手)
+    pub temperature: f64,
+    /// 温度を使って手を選ぶ手数(これ以降は最も訪問回数の多い手を選ぶ)
+    pub temperature_moves: u32,
+    /// まとめて評価する葉の数
+    ///
+    /// 評価待ちの経路には仮想的な負けを加えて、同じ葉ばかり選ばないようにする。
+    pub batch_size: usize,
+}
+
+impl Default for PuctConfig {
+    /// 対局用の設定(ノイズなし、常に最も訪問回数の多い手)
+    fn default() -> Self {
+        PuctConfig {
+            c_puct: 1.5,
+            value_scale: DEFAULT_VALUE_SCALE,
+            dirichlet_alpha: 0.5,
+            dirichlet_epsilon: 0.0,
+            temperature: 1.0,
+            temperature_moves: 0,
+            batch_size: 1,
+        }
+    }
+}
+
+impl PuctConfig {
+    /// 自己対局用の設定(ルートにノイズを混ぜ、序盤は訪問回数に比例した確率で手を選ぶ)
+    pub fn self_play() -> Self {
+        PuctConfig {
+            dirichlet_epsilon: 0.25,
+            temperature_moves: 20,
+            ..Default::default()
+        }
+    }
+}
+
+/// ルートの手ごとの統計
+#[derive(Clone, Copy, PartialEq, Debug)]
+pub struct PuctMoveStats {
+    pub move_: Move,
+    /// 訪問回数
+    pub visits: u64,
+    /// 評価関数の方策による事前確率
+    pub prior: f64,
+    /// 手を指した側から見た平均価値([-1, 1])
+    pub value: f64,
+}
+
+/// PUCT探索の結果
+#[derive(Clone, Debug)]
+pub struct PuctResult {
+    /// 最も訪問回数の多い手(終局している場合は`None`)
+    pub best_move: Option<Move>,
+    /// 手番側から見たルートの平均価値([-1, 1])
+    pub value: f64,
+    /// ルートの手ごとの統計(訪問回数の多い順)
+    pub moves: Vec<PuctMoveStats>,
+    /// この探索で行ったシミュレーション回数(再利用した木の分は含まない)
+    pub simulations: u64,
+    /// 木のノード数
+    pub nodes: usize,
+    pub elapsed: Duration,
+}
+
+impl PuctResult {
+    /// 訪問回数の割合をマスごとに並べた方策(学習の教師データ用、パスは含まない)
+    pub fn visit_policy(&self) -> [f32; BOARD_SIZE * BOARD_SIZE] {
+        let mut policy = [0.0; BOARD_SIZE * BOARD_SIZE];
+        let total = self.moves.iter().map(|stats| stats.visits).sum::<u64>();
+        if total == 0 {
+            return policy;
+        }
+        for stats in self.moves.iter() {
+            if let Move::Position(_, position) = stats.move_ {
+                policy[position_to_index(&position)] = stats.visits as f32 / total as f32;
+            }
+        }
+        policy
+    }
+}
+
+struct PuctNode {
+    /// このノードに至る手(ルートは`None`)
+    move_: Option<Move>,
+    /// 局面のハッシュ値
+    hash: u64,
+    prior: f64,
+    visits: u64,
+    /// このノードに至る手を指した側から見た価値の合計
+    value_sum: f64,
+    children: Vec<usize>,
+    expanded: bool,
+}
+
+impl PuctNode {
+    fn new(move_: Option<Move>, hash: u64, prior: f64) -> Self {
+        PuctNode {
+            move_,
+            hash,
+            prior,
+            visits: 0,
+            value_sum: 0.0,
+            children: Vec::new(),
+            expanded: false,
+        }
+    }
+
+    fn value(&self) -> f64 {
+        if self.visits == 0 {
+            0.0
+        } else {
+            self.value_sum / self.visits as f64
+        }
+    }
+}
+
+/// AlphaZero方式のPUCT探索
+///
+/// 評価関数の方策を事前確率に、評価値をプレイアウトの代わりに使う。
+/// 方策がない評価関数では事前確率を一様にする。
+/// 探索木は次の探索に持ち越し、ルートの子孫の局面を探索するときはその部分木を再利用する。
+pub struct Puct<E>
+where
+    E: Evaluator,
+{
+    evaluator: E,
+    config: PuctConfig,
+    rng: XorShift,
+    nodes: Vec<PuctNode>,
+    root_color: PlayerColor,
+    /// ルートの子ごとのディリクレノイズ
+    root_noise: Vec<f64>,
+}
+
+impl<E> Puct<E>
+where
+    E: Evaluator,
+{
+    pub fn new(evaluator: E, config: PuctConfig, seed: u64) -> Self {
+        Puct {
+            evaluator,
+            config,
+            rng: XorShift::new(seed),
+            nodes: Vec::new(),
+            root_color: PlayerColor::Black,
+            root_noise: Vec::new(),
+        }
+    }
+
+    pub fn evaluator(&self) -> &E {
+        &self.evaluator
+    }
+
+    pub fn evaluator_mut(&mut self) -> &mut E {
+        &mut self.evaluator
+    }
+
+    pub fn config(&self) -> &PuctConfig {
+        &self.config
+    }
+
+    /// 木のノード数
+    pub fn tree_size(&self) -> usize {
+        self.nodes.len()
+    }
+
+    /// 探索木を捨てる
+    pub fn clear(&mut self) {
+        self.nodes.clear();
+        self.root_noise.clear();
+    }
+
+    /// 探索木を捨て、乱数の種を設定し直す(新しい対局の前に使う)
+    pub fn reset(&mut self, seed: u64) {
+        self.clear();
+        self.rng = XorShift::new(seed);
+    }
+
+    /// 制限に達するまでシミュレーションを繰り返す
+    ///
+    /// 前の探索のルートかその子・孫の局面なら、その部分木を引き継ぐ。
+    /// `limits.iterations`はこの探索で行うシミュレーション回数。
+    pub fn search(
+        &mut self,
+        board: &BitBoard,
+        color: PlayerColor,
+        limits: &MctsLimits,
+    ) -> PuctResult {
+        let start = Instant::now();
+        let deadline = limits.time.map(|time| start + time);
+        self.reuse_or_reset(board, color);
+
+        let mut position = SearchPosition::new(board.clone(), color);
+        if !self.nodes[0].expanded {
+            let result = self.evaluator.evaluate(board.squares(), &color);
+            self.expand(0, board, color, &result);
+        }
+        self.root_noise = if self.config.dirichlet_epsilon > 0.0 {
+            let n = self.nodes[0].children.len();
+            self.rng.dirichlet(self.config.dirichlet_alpha, n)
+        } else {
+            Vec::new()
+        };
+
+        let batch_size = self.config.batch_size.max(1) as u64;
+        let mut simulations = 0;
+        let mut next_time_check = 0;
+        while simulations < limits.iterations {
+            if simulations >= next_time_check {
+                if let Some(deadline) = deadline {
+                    if Instant::now() >= deadline {
+                        break;
+                    }
+                }
+                next_time_check = simulations + TIME_CHECK_INTERVAL;
+            }
+            let batch = batch_size.min(limits.iterations - simulations);
+            self.simulate_batch(&mut position, batch as usize);
+            simulations += batch;
+        }
+
+        let mut moves = self.nodes[0]
+            .children
+            .iter()
+            .map(|&child| {
+                let node = &self.nodes[child];
+                PuctMoveStats {
+                    move_: node.move_.unwrap(),
+                    visits: node.visits,
+                    prior: node.prior,
+                    value: node.value(),
+                }
+            })
+            .collect::<Vec<_>>();
+        moves.sort_by_key(|stats| std::cmp::Reverse(stats.visits));
+        PuctResult {
+            best_move: moves.first().map(|stats| stats.move_),
+            value: -self.nodes[0].value(),
+            moves,
+            simulations,
+            nodes: self.nodes.len(),
+            elapsed: start.elapsed(),
+        }
+    }
+
+    /// 直前の探索結果から手を選ぶ
+    ///
+    /// `move_count`(初期局面からの手数)が[`PuctConfig::temperature_moves`]未満なら、
+    /// 訪問回数の1/温度乗に比例した確率で選ぶ。
+    pub fn select_move(&mut self, result: &PuctResult, move_count: u32) -> Option<Move> {
+        let temperature = self.config.temperature;
+        if move_count >= self.config.temperature_moves || temperature <= 0.0 {
+            return result.best_move;
+        }
+        let weights = result
+            .moves
+            .iter()
+            .map(|stats| (stats.visits as f64).powf(1.0 / temperature))
+            .collect::<Vec<_>>();
+        let total = weights.iter().sum::<f64>();
+        if total <= 0.0 {
+            return result.best_move;
+        }
+        let mut r = self.rng.uniform() * total;
+        for (stats, weight) in result.moves.iter().zip(weights) {
+            if r <= weight {
+                return Some(stats.move_);
+            }
+            r -= weight;
+        }
+        result.best_move
+    }
+
+    /// `move_`を指した後の局面をルートにする(部分木は残す)
+    ///
+    /// 探索していない手なら木を捨てる。
+    pub fn advance(&mut self, move_: &Move) {
+        let child = self.nodes.first().and_then(|root| {
+            root.children
+                .iter()
+                .copied()
+                .find(|&child| self.nodes[child].move_ == Some(*move_))
+        });
+        match child {
+            Some(child) => {
+                self.reroot(child);
+                self.root_color = self.root_color.opponent();
+            }
+            None => self.clear(),
+        }
+    }
+
+    /// 今のルートかその子・孫が`board`なら、そこをルートにする
+    fn reuse_or_reset(&mut self, board: &BitBoard, color: PlayerColor) {
+        let hash = board.hash();
+        if let Some(root) = self.nodes.first() {
+            if root.hash == hash && self.root_color == color {
+                return;
+            }
+            // 相手の手の後、または自分と相手の手の後
+            let found = root.children.iter().find_map(|&child| {
+                let node = &self.nodes[child];
+                if node.hash == hash && self.root_color.opponent() == color {
+                    return Some(vec![child]);
+                }
+                node.children
+                    .iter()
+                    .find(|&&grandchild| {
+                        self.nodes[grandchild].hash == hash && self.root_color == color
+                    })
+                    .map(|&grandchild| vec![child, grandchild])
+            });
+            if let Some(path) = found {
+                self.reroot(*path.last().unwrap());
+                self.root_color = color;
+                return;
+            }
+        }
+        self.nodes.clear();
+        self.nodes.push(PuctNode::new(None, hash, 1.0));
+        self.root_color = color;
+    }
+
+    /// `node`の部分木だけを残して`node`をルートにする
+    fn reroot(&mut self, node: usize) {
+        let mut nodes = Vec::new();
+        let mut old = std::mem::take(&mut self.nodes);
+        let mut stack: Vec<(usize, Option<usize>)> = vec![(node, None)];
+        while let Some((index, parent)) = stack.pop() {
+            let new_index = nodes.len();
+            let placeholder = PuctNode::new(None, 0, 0.0);
+            let mut moved = std::mem::replace(&mut old[index], placeholder);
+            let children = std::mem::take(&mut moved.children);
+            nodes.push(moved);
+            if let Some(parent) = parent {
+                nodes[parent].children.push(new_index);
+            }
+            // 子の順序を保つため逆順に積む
+            for &child in children.iter().rev() {
+                stack.push((child, Some(new_index)));
+            }
+        }
+        nodes[0].move_ = None;
+        self.nodes = nodes;
+        self.root_noise.clear();
+    }
+
+    /// `batch_size`回分の選択を行い、葉をまとめて評価して展開・逆伝播する
+    ///
+    /// `position`は終了後に元の局面に戻る。
+    fn simulate_batch(&mut self, position: &mut SearchPosition, batch_size: usize) {
+        // 経路と、終局していれば手番側から見た価値
+        let mut leaves = Vec::with_capacity(batch_size);
+        // 評価する局面と、その葉の番号
+        let mut pending = Vec::new();
+        let mut pending_leaves = Vec::new();
+        for _ in 0..batch_size {
+            let root_ply = position.ply();
+            let mut path = vec![0];
+            let mut current = 0;
+            while self.nodes[current].expanded && !self.nodes[current].children.is_empty() {
+                current = self.select_child(current);
+                position.push(&self.nodes[current].move_.unwrap());
+                path.push(current);
+            }
+
+            let terminal = if position.is_game_over() {
+                Some(game_value(position.board(), position.color()))
+            } else {
+                pending.push((*position.board().squares(), position.color()));
+                pending_leaves.push((leaves.len(), position.board().clone()));
+                None
+            };
+            // 仮想的な負け: 評価が終わるまで、経路上の手を指した側が負けたとみなす
+            for &node in path.iter() {
+                let node = &mut self.nodes[node];
+                node.visits += 1;
+                node.value_sum -= 1.0;
+            }
+            leaves.push((path, terminal));
+            while position.ply() > root_ply {
+                position.pop();
+            }
+        }
+
+        let mut values = leaves
+            .iter()
+            .map(|(_, terminal)| terminal.unwrap_or(0.0))
+            .collect::<Vec<_>>();
+        if !pending.is_empty() {
+            let results = self.evaluator.evaluate_batch(&pending);
+            for (((leaf, board), (_, color)), result) in
+                pending_leaves.iter().zip(pending.iter()).zip(results)
+            {
+                let node = *leaves[*leaf].0.last().unwrap();
+                // 同じ葉を複数回選んだ場合は最初の1回だけ展開する
+                if !self.nodes[node].expanded {
+                    self.expand(node, board, *color, &result);
+                }
+                values[*leaf] = self.scale_value(result.value);
+            }
+        }
+
+        // 各ノードにはそのノードに至る手を指した側から見た価値を加え、仮想的な負けを取り消す
+        for ((path, _), value) in leaves.iter().zip(values) {
+            let mut value = -value;
+            for &node in path.iter().rev() {
+                self.nodes[node].value_sum += value + 1.0;
+                value = -value;
+            }
+        }
+    }
+
+    /// PUCTの値が最大の子
+    fn select_child(&self, parent: usize) -> usize {
+        let node = &self.nodes[parent];
+        // 初めて子を選ぶときも事前確率の大きい手を選ぶよう、訪問回数は最低1とする
+        let sqrt_visits = (node.visits.max(1) as f64).sqrt();
+        let use_noise = parent == 0 && !self.root_noise.is_empty();
+        let epsilon = self.config.dirichlet_epsilon;
+        let puct = |(i, &child): (usize, &usize)| {
+            let child = &self.nodes[child];
+            let prior = if use_noise {
+                (1.0 - epsilon) * child.prior + epsilon * self.root_noise[i]
+            } else {
+                child.prior
+            };
+            child.value() + self.config.c_puct * prior * sqrt_visits / (1.0 + child.visits as f64)
+        };
+        node.children
+            .iter()
+            .enumerate()
+            .max_by(|&a, &b| puct(a).total_cmp(&puct(b)))
+            .map(|(_, &child)| child)
+            .unwrap()
+    }
+
+    /// 評価結果の方策を事前確率にして子ノードを作る
+    fn expand(&mut self, node: usize, board: &BitBoard, color: PlayerColor, result: &EvalResult) {
+        let moves = next_moves(board, &color);
+        let priors = priors(&moves, result.policy.as_ref());
+
+        let mut board = board.clone();
+        for (move_, prior) in moves.into_iter().zip(priors) {
+            let undo = board.make_move(&move_).unwrap();
+            let child = PuctNode::new(Some(move_), board.hash(), prior);
+            board.undo_move(&undo);
+            let index = self.nodes.len();
+            self.nodes.push(child);
+            self.nodes[node].children.push(index);
+        }
+        self.nodes[node].expanded = true;
+    }
+
+    /// 評価値を[-1, 1]の価値にする
+    fn scale_value(&self, value: i32) -> f64 {
+        let scale = self.config.value_scale.max(1) as f64;
+        (value as f64 / scale).clamp(-1.0, 1.0)
+    }
+}
+
+/// 方策から合法手の事前確率を求める(負の値は0とみなし、合計が0なら一様)
+fn priors(moves: &[Move], policy: Option<&[i32; BOARD_SIZE * BOARD_SIZE]>) -> Vec<f64> {
+    let weights = moves
+        .iter()
+        .map(|move_| match (move_, policy) {
+            (Move::Position(_, position), Some(policy)) => {
+                policy[position_to_index(position)].max(0) as f64
+            }
+            _ => 0.0,
+        })
+        .collect::<Vec<_>>();
+    let total = weights.iter().sum::<f64>();
+    if total > 0.0 {
+        weights.iter().map(|w| w / total).collect()
+    } else {
+        vec![1.0 / moves.len() as f64; moves.len()]
+    }
+}
+
+/// 終局した盤面の`color`から見た価値(勝ち1、引き分け0、負け-1)
+fn game_value(board: &BitBoard, color: PlayerColor) -> f64 {
+    let diff = board.black_count() as i32 - board.white_count() as i32;
+    let diff = match color {
+        PlayerColor::Black => diff,
+        PlayerColor::White => -diff,
+    };
+    diff.signum() as f64
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::ai::SimpleEvaluator;
+    use crate::{Position, Square, Squares};
+
+    /// 黒から見てc4(Position(3, 2))だけを勧める方策
+    struct PolicyEvaluator {}
+
+    impl Evaluator for PolicyEvaluator {
+        fn evaluate(&mut self, _board: &Squares, _color: &PlayerColor) -> EvalResult {
+            let mut policy = [0; BOARD_SIZE * BOARD_SIZE];
+            policy[position_to_index(&Position(3, 2))] = 1000;
+            EvalResult {
+                value: 0,
+                policy: Some(policy),
+            }
+        }
+    }
+
+    #[test]
+    fn test_priors() {
+        let moves = [
+            Move::new_position(PlayerColor::Black, Position(2, 3)),
+            Move::new_position(PlayerColor::Black, Position(3, 2)),
+        ];
+        let mut policy = [0; BOARD_SIZE * BOARD_SIZE];
+        policy[position_to_index(&Position(2, 3))] = 1;
+        policy[position_to_index(&Position(3, 2))] = 3;
+        assert_eq!(priors(&moves, Some(&policy)), vec![0.25, 0.75]);
+        assert_eq!(priors(&moves, None), vec![0.5, 0.5]);
+        assert_eq!(
+            priors(&[Move::new_pass(PlayerColor::Black)], Some(&policy)),
+            vec![1.0]
+        );
+    }
+
+    #[test]
+    fn test_search() {
+        let board = BitBoard::new_initial();
+        let mut puct = Puct::new(SimpleEvaluator::new(), PuctConfig::default(), 1);
+        let result = puct.search(&board, PlayerColor::Black, &MctsLimits::iterations(200));
+        assert_eq!(result.simulations, 200);
+        assert_eq!(result.moves.len(), 4);
+        // ルートの展開は探索前に行うので、シミュレーションは全て子に配られる
+        assert_eq!(result.moves.iter().map(|m| m.visits).sum::<u64>(), 200);
+        assert!(board.apply_move(&result.best_move.unwrap()).is_some());
+        assert!((-1.0..=1.0).contains(&result.value));
+
+        let policy = result.visit_policy();
+        assert!((policy.iter().sum::<f32>() - 1.0).abs() < 1e-6);
+    }
+
+    #[test]
+    fn test_policy_prior() {
+        let board = BitBoard::new_initial();
+        let mut puct = Puct::new(PolicyEvaluator {}, PuctConfig::default(), 1);
+        let result = puct.search(&board, PlayerColor::Black, &MctsLimits::iterations(50));
+        let expected = Move::new_position(PlayerColor::Black, Position(3, 2));
+        assert_eq!(result.best_move, Some(expected));
+        assert_eq!(result.moves[0].prior, 1.0);
+    }
+
+    #[test]
+    fn test_finds_winning_move() {
+        // 黒がh1に置けば白石が全て黒になって勝つ
+        let mut squares: Squares = [Square::Empty; BOARD_SIZE * BOARD_SIZE];
+        squares[0] = Square::Black;
+        for square in squares.iter_mut().take(7).skip(1) {
+            *square = Square::White;
+        }
+        squares[9] = Square::White;
+        squares[18] = Square::Black;
+        let board = BitBoard::new(&squares, 0);
+        let mut puct = Puct::new(SimpleEvaluator::new(), PuctConfig::default(), 1);
+        let result = puct.search(&board, PlayerColor::Black, &MctsLimits::iterations(500));
+        assert_eq!(
+            result.best_move,
+            Some(Move::new_position(PlayerColor::Black, Position(0, 7)))
+        );
+    }
+
+    #[test]
+    fn test_reuse_tree() {
+        let board = BitBoard::new_initial();
+        let mut puct = Puct::new(SimpleEvaluator::new(), PuctConfig::default(), 1);
+        let result = puct.search(&board, PlayerColor::Black, &MctsLimits::iterations(300));
+        let move_ = result.best_move.unwrap();
+        let visits = result.moves[0].visits;
+
+        // 自分の手で進めた木を使って相手の手番を探索する
+        puct.advance(&move_);
+        let next = board.apply_move(&move_).unwrap();
+        let size = puct.tree_size();
+        assert!(size > 1);
+        let result = puct.search(&next, PlayerColor::White, &MctsLimits::iterations(0));
+        assert_eq!(result.nodes, size);
+        assert_eq!(
+            result.moves.iter().map(|m| m.visits).sum::<u64>() + 1,
+            visits
+        );
+
+        // 孫の局面も引き継ぐ
+        let reply = result.best_move.unwrap();
+        let after = next.apply_move(&reply).unwrap();
+        let mut puct2 = Puct::new(SimpleEvaluator::new(), PuctConfig::default(), 1);
+        puct2.search(&board, PlayerColor::Black, &MctsLimits::iterations(300));
+        let result = puct2.search(&after, PlayerColor::Black, &MctsLimits::iterations(0));
+        assert!(result.nodes > 1);
+
+        // 関係ない局面では作り直す
+        let result = puct2.search(&board, PlayerColor::Black, &MctsLimits::iterations(0));
+        assert_eq!(result.nodes, 5);
+    }
+
+    #[test]
+    fn test_noise_and_temperature() {
+        let board = BitBoard::new_initial();
+        let mut puct = Puct::new(SimpleEvaluator::new(), PuctConfig::self_play(), 1);
+        let result = puct.search(&board, PlayerColor::Black, &MctsLimits::iterations(100));
+        assert!(result.moves.iter().all(|m| m.visits > 0));
+
+        // 温度を使う手数では訪問回数の少ない手も選ばれる
+        let selected = (0..100)
+            .map(|_| puct.select_move(&result, 0).unwrap())
+            .collect::<Vec<_>>();
+        assert!(selected.iter().any(|m| Some(*m) != result.best_move));
+        assert_eq!(puct.select_move(&result, 20), result.best_move);
+    }
+
+    /// まとめて評価した局面数を記録する
+    #[derive(Default)]
+    struct BatchEvaluator {
+        batch_sizes: Vec<usize>,
+    }
+
+    impl Evaluator for BatchEvaluator {
+        fn evaluate(&mut self, board: &Squares, color: &PlayerColor) -> EvalResult {
+            SimpleEvaluator::new().evaluate(board, color)
+        }
+
+        fn evaluate_batch(&mut self, positions: &[(Squares, PlayerColor)]) -> Vec<EvalResult> {
+            self.batch_sizes.push(positions.len());
+            positions
+                .iter()
+                .map(|(board, color)| self.evaluate(board, color))
+                .collect()
+        }
+    }
+
+    #[test]
+    fn test_batch() {
+        let board = BitBoard::new_initial();
+        let config = PuctConfig {
+            batch_size: 8,
+            ..Default::default()
+        };
+        let mut puct = Puct::new(BatchEvaluator::default(), config, 1);
+        let result = puct.search(&board, PlayerColor::Black, &MctsLimits::iterations(100));
+        assert_eq!(result.simulations, 100);
+        assert_eq!(result.moves.iter().map(|m| m.visits).sum::<u64>(), 100);
+        // 仮想的な負けは全て取り消されている
+        assert!((-1.0..=1.0).contains(&result.value));
+        assert!(result.moves.iter().all(|m| (-1.0..=1.0).contains(&m.value)));
+
+        let batch_sizes = &puct.evaluator().batch_sizes;
+        assert!(batch_sizes.iter().all(|&n| n <= 8));
+        assert!(batch_sizes.iter().any(|&n| n > 1));
+    }
+}
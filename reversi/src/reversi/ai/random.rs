@@ -0,0 +1,95 @@
+/// 探索用の乱数生成(xorshift64*)
+///
+/// 種が同じなら同じ列を返すので、探索を再現できる。
+pub(crate) struct XorShift(u64);
+
+impl XorShift {
+    pub(crate) fn new(seed: u64) -> Self {
+        // 状態が0だと0しか出ない
+        XorShift(seed ^ 0x9e37_79b9_7f4a_7c15)
+    }
+
+    pub(crate) fn next(&mut self) -> u64 {
+        self.0 ^= self.0 >> 12;
+        self.0 ^= self.0 << 25;
+        self.0 ^= self.0 >> 27;
+        self.0.wrapping_mul(0x2545_f491_4f6c_dd1d)
+    }
+
+    /// 0以上`n`未満
+    pub(crate) fn below(&mut self, n: u64) -> u64 {
+        self.next() % n
+    }
+
+    /// 0より大きく1以下の一様乱数
+    pub(crate) fn uniform(&mut self) -> f64 {
+        ((self.next() >> 11) + 1) as f64 / (1u64 << 53) as f64
+    }
+
+    /// 標準正規分布(Box-Muller法)
+    pub(crate) fn normal(&mut self) -> f64 {
+        let r = (-2.0 * self.uniform().ln()).sqrt();
+        r * (2.0 * std::f64::consts::PI * self.uniform()).cos()
+    }
+
+    /// 形状`shape`、尺度1のガンマ分布(Marsaglia-Tsang法)
+    pub(crate) fn gamma(&mut self, shape: f64) -> f64 {
+        if shape < 1.0 {
+            // Gamma(a) = Gamma(a + 1) * U^(1/a)
+            return self.gamma(shape + 1.0) * self.uniform().powf(1.0 / shape);
+        }
+        let d = shape - 1.0 / 3.0;
+        let c = 1.0 / (9.0 * d).sqrt();
+        loop {
+            let x = self.normal();
+            let v = (1.0 + c * x).powi(3);
+            if v <= 0.0 {
+                continue;
+            }
+            if self.uniform().ln() < 0.5 * x * x + d - d * v + d * v.ln() {
+                return d * v;
+            }
+        }
+    }
+
+    /// 全ての要素が`alpha`のディリクレ分布から`n`個の値を生成する
+    pub(crate) fn dirichlet(&mut self, alpha: f64, n: usize) -> Vec<f64> {
+        let samples = (0..n).map(|_| self.gamma(alpha)).collect::<Vec<_>>();
+        let sum = samples.iter().sum::<f64>();
+        samples.iter().map(|x| x / sum).collect()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_uniform() {
+        let mut rng = XorShift::new(1);
+        for _ in 0..10000 {
+            let x = rng.uniform();
+            assert!(x > 0.0 && x <= 1.0);
+        }
+    }
+
+    #[test]
+    fn test_gamma_mean() {
+        // ガンマ分布の平均は形状パラメータに等しい
+        let mut rng = XorShift::new(2);
+        for shape in [0.3, 1.0, 2.5] {
+            let n = 20000;
+            let mean = (0..n).map(|_| rng.gamma(shape)).sum::<f64>() / n as f64;
+            assert!((mean - shape).abs() < shape * 0.05, "shape {}", shape);
+        }
+    }
+
+    #[test]
+    fn test_dirichlet() {
+        let mut rng = XorShift::new(3);
+        let noise = rng.dirichlet(0.5, 8);
+        assert_eq!(noise.len(), 8);
+        assert!(noise.iter().all(|&x| x >= 0.0));
+        assert!((noise.iter().sum::<f64>() - 1.0).abs() < 1e-9);
+    }
+}
@@ -0,0 +1,113 @@
+use crate::{
+    board::{BitBoard, Board, MoveUndo},
+    Move, PlayerColor,
+};
+
+use super::node::next_moves;
+
+/// 探索中の局面
+///
+/// 盤面を1つだけ持ち、ルートからの着手をスタックで管理する。
+/// ノードの木を作らないので、メモリ使用量は探索深さに比例する分しか増えない。
+#[derive(Clone, Debug)]
+pub struct SearchPosition {
+    board: BitBoard,
+    color: PlayerColor,
+    stack: Vec<MoveUndo>,
+}
+
+impl SearchPosition {
+    pub fn new(board: BitBoard, color: PlayerColor) -> Self {
+        SearchPosition {
+            board,
+            color,
+            stack: Vec::new(),
+        }
+    }
+
+    pub fn board(&self) -> &BitBoard {
+        &self.board
+    }
+
+    /// 手番
+    pub fn color(&self) -> PlayerColor {
+        self.color
+    }
+
+    /// ルートからの手数
+    pub fn ply(&self) -> usize {
+        self.stack.len()
+    }
+
+    /// ルートからの着手
+    pub fn moves(&self) -> impl Iterator<Item = &Move> {
+        self.stack.iter().map(|undo| &undo.move_)
+    }
+
+    /// 手番側が指せる手の一覧(置ける場所がなければパスのみ)
+    pub fn next_moves(&self) -> Vec<Move> {
+        next_moves(&self.board, &self.color)
+    }
+
+    pub fn is_game_over(&self) -> bool {
+        self.board.is_game_over()
+    }
+
+    /// 着手して手番を交代する
+    ///
+    /// 無効な手の場合は何もせずに`false`を返す。
+    pub fn push(&mut self, move_: &Move) -> bool {
+        match self.board.make_move(move_) {
+            Some(undo) => {
+                self.stack.push(undo);
+                self.color = self.color.opponent();
+                true
+            }
+            None => false,
+        }
+    }
+
+    /// 最後の着手を取り消す
+    pub fn pop(&mut self) -> Option<Move> {
+        let undo = self.stack.pop()?;
+        self.board.undo_move(&undo);
+        self.color = self.color.opponent();
+        Some(undo.move_)
+    }
+
+    pub fn into_board(self) -> BitBoard {
+        self.board
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::Position;
+
+    #[test]
+    fn test_push_pop() {
+        let initial = BitBoard::new_initial();
+        let mut position = SearchPosition::new(initial.clone(), PlayerColor::Black);
+
+        let m1 = Move::new_position(PlayerColor::Black, Position(2, 3));
+        let m2 = Move::new_position(PlayerColor::White, Position(2, 2));
+        assert!(position.push(&m1));
+        assert!(position.push(&m2));
+        // 置けない場所
+        assert!(!position.push(&Move::new_position(PlayerColor::Black, Position(0, 0))));
+
+        assert_eq!(position.ply(), 2);
+        assert_eq!(position.color(), PlayerColor::Black);
+        assert_eq!(position.moves().copied().collect::<Vec<_>>(), vec![m1, m2]);
+        let expected = initial.apply_move(&m1).unwrap().apply_move(&m2).unwrap();
+        assert!(position.board().squares() == expected.squares());
+
+        assert_eq!(position.pop(), Some(m2));
+        assert_eq!(position.pop(), Some(m1));
+        assert_eq!(position.pop(), None);
+        assert_eq!(position.color(), PlayerColor::Black);
+        assert!(position.board().squares() == initial.squares());
+        assert_eq!(position.board().hash(), initial.hash());
+    }
+}
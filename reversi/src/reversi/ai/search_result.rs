@@ -0,0 +1,73 @@
+use std::time::Duration;
+
+use crate::Move;
+
+/// 探索の結果
+#[derive(Clone, PartialEq, Eq, Debug)]
+pub struct SearchResult {
+    /// 最善手(探索できなかった場合は`None`)
+    pub best_move: Option<Move>,
+    /// 手番側から見た評価値
+    pub score: i32,
+    /// 読み筋(最善手から始まる)
+    pub pv: Vec<Move>,
+    /// 探索した深さ
+    pub depth: usize,
+    /// 探索したノード数
+    pub nodes: u64,
+    pub elapsed: Duration,
+}
+
+impl SearchResult {
+    /// 1秒あたりの探索ノード数
+    pub fn nps(&self) -> u64 {
+        let secs = self.elapsed.as_secs_f64();
+        if secs > 0.0 {
+            (self.nodes as f64 / secs) as u64
+        } else {
+            0
+        }
+    }
+}
+
+/// `move_`と子ノードの読み筋をつなげて読み筋を更新する
+pub(crate) fn update_pv(pv: &mut Vec<Move>, move_: Move, child_pv: &[Move]) {
+    pv.clear();
+    pv.push(move_);
+    pv.extend_from_slice(child_pv);
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{PlayerColor, Position};
+
+    #[test]
+    fn test_update_pv() {
+        let a = Move::new_position(PlayerColor::Black, Position(2, 3));
+        let b = Move::new_position(PlayerColor::White, Position(2, 2));
+        let c = Move::new_pass(PlayerColor::Black);
+        let mut pv = vec![c];
+        update_pv(&mut pv, a, &[b, c]);
+        assert_eq!(pv, vec![a, b, c]);
+    }
+
+    #[test]
+    fn test_nps() {
+        let result = SearchResult {
+            best_move: None,
+            score: 0,
+            pv: Vec::new(),
+            depth: 1,
+            nodes: 3000,
+            elapsed: Duration::from_millis(1500),
+        };
+        assert_eq!(result.nps(), 2000);
+
+        let result = SearchResult {
+            elapsed: Duration::ZERO,
+            ..result
+        };
+        assert_eq!(result.nps(), 0);
+    }
+}
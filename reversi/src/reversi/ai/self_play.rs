@@ -1,12 +1,801 @@
-use crate::{board::BitBoard, game::play_game, player::AiPlayer};
+use std::collections::HashSet;
+use std::convert::TryInto;
+use std::io::{Error, ErrorKind};
+use std::path::{Path, PathBuf};
 
-pub struct SelfPlayParameter {
-    pub search_depth: usize,
+use crate::{
+    board::{BitBoard, Board, Symmetry},
+    game::GameState,
+    index_to_position,
+    player::Player,
+    position_to_index, Move, PlayerColor, Square, Squares, BOARD_SIZE,
+};
+
+use super::alpha_zero::{board_planes, PLANE_COUNT};
+use super::evaluator::Evaluator;
+use super::mcts::MctsLimits;
+use super::puct::{Puct, PuctConfig};
+use super::random::XorShift;
+
+const SQUARE_COUNT: usize = BOARD_SIZE * BOARD_SIZE;
+
+/// 自己対局データのファイルの先頭の識別子
+const SELF_PLAY_FILE_MAGIC: &[u8; 4] = b"RVSP";
+
+/// 自己対局データのファイルの形式のバージョン
+pub const SELF_PLAY_FILE_VERSION: u32 = 1;
+
+/// 識別子、バージョン、局面数
+const SELF_PLAY_FILE_HEADER_SIZE: usize = 12;
+
+/// 1局面あたりのバイト数(面、手番、方策、結果)
+const SAMPLE_SIZE: usize = SQUARE_COUNT * PLANE_COUNT + 1 + SQUARE_COUNT * 4 + 4;
+
+/// 自己対局データのファイル名(`chunk_000000.bin`のように連番を付ける)
+const CHUNK_PREFIX: &str = "chunk_";
+const CHUNK_EXTENSION: &str = "bin";
+/// ファイルに入れた対局の番号の一覧
+const GAME_LIST_EXTENSION: &str = "games";
+/// 書き込み中の局面のファイル
+const TEMPORARY_EXTENSION: &str = "tmp";
+
+/// 自己対局の教師データの1局面
+#[derive(Clone, PartialEq, Debug)]
+pub struct SelfPlaySample {
+    pub board: Squares,
+    /// 手番
+    pub color: PlayerColor,
+    /// 探索による方策(マスごとの確率、[`position_to_index`]の順)
+    pub policy: [f32; SQUARE_COUNT],
+    /// `color`から見た対局結果(勝ち1、引き分け0、負け-1)
+    pub outcome: f32,
+}
+
+impl SelfPlaySample {
+    /// ネットワークの入力の面([`board_planes`])
+    pub fn planes(&self) -> Vec<f32> {
+        board_planes(&self.board, &self.color)
+    }
+
+    /// 盤面と方策を対称変換する
+    pub fn transform(&self, symmetry: Symmetry) -> SelfPlaySample {
+        let mut policy = [0.0; SQUARE_COUNT];
+        for (i, p) in self.policy.iter().enumerate() {
+            let position = symmetry.transform_position(&index_to_position(i));
+            policy[position_to_index(&position)] = *p;
+        }
+        SelfPlaySample {
+            board: symmetry.transform_squares(&self.board),
+            color: self.color,
+            policy,
+            outcome: self.outcome,
+        }
+    }
+
+    /// 8通りの対称変換をした局面([`Symmetry::ALL`]の順で、先頭は元の局面)
+    pub fn augment(&self) -> Vec<SelfPlaySample> {
+        Symmetry::ALL
+            .iter()
+            .map(|symmetry| self.transform(*symmetry))
+            .collect()
+    }
+}
+
+/// 自己対局データのファイル1つ分の局面
+///
+/// ファイルは識別子、バージョン、局面数(u32)の後に、全局面の面(u8、`[局面][行][列][面]`)、
+/// 手番(u8、黒0、白1)、方策(f32、`[局面][マス]`)、結果(f32)を順に並べる。
+/// 数値はリトルエンディアン。`python/alpha_zero_network.py`の`load_self_play`で読み込める。
+#[derive(Clone, PartialEq, Debug, Default)]
+pub struct SelfPlayChunk {
+    pub samples: Vec<SelfPlaySample>,
+}
+
+impl SelfPlayChunk {
+    pub fn new(samples: Vec<SelfPlaySample>) -> Self {
+        SelfPlayChunk { samples }
+    }
+
+    pub fn to_bytes(&self) -> Vec<u8> {
+        let mut bytes =
+            Vec::with_capacity(SELF_PLAY_FILE_HEADER_SIZE + self.samples.len() * SAMPLE_SIZE);
+        bytes.extend_from_slice(SELF_PLAY_FILE_MAGIC);
+        bytes.extend_from_slice(&SELF_PLAY_FILE_VERSION.to_le_bytes());
+        bytes.extend_from_slice(&(self.samples.len() as u32).to_le_bytes());
+        for sample in &self.samples {
+            bytes.extend(sample.planes().iter().map(|p| *p as u8));
+        }
+        for sample in &self.samples {
+            bytes.push(match sample.color {
+                PlayerColor::Black => 0,
+                PlayerColor::White => 1,
+            });
+        }
+        for sample in &self.samples {
+            for p in &sample.policy {
+                bytes.extend_from_slice(&p.to_le_bytes());
+            }
+        }
+        for sample in &self.samples {
+            bytes.extend_from_slice(&sample.outcome.to_le_bytes());
+        }
+        bytes
+    }
+
+    /// [`SelfPlayChunk::to_bytes`]の形式から読み込む
+    ///
+    /// 盤面は手番側と相手の石の面から復元する。
+    pub fn from_bytes(bytes: &[u8]) -> std::io::Result<Self> {
+        let invalid = |message: String| Error::new(ErrorKind::InvalidData, message);
+        if bytes.len() < SELF_PLAY_FILE_HEADER_SIZE || &bytes[0..4] != SELF_PLAY_FILE_MAGIC {
+            return Err(invalid("not a self-play data file".to_string()));
+        }
+        let header = |i: usize| u32::from_le_bytes(bytes[i..i + 4].try_into().unwrap());
+        let version = header(4);
+        if version != SELF_PLAY_FILE_VERSION {
+            return Err(invalid(format!(
+                "unsupported self-play data file version: {}",
+                version
+            )));
+        }
+        let count = header(8) as usize;
+        let data = &bytes[SELF_PLAY_FILE_HEADER_SIZE..];
+        if data.len() != count * SAMPLE_SIZE {
+            return Err(invalid(format!(
+                "self-play data file size mismatch: {} bytes for {} positions",
+                data.len(),
+                count
+            )));
+        }
+
+        let (planes, data) = data.split_at(count * SQUARE_COUNT * PLANE_COUNT);
+        let (colors, data) = data.split_at(count);
+        let (policies, outcomes) = data.split_at(count * SQUARE_COUNT * 4);
+        let floats = |bytes: &[u8]| {
+            bytes
+                .chunks_exact(4)
+                .map(|chunk| f32::from_le_bytes(chunk.try_into().unwrap()))
+                .collect::<Vec<_>>()
+        };
+        let policies = floats(policies);
+        let outcomes = floats(outcomes);
+
+        let mut samples = Vec::with_capacity(count);
+        for i in 0..count {
+            let color = match colors[i] {
+                0 => PlayerColor::Black,
+                1 => PlayerColor::White,
+                c => return Err(invalid(format!("invalid side to move: {}", c))),
+            };
+            let (own, opponent) = match color {
+                PlayerColor::Black => (Square::Black, Square::White),
+                PlayerColor::White => (Square::White, Square::Black),
+            };
+            let mut board = [Square::Empty; SQUARE_COUNT];
+            let sample_planes = &planes[i * SQUARE_COUNT * PLANE_COUNT..];
+            for (s, plane) in board
+                .iter_mut()
+                .zip(sample_planes.chunks_exact(PLANE_COUNT))
+            {
+                match (plane[0], plane[1]) {
+                    (0, 0) => {}
+                    (1, 0) => *s = own,
+                    (0, 1) => *s = opponent,
+                    _ => return Err(invalid("invalid board planes".to_string())),
+                }
+            }
+            let mut policy = [0.0; SQUARE_COUNT];
+            policy.copy_from_slice(&policies[i * SQUARE_COUNT..(i + 1) * SQUARE_COUNT]);
+            samples.push(SelfPlaySample {
+                board,
+                color,
+                policy,
+                outcome: outcomes[i],
+            });
+        }
+        Ok(SelfPlayChunk { samples })
+    }
+
+    pub fn save<P: AsRef<Path>>(&self, path: P) -> std::io::Result<()> {
+        std::fs::write(path, self.to_bytes())
+    }
+
+    pub fn load<P: AsRef<Path>>(path: P) -> std::io::Result<Self> {
+        Self::from_bytes(&std::fs::read(path)?)
+    }
+}
+
+/// 自己対局で手を決めるエンジン
+pub trait SelfPlayEngine {
+    /// 対局の始めに呼ぶ(`seed`は対局ごとに決まる乱数の種)
+    fn new_game(&mut self, seed: u64);
+
+    /// 手番側の手と、学習に使う方策を返す
+    ///
+    /// 着手可能な位置がある局面でだけ呼ぶ。
+    fn think(&mut self, board: &BitBoard, color: PlayerColor) -> (Move, [f32; SQUARE_COUNT]);
+}
+
+/// PUCT探索のエンジン
+///
+/// 方策はルートの訪問回数の割合で、手は[`Puct::select_move`]で選ぶ。
+/// ノイズや温度は[`PuctConfig::self_play`]などで設定する。
+pub struct PuctEngine<E>
+where
+    E: Evaluator,
+{
+    puct: Puct<E>,
+    limits: MctsLimits,
+}
+
+impl<E> PuctEngine<E>
+where
+    E: Evaluator,
+{
+    pub fn new(evaluator: E, limits: MctsLimits, config: PuctConfig) -> Self {
+        PuctEngine {
+            puct: Puct::new(evaluator, config, 0),
+            limits,
+        }
+    }
+
+    pub fn puct(&self) -> &Puct<E> {
+        &self.puct
+    }
+
+    pub fn limits(&self) -> &MctsLimits {
+        &self.limits
+    }
+}
+
+impl<E> SelfPlayEngine for PuctEngine<E>
+where
+    E: Evaluator,
+{
+    fn new_game(&mut self, seed: u64) {
+        self.puct.reset(seed);
+    }
+
+    fn think(&mut self, board: &BitBoard, color: PlayerColor) -> (Move, [f32; SQUARE_COUNT]) {
+        let result = self.puct.search(board, color, &self.limits);
+        let move_ = self
+            .puct
+            .select_move(&result, board.depth())
+            .expect("no legal move");
+        self.puct.advance(&move_);
+
+        let policy = result.visit_policy();
+        if policy.iter().any(|p| *p > 0.0) {
+            (move_, policy)
+        } else {
+            // シミュレーションしていなければ指した手だけにする
+            (move_, one_hot_policy(&move_))
+        }
+    }
+}
+
+/// [`Player`]をそのまま使うエンジン
+///
+/// 方策は指した手だけが1になる。乱数の種は使わない。
+pub struct PlayerEngine<P>
+where
+    P: Player,
+{
+    player: P,
+}
+
+impl<P> PlayerEngine<P>
+where
+    P: Player,
+{
+    pub fn new(player: P) -> Self {
+        PlayerEngine { player }
+    }
+
+    pub fn player(&self) -> &P {
+        &self.player
+    }
+}
+
+impl<P> SelfPlayEngine for PlayerEngine<P>
+where
+    P: Player,
+{
+    fn new_game(&mut self, _seed: u64) {}
+
+    fn think(&mut self, board: &BitBoard, _color: PlayerColor) -> (Move, [f32; SQUARE_COUNT]) {
+        let move_ = self.player.take_action(&GameState::new(board));
+        (move_, one_hot_policy(&move_))
+    }
+}
+
+fn one_hot_policy(move_: &Move) -> [f32; SQUARE_COUNT] {
+    let mut policy = [0.0; SQUARE_COUNT];
+    if let Move::Position(_, position) = move_ {
+        policy[position_to_index(position)] = 1.0;
+    }
+    policy
+}
+
+/// 自己対局の設定
+#[derive(Clone, Copy, PartialEq, Debug)]
+pub struct SelfPlayConfig {
+    /// 対局数
+    pub games: u64,
+    /// 乱数の種(対局ごとの種はこれと対局の番号から決まる)
+    pub seed: u64,
+    /// 初手からこの手数は着手可能な手から一様に選ぶ(教師データには含めない)
+    pub random_moves: u32,
+    /// 8通りの対称変換で局面を増やして書き出す
+    pub augment: bool,
+    /// 1ファイルに書き出す局面数
+    pub chunk_size: usize,
+}
+
+impl Default for SelfPlayConfig {
+    fn default() -> Self {
+        SelfPlayConfig {
+            games: 100,
+            seed: 0,
+            random_moves: 0,
+            augment: false,
+            chunk_size: 8192,
+        }
+    }
+}
+
+/// 自己対局1局分の結果
+#[derive(Clone, PartialEq, Debug)]
+pub struct SelfPlayGame {
+    /// 対局の番号
+    pub index: u64,
+    /// エンジンが手を選んだ局面(ランダムな手とパスの局面は含まない)
+    pub samples: Vec<SelfPlaySample>,
+    /// パスを含む全ての手
+    pub game_record: Vec<Move>,
+    pub black_count: u32,
+    pub white_count: u32,
+}
+
+impl SelfPlayGame {
+    /// 勝った側(引き分けなら`None`)
+    pub fn winner(&self) -> Option<PlayerColor> {
+        match self.black_count.cmp(&self.white_count) {
+            std::cmp::Ordering::Greater => Some(PlayerColor::Black),
+            std::cmp::Ordering::Less => Some(PlayerColor::White),
+            std::cmp::Ordering::Equal => None,
+        }
+    }
+}
+
+/// 対局ごとの乱数の種(splitmix64)
+fn game_seed(seed: u64, index: u64) -> u64 {
+    let mut z = seed.wrapping_add(index.wrapping_add(1).wrapping_mul(0x9e37_79b9_7f4a_7c15));
+    z = (z ^ (z >> 30)).wrapping_mul(0xbf58_476d_1ce4_e5b9);
+    z = (z ^ (z >> 27)).wrapping_mul(0x94d0_49bb_1331_11eb);
+    z ^ (z >> 31)
+}
+
+/// `index`番目の対局を初期局面から終局まで指す
+///
+/// 結果は設定の種と`index`だけで決まるので、同じ番号の対局は何度指しても同じになる
+/// (エンジンが乱数の種以外の状態に左右されない場合)。
+pub fn play_self_play_game<E>(engine: &mut E, config: &SelfPlayConfig, index: u64) -> SelfPlayGame
+where
+    E: SelfPlayEngine,
+{
+    let mut rng = XorShift::new(game_seed(config.seed, index));
+    engine.new_game(rng.next());
+
+    let mut board = BitBoard::new_initial();
+    let mut samples = Vec::new();
+    let mut game_record = Vec::new();
+    while !board.is_game_over() {
+        let color = board.turn();
+        let positions = board.get_movable_positions(&color);
+        let move_ = if positions.is_empty() {
+            Move::new_pass(color)
+        } else if board.depth() < config.random_moves {
+            let position = positions[rng.below(positions.len() as u64) as usize];
+            Move::new_position(color, position)
+        } else {
+            let (move_, policy) = engine.think(&board, color);
+            samples.push(SelfPlaySample {
+                board: *board.squares(),
+                color,
+                policy,
+                outcome: 0.0,
+            });
+            move_
+        };
+        board = board.apply_move(&move_).expect("illegal move");
+        game_record.push(move_);
+    }
+
+    let black_count = board.black_count();
+    let white_count = board.white_count();
+    let black_outcome = match black_count.cmp(&white_count) {
+        std::cmp::Ordering::Greater => 1.0,
+        std::cmp::Ordering::Less => -1.0,
+        std::cmp::Ordering::Equal => 0.0,
+    };
+    for sample in samples.iter_mut() {
+        sample.outcome = match sample.color {
+            PlayerColor::Black => black_outcome,
+            PlayerColor::White => -black_outcome,
+        };
+    }
+    SelfPlayGame {
+        index,
+        samples,
+        game_record,
+        black_count,
+        white_count,
+    }
+}
+
+/// 自己対局の局面を一定数ごとにファイルに書き出す
+///
+/// ファイルは`directory`に`chunk_000000.bin`から順に作る。既にあるファイルの続きの番号から書く。
+/// 対局の途中ではファイルを分けず、ファイルに入れた対局の番号を`chunk_000000.games`に1行ずつ書く。
+/// 番号のファイルを先に書き、局面のファイルは一時ファイルから名前を変えて作るので、
+/// 途中で中断しても局面のファイルがある対局だけが書き出し済みになる。
+pub struct SelfPlayWriter {
+    directory: PathBuf,
+    chunk_size: usize,
+    augment: bool,
+    pending: Vec<SelfPlaySample>,
+    pending_games: Vec<u64>,
+    /// 書き出し済みの対局(前の実行の分を含む)
+    completed: HashSet<u64>,
+    next_chunk: usize,
+    sample_count: usize,
+}
+
+impl SelfPlayWriter {
+    /// `directory`の既存のファイルから書き出し済みの対局を読み込んで作成
+    pub fn new<P: AsRef<Path>>(
+        directory: P,
+        chunk_size: usize,
+        augment: bool,
+    ) -> std::io::Result<Self> {
+        let directory = directory.as_ref().to_path_buf();
+        std::fs::create_dir_all(&directory)?;
+        let paths = chunk_paths(&directory)?;
+        let mut completed = HashSet::new();
+        for path in &paths {
+            completed.extend(read_game_list(&path.with_extension(GAME_LIST_EXTENSION))?);
+        }
+        Ok(SelfPlayWriter {
+            directory,
+            chunk_size: chunk_size.max(1),
+            augment,
+            pending: Vec::new(),
+            pending_games: Vec::new(),
+            completed,
+            next_chunk: paths.len(),
+            sample_count: 0,
+        })
+    }
+
+    /// この書き出しでファイルに書き出した局面数
+    pub fn sample_count(&self) -> usize {
+        self.sample_count
+    }
+
+    /// `index`番目の対局がファイルに書き出し済みか
+    pub fn is_completed(&self, index: u64) -> bool {
+        self.completed.contains(&index)
+    }
+
+    /// ファイルに書き出し済みの対局数(前の実行の分を含む)
+    pub fn completed_games(&self) -> usize {
+        self.completed.len()
+    }
+
+    /// 対局の局面を追加し、一定数たまったらファイルに書き出す
+    ///
+    /// 書き出し済みか書き出し待ちの対局は無視する。
+    pub fn add_game(&mut self, game: &SelfPlayGame) -> std::io::Result<()> {
+        if self.is_completed(game.index) || self.pending_games.contains(&game.index) {
+            return Ok(());
+        }
+        for sample in &game.samples {
+            if self.augment {
+                self.pending.extend(sample.augment());
+            } else {
+                self.pending.push(sample.clone());
+            }
+        }
+        self.pending_games.push(game.index);
+        if self.pending.len() >= self.chunk_size {
+            self.flush()?;
+        }
+        Ok(())
+    }
+
+    /// 書き出し待ちの対局を(一定数に満たなくても)ファイルに書き出す
+    pub fn flush(&mut self) -> std::io::Result<()> {
+        if self.pending_games.is_empty() {
+            return Ok(());
+        }
+        let path = self.directory.join(format!(
+            "{}{:06}.{}",
+            CHUNK_PREFIX, self.next_chunk, CHUNK_EXTENSION
+        ));
+        let game_list = self
+            .pending_games
+            .iter()
+            .map(|index| format!("{}\n", index))
+            .collect::<String>();
+        std::fs::write(path.with_extension(GAME_LIST_EXTENSION), game_list)?;
+        let temporary = path.with_extension(TEMPORARY_EXTENSION);
+        let samples = std::mem::take(&mut self.pending);
+        self.sample_count += samples.len();
+        SelfPlayChunk::new(samples).save(&temporary)?;
+        std::fs::rename(&temporary, &path)?;
+
+        self.completed.extend(self.pending_games.drain(..));
+        self.next_chunk += 1;
+        Ok(())
+    }
 }
 
-pub fn run_self_play(param: &SelfPlayParameter) {
-    let black_player = Box::new(AiPlayer::new(param.search_depth));
-    let white_player = Box::new(AiPlayer::new(param.search_depth));
-    let board = BitBoard::new_initial();
-    let result = play_game(&board, black_player, white_player);
+/// 対局の番号のファイル(1行に1つ)を読む
+fn read_game_list(path: &Path) -> std::io::Result<Vec<u64>> {
+    std::fs::read_to_string(path)?
+        .lines()
+        .map(|line| {
+            line.trim().parse().map_err(|_| {
+                Error::new(
+                    ErrorKind::InvalidData,
+                    format!("invalid game index in {}: {}", path.display(), line),
+                )
+            })
+        })
+        .collect()
+}
+
+/// `directory`の自己対局データのファイル(番号順)
+pub fn chunk_paths<P: AsRef<Path>>(directory: P) -> std::io::Result<Vec<PathBuf>> {
+    let mut paths = Vec::new();
+    for entry in std::fs::read_dir(directory)? {
+        let path = entry?.path();
+        let is_chunk = path
+            .file_name()
+            .and_then(|name| name.to_str())
+            .is_some_and(|name| {
+                name.starts_with(CHUNK_PREFIX)
+                    && path.extension().and_then(|e| e.to_str()) == Some(CHUNK_EXTENSION)
+            });
+        if is_chunk {
+            paths.push(path);
+        }
+    }
+    paths.sort();
+    Ok(paths)
+}
+
+/// 設定の対局数だけ自己対局して、局面を`directory`に書き出す
+///
+/// 書き出し済みの対局は飛ばすので、中断した後に同じ設定で実行すると続きから再開する。
+/// この実行で書き出した局面数を返す。
+pub fn run_self_play<E, P>(
+    engine: &mut E,
+    config: &SelfPlayConfig,
+    directory: P,
+) -> std::io::Result<usize>
+where
+    E: SelfPlayEngine,
+    P: AsRef<Path>,
+{
+    let mut writer = SelfPlayWriter::new(directory, config.chunk_size, config.augment)?;
+    for index in 0..config.games {
+        if writer.is_completed(index) {
+            continue;
+        }
+        let game = play_self_play_game(engine, config, index);
+        writer.add_game(&game)?;
+    }
+    writer.flush()?;
+    Ok(writer.sample_count())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::ai::SimpleEvaluator;
+    use crate::player::AiPlayer;
+
+    fn puct_engine() -> PuctEngine<SimpleEvaluator> {
+        PuctEngine::new(
+            SimpleEvaluator::new(),
+            MctsLimits::iterations(16),
+            PuctConfig::self_play(),
+        )
+    }
+
+    fn test_config() -> SelfPlayConfig {
+        SelfPlayConfig {
+            games: 2,
+            seed: 7,
+            random_moves: 4,
+            augment: false,
+            chunk_size: 50,
+        }
+    }
+
+    fn test_dir(name: &str) -> PathBuf {
+        std::env::temp_dir().join(format!("self_play_test_{}_{}", name, std::process::id()))
+    }
+
+    #[test]
+    fn test_play_self_play_game() {
+        let config = test_config();
+        let mut engine = puct_engine();
+        let game = play_self_play_game(&mut engine, &config, 0);
+        assert_eq!(game.black_count + game.white_count, 64);
+
+        let legal_moves = game
+            .game_record
+            .iter()
+            .filter(|m| matches!(m, Move::Position(..)));
+        assert!(game.samples.len() <= legal_moves.count() - config.random_moves as usize);
+        for sample in &game.samples {
+            let board = BitBoard::new(&sample.board, 0);
+            let movable = board.get_movable_positions(&sample.color);
+            let total = sample.policy.iter().sum::<f32>();
+            assert!((total - 1.0).abs() < 1e-5);
+            for (i, p) in sample.policy.iter().enumerate() {
+                if *p > 0.0 {
+                    assert!(movable.contains(&index_to_position(i)));
+                }
+            }
+            let expected = match (game.winner(), sample.color) {
+                (None, _) => 0.0,
+                (Some(winner), color) if winner == color => 1.0,
+                _ => -1.0,
+            };
+            assert_eq!(sample.outcome, expected);
+        }
+
+        // 同じ番号の対局は同じになり、番号が違えば(ほぼ確実に)変わる
+        assert_eq!(play_self_play_game(&mut puct_engine(), &config, 0), game);
+        assert_ne!(
+            play_self_play_game(&mut engine, &config, 1).game_record,
+            game.game_record
+        );
+    }
+
+    #[test]
+    fn test_player_engine() {
+        let mut engine = PlayerEngine::new(AiPlayer::new(1));
+        let config = SelfPlayConfig {
+            random_moves: 0,
+            ..test_config()
+        };
+        let game = play_self_play_game(&mut engine, &config, 0);
+        for (sample, move_) in game.samples.iter().zip(
+            game.game_record
+                .iter()
+                .filter(|m| matches!(m, Move::Position(..))),
+        ) {
+            assert_eq!(sample.policy, one_hot_policy(move_));
+        }
+    }
+
+    #[test]
+    fn test_augment() {
+        let mut engine = puct_engine();
+        let game = play_self_play_game(&mut engine, &test_config(), 0);
+        let sample = &game.samples[3];
+        let augmented = sample.augment();
+        assert_eq!(augmented.len(), 8);
+        assert_eq!(&augmented[0], sample);
+        for (symmetry, transformed) in Symmetry::ALL.iter().zip(&augmented) {
+            let board = BitBoard::new(&transformed.board, 0);
+            for position in board.get_movable_positions(&transformed.color) {
+                let original = symmetry.inverse().transform_position(&position);
+                assert_eq!(
+                    transformed.policy[position_to_index(&position)],
+                    sample.policy[position_to_index(&original)]
+                );
+            }
+            assert_eq!(transformed.transform(symmetry.inverse()), *sample);
+        }
+    }
+
+    #[test]
+    fn test_chunk_round_trip() {
+        let mut engine = puct_engine();
+        let game = play_self_play_game(&mut engine, &test_config(), 0);
+        let chunk = SelfPlayChunk::new(game.samples[..5].to_vec());
+        let bytes = chunk.to_bytes();
+        assert_eq!(bytes.len(), SELF_PLAY_FILE_HEADER_SIZE + 5 * SAMPLE_SIZE);
+        assert_eq!(SelfPlayChunk::from_bytes(&bytes).unwrap(), chunk);
+
+        // 面はそのまま書き出される
+        let planes = &bytes[SELF_PLAY_FILE_HEADER_SIZE..][..SQUARE_COUNT * PLANE_COUNT];
+        for (b, p) in planes.iter().zip(chunk.samples[0].planes()) {
+            assert_eq!(*b as f32, p);
+        }
+
+        assert!(SelfPlayChunk::from_bytes(&bytes[..bytes.len() - 1]).is_err());
+        let mut wrong_version = bytes.clone();
+        wrong_version[4] = 2;
+        assert!(SelfPlayChunk::from_bytes(&wrong_version).is_err());
+    }
+
+    #[test]
+    fn test_run_self_play() {
+        let dir = test_dir("run");
+        let config = SelfPlayConfig {
+            augment: true,
+            ..test_config()
+        };
+        let count = run_self_play(&mut puct_engine(), &config, &dir).unwrap();
+
+        // 1局で一定数を超えるので、1局ごとにファイルになる
+        let paths = chunk_paths(&dir).unwrap();
+        assert_eq!(paths.len(), config.games as usize);
+        let samples = paths
+            .iter()
+            .flat_map(|path| SelfPlayChunk::load(path).unwrap().samples)
+            .collect::<Vec<_>>();
+        assert_eq!(samples.len(), count);
+        let expected = (0..config.games)
+            .flat_map(|i| play_self_play_game(&mut puct_engine(), &config, i).samples)
+            .flat_map(|sample| sample.augment())
+            .collect::<Vec<_>>();
+        assert_eq!(samples, expected);
+
+        // 書き出し済みの対局は指さない
+        assert_eq!(run_self_play(&mut puct_engine(), &config, &dir).unwrap(), 0);
+        let more = SelfPlayConfig { games: 3, ..config };
+        let count = run_self_play(&mut puct_engine(), &more, &dir).unwrap();
+        let paths = chunk_paths(&dir).unwrap();
+        assert_eq!(paths.len(), 3);
+        assert_eq!(SelfPlayChunk::load(&paths[2]).unwrap().samples.len(), count);
+        let writer = SelfPlayWriter::new(&dir, config.chunk_size, config.augment).unwrap();
+        assert_eq!(writer.completed_games(), 3);
+        std::fs::remove_dir_all(&dir).unwrap();
+    }
+
+    #[test]
+    fn test_writer_keeps_games_whole() {
+        let dir = test_dir("writer");
+        let mut engine = puct_engine();
+        let config = test_config();
+        let games = (0..3)
+            .map(|i| play_self_play_game(&mut engine, &config, i))
+            .collect::<Vec<_>>();
+        let chunk_size = games[0].samples.len() + 1;
+        let mut writer = SelfPlayWriter::new(&dir, chunk_size, false).unwrap();
+        for game in &games {
+            writer.add_game(game).unwrap();
+        }
+        writer.add_game(&games[0]).unwrap();
+        writer.flush().unwrap();
+
+        let paths = chunk_paths(&dir).unwrap();
+        let game_lists = paths
+            .iter()
+            .map(|path| read_game_list(&path.with_extension(GAME_LIST_EXTENSION)).unwrap())
+            .collect::<Vec<_>>();
+        let sizes = paths
+            .iter()
+            .map(|path| SelfPlayChunk::load(path).unwrap().samples.len())
+            .collect::<Vec<_>>();
+        std::fs::remove_dir_all(&dir).unwrap();
+
+        assert_eq!(game_lists, vec![vec![0, 1], vec![2]]);
+        assert_eq!(
+            sizes,
+            vec![
+                games[0].samples.len() + games[1].samples.len(),
+                games[2].samples.len()
+            ]
+        );
+        assert_eq!(writer.sample_count(), sizes.iter().sum::<usize>());
+    }
 }
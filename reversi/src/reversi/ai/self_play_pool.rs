@@ -0,0 +1,245 @@
+use std::fmt;
+use std::path::Path;
+use std::sync::atomic::{AtomicBool, AtomicUsize, Ordering};
+use std::sync::mpsc;
+use std::time::{Duration, Instant};
+
+use crate::PlayerColor;
+
+use super::self_play::{
+    play_self_play_game, SelfPlayConfig, SelfPlayEngine, SelfPlayGame, SelfPlayWriter,
+};
+
+/// 並列自己対局の進み具合
+#[derive(Clone, Copy, PartialEq, Debug, Default)]
+pub struct SelfPlayStats {
+    /// この実行で終わった対局数
+    pub games: u64,
+    /// 前の実行で書き出し済みのため飛ばした対局数
+    pub skipped: u64,
+    /// 終わった対局の手数の合計(パスを含む)
+    pub moves: u64,
+    /// ファイルに書き出した局面数
+    pub positions: usize,
+    /// 黒の勝ち数(表示のW/D/Lは黒から見た勝ち/引き分け/負け)
+    pub black_wins: u64,
+    pub draws: u64,
+    pub white_wins: u64,
+    pub elapsed: Duration,
+}
+
+impl SelfPlayStats {
+    /// 1秒あたりの対局数
+    pub fn games_per_second(&self) -> f64 {
+        let seconds = self.elapsed.as_secs_f64();
+        if seconds > 0.0 {
+            self.games as f64 / seconds
+        } else {
+            0.0
+        }
+    }
+
+    /// 1局の平均手数
+    pub fn average_length(&self) -> f64 {
+        if self.games > 0 {
+            self.moves as f64 / self.games as f64
+        } else {
+            0.0
+        }
+    }
+
+    fn add_game(&mut self, game: &SelfPlayGame) {
+        self.games += 1;
+        self.moves += game.game_record.len() as u64;
+        match game.winner() {
+            Some(PlayerColor::Black) => self.black_wins += 1,
+            Some(PlayerColor::White) => self.white_wins += 1,
+            None => self.draws += 1,
+        }
+    }
+}
+
+impl fmt::Display for SelfPlayStats {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(
+            f,
+            "{} games ({:.2} games/s), {} positions, average length {:.1}, W/D/L {}/{}/{}",
+            self.games,
+            self.games_per_second(),
+            self.positions,
+            self.average_length(),
+            self.black_wins,
+            self.draws,
+            self.white_wins
+        )
+    }
+}
+
+/// 複数のスレッドで自己対局する
+///
+/// スレッドごとに`make_engine`(引数はスレッドの番号)でエンジンを作る。
+/// 評価関数は複製しても重みを共有するので、1つを読み込んで複製して渡せばよい。
+/// 終わった対局から[`SelfPlayWriter`]で書き出し、書き出し済みの対局は飛ばすので、
+/// 中断した後に同じ設定で実行すると続きから再開する。
+/// 各対局の内容は対局の番号だけで決まり、どのスレッドで指したかによらない。
+pub struct SelfPlayPool<F> {
+    make_engine: F,
+    threads: usize,
+}
+
+impl<F, E> SelfPlayPool<F>
+where
+    F: Fn(usize) -> E + Sync,
+    E: SelfPlayEngine,
+{
+    /// 使えるCPUの数だけスレッドを使う
+    pub fn new(make_engine: F) -> Self {
+        let threads = std::thread::available_parallelism().map_or(1, |n| n.get());
+        SelfPlayPool {
+            make_engine,
+            threads,
+        }
+    }
+
+    pub fn with_threads(mut self, threads: usize) -> Self {
+        self.threads = threads.max(1);
+        self
+    }
+
+    pub fn threads(&self) -> usize {
+        self.threads
+    }
+
+    /// 設定の対局数まで自己対局して、局面を`directory`に書き出す
+    ///
+    /// 対局が終わるたびに`report`を呼ぶ。
+    pub fn run<P, R>(
+        &self,
+        config: &SelfPlayConfig,
+        directory: P,
+        mut report: R,
+    ) -> std::io::Result<SelfPlayStats>
+    where
+        P: AsRef<Path>,
+        R: FnMut(&SelfPlayStats),
+    {
+        let start = Instant::now();
+        let mut writer = SelfPlayWriter::new(directory, config.chunk_size, config.augment)?;
+        let remaining = (0..config.games)
+            .filter(|index| !writer.is_completed(*index))
+            .collect::<Vec<_>>();
+        let mut stats = SelfPlayStats {
+            skipped: config.games - remaining.len() as u64,
+            ..Default::default()
+        };
+
+        let next = AtomicUsize::new(0);
+        let stop = AtomicBool::new(false);
+        let (sender, receiver) = mpsc::channel();
+        std::thread::scope(|s| {
+            for thread in 0..self.threads.min(remaining.len()) {
+                let sender = sender.clone();
+                let (next, stop, remaining) = (&next, &stop, &remaining);
+                s.spawn(move || {
+                    let mut engine = (self.make_engine)(thread);
+                    while !stop.load(Ordering::Relaxed) {
+                        let index = match remaining.get(next.fetch_add(1, Ordering::Relaxed)) {
+                            Some(index) => *index,
+                            None => break,
+                        };
+                        let game = play_self_play_game(&mut engine, config, index);
+                        if sender.send(game).is_err() {
+                            break;
+                        }
+                    }
+                });
+            }
+            drop(sender);
+
+            let mut write = || {
+                for game in receiver.iter() {
+                    writer.add_game(&game)?;
+                    stats.add_game(&game);
+                    stats.positions = writer.sample_count();
+                    stats.elapsed = start.elapsed();
+                    report(&stats);
+                }
+                writer.flush()
+            };
+            let result = write();
+            if result.is_err() {
+                // 指している対局が終わったら止める
+                stop.store(true, Ordering::Relaxed);
+            }
+            result
+        })?;
+
+        stats.positions = writer.sample_count();
+        stats.elapsed = start.elapsed();
+        Ok(stats)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::collections::HashSet;
+
+    use super::*;
+    use crate::ai::{
+        chunk_paths, MctsLimits, PuctConfig, PuctEngine, SelfPlayChunk, SimpleEvaluator,
+    };
+
+    fn make_engine(_thread: usize) -> PuctEngine<SimpleEvaluator> {
+        PuctEngine::new(
+            SimpleEvaluator::new(),
+            MctsLimits::iterations(8),
+            PuctConfig::self_play(),
+        )
+    }
+
+    #[test]
+    fn test_run() {
+        let dir = std::env::temp_dir().join(format!("self_play_pool_test_{}", std::process::id()));
+        let config = SelfPlayConfig {
+            games: 6,
+            seed: 3,
+            random_moves: 2,
+            augment: false,
+            chunk_size: 100,
+        };
+        let pool = SelfPlayPool::new(make_engine).with_threads(3);
+
+        // 途中で中断した状態として、先に4局だけ書き出しておく
+        let first = SelfPlayConfig { games: 4, ..config };
+        let mut reports = 0;
+        let stats = pool.run(&first, &dir, |_| reports += 1).unwrap();
+        assert_eq!(stats.games, 4);
+        assert_eq!(reports, 4);
+
+        let stats = pool.run(&config, &dir, |_| {}).unwrap();
+        assert_eq!(stats.skipped, 4);
+        assert_eq!(stats.games, 2);
+        assert_eq!(stats.black_wins + stats.draws + stats.white_wins, 2);
+        assert!(stats.average_length() > 0.0);
+
+        // 各対局がちょうど1回ずつ、1スレッドで指した場合と同じ内容で書き出されている
+        let mut written = HashSet::new();
+        for path in chunk_paths(&dir).unwrap() {
+            let samples = SelfPlayChunk::load(&path).unwrap().samples;
+            let list = std::fs::read_to_string(path.with_extension("games")).unwrap();
+            let mut samples = samples.into_iter();
+            for index in list.lines().map(|line| line.parse::<u64>().unwrap()) {
+                let game = play_self_play_game(&mut make_engine(0), &config, index);
+                let game_samples = samples
+                    .by_ref()
+                    .take(game.samples.len())
+                    .collect::<Vec<_>>();
+                assert_eq!(game_samples, game.samples);
+                assert!(written.insert(index));
+            }
+            assert!(samples.next().is_none());
+        }
+        std::fs::remove_dir_all(&dir).unwrap();
+        assert_eq!(written.len(), 6);
+    }
+}
@@ -0,0 +1,229 @@
+use std::error::Error;
+use std::path::Path;
+use std::sync::Arc;
+
+use tensorflow::Graph;
+use tensorflow::Operation;
+use tensorflow::SavedModelBundle;
+use tensorflow::SessionOptions;
+use tensorflow::SessionRunArgs;
+use tensorflow::Status;
+use tensorflow::Tensor;
+use tensorflow::TensorInfo;
+
+use crate::reversi::common::*;
+
+use super::alpha_zero::{board_planes, PLANE_COUNT, POLICY_SCALE};
+use super::evaluator::{EvalResult, Evaluator};
+use super::puct::DEFAULT_VALUE_SCALE;
+
+/// SavedModelのタグ
+const SERVE_TAG: &str = "serve";
+
+/// 推論に使うシグネチャと入出力の名前(`export_saved_model`で書き出すもの)
+const SIGNATURE: &str = "serving_default";
+const INPUT: &str = "board";
+const POLICY_OUTPUT: &str = "policy";
+const VALUE_OUTPUT: &str = "value";
+
+const SQUARE_COUNT: usize = BOARD_SIZE * BOARD_SIZE;
+
+struct Model {
+    // 操作はグラフに属するので、セッションと一緒に保持する
+    _graph: Graph,
+    bundle: SavedModelBundle,
+    input: (Operation, i32),
+    policy: (Operation, i32),
+    value: (Operation, i32),
+}
+
+/// TensorFlowのSavedModelによる評価関数
+///
+/// `python/alpha_zero_network.py`の`export_saved_model`で書き出したモデルを読み込む。
+/// 入力は[`board_planes`]の先頭から、モデルの入力チャンネル数の面を使う。
+/// 価値は[`DEFAULT_VALUE_SCALE`]倍、方策は[`POLICY_SCALE`]倍した整数で返す。
+/// 複製してもモデルは共有する。
+#[derive(Clone)]
+pub struct TensorflowEvaluator {
+    model: Arc<Model>,
+    input_channels: usize,
+}
+
+impl TensorflowEvaluator {
+    /// `save_dir`のSavedModelを読み込む
+    pub fn load<P: AsRef<Path>>(save_dir: P) -> Result<Self, Box<dyn Error>> {
+        let mut graph = Graph::new();
+        let bundle =
+            SavedModelBundle::load(&SessionOptions::new(), [SERVE_TAG], &mut graph, save_dir)?;
+        let signature = bundle.meta_graph_def().get_signature(SIGNATURE)?;
+        let operation = |info: &TensorInfo| -> Result<(Operation, i32), Status> {
+            let op = graph.operation_by_name_required(&info.name().name)?;
+            Ok((op, info.name().index))
+        };
+
+        // 入力は(局面数, 8, 8, チャンネル数)
+        let input_info = signature.get_input(INPUT)?;
+        let shape = input_info.shape();
+        let channels = if shape.dims() == Some(4) {
+            shape[3]
+        } else {
+            None
+        };
+        let input_channels = match channels {
+            Some(channels) if (1..=PLANE_COUNT as i64).contains(&channels) => channels as usize,
+            _ => return Err(format!("unsupported input shape: {:?}", shape).into()),
+        };
+        let input = operation(input_info)?;
+        let policy = operation(signature.get_output(POLICY_OUTPUT)?)?;
+        let value = operation(signature.get_output(VALUE_OUTPUT)?)?;
+
+        Ok(TensorflowEvaluator {
+            model: Arc::new(Model {
+                _graph: graph,
+                bundle,
+                input,
+                policy,
+                value,
+            }),
+            input_channels,
+        })
+    }
+
+    pub fn input_channels(&self) -> usize {
+        self.input_channels
+    }
+
+    /// 局面をまとめて推論し、方策と価値を返す
+    pub fn predict(
+        &self,
+        positions: &[(Squares, PlayerColor)],
+    ) -> Result<Vec<(Vec<f32>, f32)>, Status> {
+        let input = positions
+            .iter()
+            .flat_map(|(board, color)| {
+                board_planes(board, color)
+                    .chunks_exact(PLANE_COUNT)
+                    .flat_map(|plane| plane[..self.input_channels].to_vec())
+                    .collect::<Vec<_>>()
+            })
+            .collect::<Vec<_>>();
+        let dims = [
+            positions.len() as u64,
+            BOARD_SIZE as u64,
+            BOARD_SIZE as u64,
+            self.input_channels as u64,
+        ];
+        let input = Tensor::<f32>::new(&dims).with_values(&input)?;
+
+        let model = &self.model;
+        let mut run_args = SessionRunArgs::new();
+        run_args.add_feed(&model.input.0, model.input.1, &input);
+        let policy_fetch = run_args.request_fetch(&model.policy.0, model.policy.1);
+        let value_fetch = run_args.request_fetch(&model.value.0, model.value.1);
+        model.bundle.session.run(&mut run_args)?;
+        let policy = run_args.fetch::<f32>(policy_fetch)?;
+        let value = run_args.fetch::<f32>(value_fetch)?;
+
+        Ok(policy
+            .chunks_exact(SQUARE_COUNT)
+            .zip(value.iter())
+            .map(|(p, v)| (p.to_vec(), *v))
+            .collect())
+    }
+}
+
+impl Evaluator for TensorflowEvaluator {
+    /// 推論に失敗した場合はエラーを標準エラー出力に書き、価値0で方策のない結果を返す
+    fn evaluate(&mut self, board: &Squares, color: &PlayerColor) -> EvalResult {
+        match self.predict(&[(*board, *color)]) {
+            Ok(mut results) => {
+                let (policy, value) = results.remove(0);
+                eval_result(&policy, value)
+            }
+            Err(status) => {
+                eprintln!("tensorflow inference failed: {}", status);
+                EvalResult {
+                    value: 0,
+                    policy: None,
+                }
+            }
+        }
+    }
+
+    /// 全ての局面を1回の推論で評価する
+    ///
+    /// 推論に失敗した場合はエラーを標準エラー出力に書き、1局面ずつ[`Evaluator::evaluate`]する。
+    fn evaluate_batch(&mut self, positions: &[(Squares, PlayerColor)]) -> Vec<EvalResult> {
+        if positions.is_empty() {
+            return Vec::new();
+        }
+        match self.predict(positions) {
+            Ok(results) => results
+                .iter()
+                .map(|(policy, value)| eval_result(policy, *value))
+                .collect(),
+            Err(status) => {
+                eprintln!(
+                    "tensorflow batch inference failed, evaluating one by one: {}",
+                    status
+                );
+                positions
+                    .iter()
+                    .map(|(board, color)| self.evaluate(board, color))
+                    .collect()
+            }
+        }
+    }
+}
+
+fn eval_result(policy: &[f32], value: f32) -> EvalResult {
+    let mut scaled = [0; SQUARE_COUNT];
+    for (s, p) in scaled.iter_mut().zip(policy) {
+        *s = (p * POLICY_SCALE).round() as i32;
+    }
+    EvalResult {
+        value: (value * DEFAULT_VALUE_SCALE as f32).round() as i32,
+        policy: Some(scaled),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::ai::AlphaZeroNetwork;
+    use crate::board::{BitBoard, Board};
+
+    /// Rustでの推論と一致するか
+    ///
+    /// `python/alpha_zero_network.py`を実行して`python/testdata`にモデルを書き出してから実行する。
+    /// 実行にはlibtensorflowが必要。
+    #[test]
+    #[ignore = "needs python/testdata/saved_model written by `python python/alpha_zero_network.py`"]
+    fn test_matches_rust_inference() {
+        let dir = Path::new(env!("CARGO_MANIFEST_DIR")).join("python/testdata");
+        let mut evaluator = TensorflowEvaluator::load(dir.join("saved_model")).unwrap();
+        let network = AlphaZeroNetwork::load(dir.join("alpha_zero_test.bin")).unwrap();
+        assert_eq!(evaluator.input_channels(), network.input_channels());
+
+        let board = BitBoard::new_initial();
+        let positions = [
+            (*board.squares(), PlayerColor::Black),
+            (*board.squares(), PlayerColor::White),
+        ];
+        let results = evaluator.predict(&positions).unwrap();
+        for ((squares, color), (policy, value)) in positions.iter().zip(&results) {
+            let (expected_policy, expected_value) = network.predict(squares, color);
+            for (p, e) in policy.iter().zip(&expected_policy) {
+                assert!((p - e).abs() < 1e-4, "{} {}", p, e);
+            }
+            assert!((value - expected_value).abs() < 1e-4);
+        }
+
+        let batch = evaluator.evaluate_batch(&positions);
+        assert_eq!(
+            batch[0],
+            evaluator.evaluate(&positions[0].0, &positions[0].1)
+        );
+        assert!(batch[1].policy.is_some());
+    }
+}
@@ -0,0 +1,494 @@
+use std::sync::atomic::{AtomicU64, AtomicU8, Ordering};
+
+use crate::{index_to_position, position_to_index, Move, PlayerColor, BOARD_SIZE};
+
+/// 置換表に保存した評価値の種類
+#[derive(Clone, Copy, PartialEq, Eq, Debug)]
+pub enum Bound {
+    /// 正確な値
+    Exact,
+    /// 下限値(beta cut)
+    Lower,
+    /// 上限値(全ての手がalpha以下)
+    Upper,
+}
+
+/// 置換表のエントリの置き換え方針
+#[derive(Clone, Copy, PartialEq, Eq, Debug)]
+pub enum ReplacementPolicy {
+    /// 常に新しいエントリで上書きする
+    Always,
+    /// 同じ探索中は、より深く探索したエントリを残す
+    DepthPreferred,
+}
+
+const NO_MOVE: u8 = u8::MAX;
+const PASS_MOVE: u8 = (BOARD_SIZE * BOARD_SIZE) as u8;
+
+fn encode_move(move_: Option<&Move>) -> u8 {
+    match move_ {
+        Some(Move::Position(_, position)) => position_to_index(position) as u8,
+        Some(Move::Pass(_)) => PASS_MOVE,
+        None => NO_MOVE,
+    }
+}
+
+#[derive(Clone, Copy, Debug)]
+pub struct TranspositionEntry {
+    hash: u64,
+    score: i32,
+    depth: u8,
+    bound: Bound,
+    best_move: u8,
+    generation: u8,
+}
+
+impl TranspositionEntry {
+    pub fn hash(&self) -> u64 {
+        self.hash
+    }
+
+    pub fn score(&self) -> i32 {
+        self.score
+    }
+
+    pub fn depth(&self) -> usize {
+        self.depth as usize
+    }
+
+    pub fn bound(&self) -> Bound {
+        self.bound
+    }
+
+    /// 最善手
+    /// 局面の手番`color`の手として返す
+    pub fn best_move(&self, color: PlayerColor) -> Option<Move> {
+        match self.best_move {
+            NO_MOVE => None,
+            PASS_MOVE => Some(Move::new_pass(color)),
+            index => Some(Move::new_position(color, index_to_position(index as usize))),
+        }
+    }
+
+    /// 探索窓(alpha, beta)に対してこのエントリだけで値が確定するなら、その値を返す
+    pub fn cutoff(&self, depth: usize, alpha: i32, beta: i32) -> Option<i32> {
+        if self.depth() < depth {
+            return None;
+        }
+        match self.bound {
+            Bound::Exact => Some(self.score),
+            Bound::Lower if self.score >= beta => Some(self.score),
+            Bound::Upper if self.score <= alpha => Some(self.score),
+            _ => None,
+        }
+    }
+}
+
+/// 置換表の統計情報
+#[derive(Clone, Copy, Default, PartialEq, Eq, Debug)]
+pub struct TranspositionTableStats {
+    /// 参照回数
+    pub probes: u64,
+    /// 同じ局面のエントリが見つかった回数
+    pub hits: u64,
+    /// 保存回数
+    pub stores: u64,
+    /// 別の局面のエントリと同じ場所に保存しようとした回数
+    pub collisions: u64,
+}
+
+/// 局面のハッシュ値をキーにした固定サイズの置換表
+///
+/// エントリ数は2のべき乗で、ハッシュ値の下位ビットで格納位置を決める。
+pub struct TranspositionTable {
+    entries: Vec<Option<TranspositionEntry>>,
+    mask: usize,
+    policy: ReplacementPolicy,
+    generation: u8,
+    stats: TranspositionTableStats,
+}
+
+impl TranspositionTable {
+    /// メモリ使用量`size_mb`MB以内で作成する
+    pub fn new(size_mb: usize, policy: ReplacementPolicy) -> Self {
+        let entry_size = std::mem::size_of::<Option<TranspositionEntry>>();
+        let count = (size_mb * 1024 * 1024 / entry_size).max(1);
+        // countを超えない最大の2のべき乗
+        let count = 1 << (usize::BITS - 1 - count.leading_zeros());
+        Self::with_entry_count(count, policy)
+    }
+
+    /// エントリ数を指定して作成する(2のべき乗に切り上げる)
+    pub fn with_entry_count(count: usize, policy: ReplacementPolicy) -> Self {
+        let count = count.max(1).next_power_of_two();
+        TranspositionTable {
+            entries: vec![None; count],
+            mask: count - 1,
+            policy,
+            generation: 0,
+            stats: Default::default(),
+        }
+    }
+
+    pub fn capacity(&self) -> usize {
+        self.entries.len()
+    }
+
+    /// おおよそのメモリ使用量(バイト)
+    pub fn memory_size(&self) -> usize {
+        self.entries.len() * std::mem::size_of::<Option<TranspositionEntry>>()
+    }
+
+    pub fn policy(&self) -> ReplacementPolicy {
+        self.policy
+    }
+
+    pub fn stats(&self) -> &TranspositionTableStats {
+        &self.stats
+    }
+
+    pub fn reset_stats(&mut self) {
+        self.stats = Default::default();
+    }
+
+    /// 全エントリを削除する
+    pub fn clear(&mut self) {
+        self.entries.iter_mut().for_each(|e| *e = None);
+        self.generation = 0;
+    }
+
+    /// 新しい探索を始める
+    ///
+    /// 以前の探索のエントリは[`ReplacementPolicy::DepthPreferred`]でも上書きされるようになる。
+    pub fn new_search(&mut self) {
+        self.generation = self.generation.wrapping_add(1);
+    }
+
+    pub fn probe(&mut self, hash: u64) -> Option<&TranspositionEntry> {
+        self.stats.probes += 1;
+        match &self.entries[hash as usize & self.mask] {
+            Some(entry) if entry.hash == hash => {
+                self.stats.hits += 1;
+                Some(entry)
+            }
+            _ => None,
+        }
+    }
+
+    /// 統計情報を更新せずに参照する
+    pub fn get(&self, hash: u64) -> Option<&TranspositionEntry> {
+        match &self.entries[hash as usize & self.mask] {
+            Some(entry) if entry.hash == hash => Some(entry),
+            _ => None,
+        }
+    }
+
+    pub fn store(
+        &mut self,
+        hash: u64,
+        depth: usize,
+        bound: Bound,
+        score: i32,
+        best_move: Option<&Move>,
+    ) {
+        let slot = &mut self.entries[hash as usize & self.mask];
+        let mut best_move = encode_move(best_move);
+        if let Some(old) = slot {
+            if old.hash == hash {
+                // 最善手が分からないときは以前の最善手を残す
+                if best_move == NO_MOVE {
+                    best_move = old.best_move;
+                }
+            } else {
+                self.stats.collisions += 1;
+            }
+
+            let replace = match self.policy {
+                ReplacementPolicy::Always => true,
+                ReplacementPolicy::DepthPreferred => {
+                    old.generation != self.generation || depth >= old.depth as usize
+                }
+            };
+            if !replace {
+                return;
+            }
+        }
+
+        *slot = Some(TranspositionEntry {
+            hash,
+            score,
+            depth: depth.min(u8::MAX as usize) as u8,
+            bound,
+            best_move,
+            generation: self.generation,
+        });
+        self.stats.stores += 1;
+    }
+}
+
+/// 複数のスレッドから同時に参照・保存できる置換表
+///
+/// エントリは64bitのデータと、ハッシュ値とデータの排他的論理和の2語で保存する。
+/// 書き込みが競合して2語が食い違ったエントリはハッシュ値が一致しないので、ロックなしでも壊れたエントリは読まれない。
+/// 統計情報は取らない。
+pub struct SharedTranspositionTable {
+    entries: Vec<SharedEntry>,
+    mask: usize,
+    policy: ReplacementPolicy,
+    generation: AtomicU8,
+}
+
+#[derive(Default)]
+struct SharedEntry {
+    key: AtomicU64,
+    data: AtomicU64,
+}
+
+impl SharedTranspositionTable {
+    /// メモリ使用量`size_mb`MB以内で作成する
+    pub fn new(size_mb: usize, policy: ReplacementPolicy) -> Self {
+        let entry_size = std::mem::size_of::<SharedEntry>();
+        let count = (size_mb * 1024 * 1024 / entry_size).max(1);
+        // countを超えない最大の2のべき乗
+        let count = 1 << (usize::BITS - 1 - count.leading_zeros());
+        Self::with_entry_count(count, policy)
+    }
+
+    /// エントリ数を指定して作成する(2のべき乗に切り上げる)
+    pub fn with_entry_count(count: usize, policy: ReplacementPolicy) -> Self {
+        let count = count.max(1).next_power_of_two();
+        SharedTranspositionTable {
+            entries: (0..count).map(|_| SharedEntry::default()).collect(),
+            mask: count - 1,
+            policy,
+            generation: AtomicU8::new(0),
+        }
+    }
+
+    pub fn capacity(&self) -> usize {
+        self.entries.len()
+    }
+
+    /// おおよそのメモリ使用量(バイト)
+    pub fn memory_size(&self) -> usize {
+        self.entries.len() * std::mem::size_of::<SharedEntry>()
+    }
+
+    pub fn policy(&self) -> ReplacementPolicy {
+        self.policy
+    }
+
+    /// 全エントリを削除する(探索中のスレッドがない状態で呼ぶこと)
+    pub fn clear(&mut self) {
+        for entry in self.entries.iter_mut() {
+            *entry.key.get_mut() = 0;
+            *entry.data.get_mut() = 0;
+        }
+        *self.generation.get_mut() = 0;
+    }
+
+    /// 新しい探索を始める
+    ///
+    /// 全スレッドの探索を始める前に1回だけ呼ぶ。
+    pub fn new_search(&self) {
+        self.generation.fetch_add(1, Ordering::Relaxed);
+    }
+
+    pub fn probe(&self, hash: u64) -> Option<TranspositionEntry> {
+        let slot = &self.entries[hash as usize & self.mask];
+        let data = slot.data.load(Ordering::Relaxed);
+        let key = slot.key.load(Ordering::Relaxed);
+        if key ^ data != hash {
+            return None;
+        }
+        unpack_entry(hash, data)
+    }
+
+    pub fn store(
+        &self,
+        hash: u64,
+        depth: usize,
+        bound: Bound,
+        score: i32,
+        best_move: Option<&Move>,
+    ) {
+        let slot = &self.entries[hash as usize & self.mask];
+        let generation = self.generation.load(Ordering::Relaxed);
+        let mut best_move = encode_move(best_move);
+        let old_data = slot.data.load(Ordering::Relaxed);
+        let old_hash = slot.key.load(Ordering::Relaxed) ^ old_data;
+        if let Some(old) = unpack_entry(old_hash, old_data) {
+            // 最善手が分からないときは以前の最善手を残す
+            if old.hash == hash && best_move == NO_MOVE {
+                best_move = old.best_move;
+            }
+            let replace = match self.policy {
+                ReplacementPolicy::Always => true,
+                ReplacementPolicy::DepthPreferred => {
+                    old.generation != generation || depth >= old.depth as usize
+                }
+            };
+            if !replace {
+                return;
+            }
+        }
+
+        let data = pack_entry(&TranspositionEntry {
+            hash,
+            score,
+            depth: depth.min(u8::MAX as usize) as u8,
+            bound,
+            best_move,
+            generation,
+        });
+        slot.key.store(hash ^ data, Ordering::Relaxed);
+        slot.data.store(data, Ordering::Relaxed);
+    }
+}
+
+/// 評価値・深さ・種類・最善手・世代を64bitに詰める(空のエントリと区別するため種類は1から)
+fn pack_entry(entry: &TranspositionEntry) -> u64 {
+    let bound = match entry.bound {
+        Bound::Exact => 1,
+        Bound::Lower => 2,
+        Bound::Upper => 3,
+    };
+    entry.score as u32 as u64
+        | (entry.depth as u64) << 32
+        | bound << 40
+        | (entry.best_move as u64) << 48
+        | (entry.generation as u64) << 56
+}
+
+fn unpack_entry(hash: u64, data: u64) -> Option<TranspositionEntry> {
+    let bound = match (data >> 40) as u8 {
+        1 => Bound::Exact,
+        2 => Bound::Lower,
+        3 => Bound::Upper,
+        _ => return None,
+    };
+    Some(TranspositionEntry {
+        hash,
+        score: data as u32 as i32,
+        depth: (data >> 32) as u8,
+        bound,
+        best_move: (data >> 48) as u8,
+        generation: (data >> 56) as u8,
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::Position;
+
+    #[test]
+    fn test_size() {
+        let table = TranspositionTable::new(1, ReplacementPolicy::Always);
+        assert!(table.capacity().is_power_of_two());
+        assert!(table.memory_size() <= 1024 * 1024);
+        assert!(table.memory_size() * 2 > 1024 * 1024);
+
+        let table = TranspositionTable::with_entry_count(1000, ReplacementPolicy::Always);
+        assert_eq!(table.capacity(), 1024);
+    }
+
+    #[test]
+    fn test_store_and_probe() {
+        let mut table = TranspositionTable::with_entry_count(16, ReplacementPolicy::Always);
+        let m = Move::new_position(PlayerColor::White, Position(2, 5));
+        table.store(0x1234, 3, Bound::Lower, 10, Some(&m));
+
+        let entry = table.probe(0x1234).unwrap();
+        assert_eq!(entry.depth(), 3);
+        assert_eq!(entry.bound(), Bound::Lower);
+        assert_eq!(entry.score(), 10);
+        assert_eq!(entry.best_move(PlayerColor::White), Some(m));
+        assert_eq!(entry.cutoff(3, 0, 10), Some(10));
+        assert_eq!(entry.cutoff(3, 0, 11), None);
+        assert_eq!(entry.cutoff(4, 0, 10), None);
+
+        assert!(table.probe(0x1235).is_none());
+        assert_eq!(table.stats().probes, 2);
+        assert_eq!(table.stats().hits, 1);
+        assert_eq!(table.stats().stores, 1);
+    }
+
+    #[test]
+    fn test_replacement_policy() {
+        let m = Move::new_pass(PlayerColor::Black);
+
+        let mut table = TranspositionTable::with_entry_count(16, ReplacementPolicy::DepthPreferred);
+        table.store(0x01, 5, Bound::Exact, 1, Some(&m));
+        // 同じ格納位置で浅い探索は保存しない
+        table.store(0x11, 2, Bound::Exact, 2, None);
+        assert_eq!(table.stats().collisions, 1);
+        assert!(table.probe(0x11).is_none());
+        assert_eq!(
+            table.probe(0x01).unwrap().best_move(PlayerColor::Black),
+            Some(m)
+        );
+
+        // 新しい探索では上書きする
+        table.new_search();
+        table.store(0x11, 2, Bound::Exact, 2, None);
+        assert!(table.probe(0x01).is_none());
+        assert_eq!(table.probe(0x11).unwrap().score(), 2);
+
+        let mut table = TranspositionTable::with_entry_count(16, ReplacementPolicy::Always);
+        table.store(0x01, 5, Bound::Exact, 1, None);
+        table.store(0x11, 2, Bound::Exact, 2, None);
+        assert!(table.probe(0x01).is_none());
+        assert_eq!(table.probe(0x11).unwrap().score(), 2);
+    }
+
+    #[test]
+    fn test_shared_store_and_probe() {
+        let table =
+            SharedTranspositionTable::with_entry_count(16, ReplacementPolicy::DepthPreferred);
+        assert_eq!(table.capacity(), 16);
+        let m = Move::new_position(PlayerColor::Black, Position(7, 0));
+        table.store(0x1234, 3, Bound::Upper, -10, Some(&m));
+
+        let entry = table.probe(0x1234).unwrap();
+        assert_eq!(entry.hash(), 0x1234);
+        assert_eq!(entry.depth(), 3);
+        assert_eq!(entry.bound(), Bound::Upper);
+        assert_eq!(entry.score(), -10);
+        assert_eq!(entry.best_move(PlayerColor::Black), Some(m));
+        assert!(table.probe(0x1235).is_none());
+        assert!(table.probe(0).is_none());
+
+        // 同じ格納位置で浅い探索は保存しない
+        table.store(0x1244, 2, Bound::Exact, 2, None);
+        assert!(table.probe(0x1244).is_none());
+        table.new_search();
+        table.store(0x1244, 2, Bound::Exact, i32::MIN + 1, None);
+        assert!(table.probe(0x1234).is_none());
+        assert_eq!(table.probe(0x1244).unwrap().score(), i32::MIN + 1);
+        assert_eq!(
+            table.probe(0x1244).unwrap().best_move(PlayerColor::White),
+            None
+        );
+    }
+
+    #[test]
+    fn test_shared_from_threads() {
+        let table = SharedTranspositionTable::with_entry_count(1024, ReplacementPolicy::Always);
+        std::thread::scope(|s| {
+            for t in 0..4u64 {
+                let table = &table;
+                s.spawn(move || {
+                    for i in 0..10000u64 {
+                        let hash = (i % 2048).wrapping_mul(0x9e37_79b9_7f4a_7c15) ^ t;
+                        table.store(hash, (i % 8) as usize, Bound::Exact, hash as i32, None);
+                        // 別のスレッドが書き込んでいても、読めたエントリは壊れていない
+                        if let Some(entry) = table.probe(hash) {
+                            assert_eq!(entry.score(), hash as i32);
+                        }
+                    }
+                });
+            }
+        });
+    }
+}
@@ -4,11 +4,34 @@ mod array_board;
 mod bit_board;
 mod index_board;
 mod indexer;
+mod symmetry;
+mod zobrist;
 
 pub use array_board::ArrayBoard;
-pub use bit_board::BitBoard;
+pub use bit_board::{flip_data, movable_position, BitBoard};
 pub use index_board::IndexBoard;
+pub(crate) use indexer::line_to_index;
 pub use indexer::Indexer;
+pub use symmetry::{
+    flip_anti_diagonal, flip_diagonal, flip_horizontal, flip_vertical, rotate180, rotate270,
+    rotate90, Symmetry,
+};
+pub use zobrist::zobrist_hash;
+
+/// 着手を取り消すための情報
+///
+/// `flipped`はひっくり返した石のビットマスク(ビット位置は[`position_to_index`]と同じ)
+#[derive(Clone, Copy, PartialEq, Eq, Debug)]
+pub struct MoveUndo {
+    pub move_: Move,
+    pub flipped: u64,
+}
+
+impl MoveUndo {
+    pub fn new(move_: Move, flipped: u64) -> MoveUndo {
+        MoveUndo { move_, flipped }
+    }
+}
 
 pub trait Board {
     fn squares(&self) -> &Squares;
@@ -21,6 +44,14 @@ pub trait Board {
         Self: Sized;
     fn get_movable_positions(&self, color: &PlayerColor) -> Vec<Position>;
 
+    /// 盤面を直接書き換えて着手する
+    ///
+    /// 無効な手の場合は盤面を変更せずに`None`を返す。
+    fn make_move(&mut self, move_: &Move) -> Option<MoveUndo>;
+
+    /// [`Board::make_move`]で行った着手を取り消す
+    fn undo_move(&mut self, undo: &MoveUndo);
+
     fn is_game_over(&self) -> bool {
         self.get_movable_positions(&PlayerColor::Black).is_empty()
             && self.get_movable_positions(&PlayerColor::White).is_empty()
@@ -38,6 +69,13 @@ pub trait Board {
         self.square_count(Square::Empty)
     }
 
+    /// 石の配置と手番から求めたZobristハッシュ
+    ///
+    /// 同じ局面なら実装によらず同じ値になる。
+    fn hash(&self) -> u64 {
+        zobrist_hash(self.squares(), self.turn())
+    }
+
     fn turn(&self) -> PlayerColor {
         if self.depth() % 2 == 0 {
             PlayerColor::Black
@@ -52,3 +90,34 @@ pub trait Board {
         text
     }
 }
+
+/// 着手前後の盤面からひっくり返った石のマスクを求める
+fn flipped_mask(before: &Squares, after: &Squares, move_: &Move) -> u64 {
+    let placed = match move_ {
+        Move::Position(_, position) => position_to_index(position),
+        Move::Pass(_) => return 0,
+    };
+    before
+        .iter()
+        .zip(after.iter())
+        .enumerate()
+        .filter(|(i, (b, a))| *i != placed && b != a)
+        .fold(0, |mask, (i, _)| mask | (1 << i))
+}
+
+/// [`MoveUndo`]に従って盤面の配列を着手前に戻す
+fn undo_squares(squares: &mut Squares, undo: &MoveUndo) {
+    if let Move::Position(color, position) = undo.move_ {
+        let opponent = match color {
+            PlayerColor::Black => Square::White,
+            PlayerColor::White => Square::Black,
+        };
+        squares[position_to_index(&position)] = Square::Empty;
+        let mut flipped = undo.flipped;
+        while flipped != 0 {
+            let index = flipped.trailing_zeros() as usize;
+            squares[index] = opponent;
+            flipped &= flipped - 1;
+        }
+    }
+}
@@ -1,4 +1,7 @@
+use crate::board::flipped_mask;
+use crate::board::undo_squares;
 use crate::board::Board;
+use crate::board::MoveUndo;
 use crate::position_to_index;
 use crate::Move;
 use crate::PlayerColor;
@@ -82,7 +85,7 @@ impl Board for ArrayBoard {
         match move_ {
             Move::Pass(color) => {
                 // パスできるかチェック
-                let movables = self.get_movable_positions(&color);
+                let movables = self.get_movable_positions(color);
                 if movables.is_empty() {
                     Some(ArrayBoard::new(self.squares, self.depth + 1))
                 } else {
@@ -90,12 +93,12 @@ impl Board for ArrayBoard {
                 }
             }
             Move::Position(color, position) => {
-                let index = position_to_index(&position);
+                let index = position_to_index(position);
                 if self.squares[index] != Square::Empty {
                     // 空きマス以外には石を置けない
                     return None;
                 }
-                let movables = self.get_movable_positions(&color);
+                let movables = self.get_movable_positions(color);
                 if movables
                     .iter()
                     .any(|p| p.0 == position.0 && p.1 == position.1)
@@ -110,7 +113,7 @@ impl Board for ArrayBoard {
                     // アクションの箇所に石を置く
                     squares[index] = square_color;
                     for dir in DIRECTIONS {
-                        let flip = self.get_flip_count(&color, &position, &dir);
+                        let flip = self.get_flip_count(color, position, &dir);
                         let mut pos = (position.0 as i32, position.1 as i32);
                         for _ in 0..flip {
                             pos.0 += dir.1;
@@ -149,6 +152,18 @@ impl Board for ArrayBoard {
         positions
     }
 
+    fn make_move(&mut self, move_: &Move) -> Option<MoveUndo> {
+        let next = self.apply_move(move_)?;
+        let flipped = flipped_mask(&self.squares, &next.squares, move_);
+        *self = next;
+        Some(MoveUndo::new(*move_, flipped))
+    }
+
+    fn undo_move(&mut self, undo: &MoveUndo) {
+        undo_squares(&mut self.squares, undo);
+        self.depth -= 1;
+    }
+
     fn square_count(&self, color: Square) -> u32 {
         let mut count = 0;
         for s in &self.squares {
@@ -217,4 +232,35 @@ mod tests {
         assert!(next_board.squares[position_to_index(&Position(3, 3))] == Square::White);
         assert!(next_board.squares[position_to_index(&Position(4, 4))] == Square::White);
     }
+
+    #[test]
+    fn test_make_undo_move() {
+        let mut board = ArrayBoard::new_initial();
+        let moves = [
+            Move::new_position(PlayerColor::Black, Position(4, 5)),
+            Move::new_position(PlayerColor::White, Position(5, 5)),
+            Move::new_position(PlayerColor::Black, Position(5, 4)),
+            Move::new_position(PlayerColor::White, Position(3, 5)),
+            Move::new_position(PlayerColor::Black, Position(2, 4)),
+        ];
+
+        let mut history = Vec::new();
+        for m in &moves {
+            let expected = board.apply_move(m).unwrap();
+            let undo = board.make_move(m).unwrap();
+            assert!(board.squares() == expected.squares());
+            assert_eq!(board.depth(), expected.depth());
+            history.push((undo, expected));
+        }
+
+        let m = Move::new_position(PlayerColor::White, Position(0, 0));
+        assert!(board.make_move(&m).is_none());
+
+        while let Some((undo, expected)) = history.pop() {
+            assert!(board.squares() == expected.squares());
+            board.undo_move(&undo);
+        }
+        assert!(board.squares() == ArrayBoard::new_initial().squares());
+        assert_eq!(board.depth(), 0);
+    }
 }
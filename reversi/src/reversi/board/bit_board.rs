@@ -1,4 +1,7 @@
+use crate::board::zobrist;
 use crate::board::Board;
+use crate::board::MoveUndo;
+use crate::board::Symmetry;
 use crate::index_to_position;
 use crate::position_to_index;
 use crate::Move;
@@ -51,6 +54,16 @@ fn data_to_squares(black: u64, white: u64) -> Squares {
     squares
 }
 
+/// マスクで指定されたマスを`square`にする
+fn set_squares(squares: &mut Squares, mask: u64, square: Square) {
+    let mut mask = mask;
+    while mask != 0 {
+        let index = mask.trailing_zeros() as usize;
+        squares[index] = square;
+        mask &= mask - 1;
+    }
+}
+
 fn squares_to_data(squares: &Squares) -> (u64, u64) {
     let mut black_data: u64 = 0;
     let mut white_data: u64 = 0;
@@ -101,7 +114,8 @@ where
     result
 }
 
-fn movable_position(player: u64, opponent: u64) -> u64 {
+/// `player`が着手できる位置のビットボード
+pub fn movable_position(player: u64, opponent: u64) -> u64 {
     fn dir_continuous_line(data: u64, opponent: u64, dir_mask: u64, shift_count: u32) -> u64 {
         let mask = opponent & dir_mask;
         let mut line1 = continuous_line::<LeftShift>(data, mask, shift_count);
@@ -119,7 +133,8 @@ fn movable_position(player: u64, opponent: u64) -> u64 {
     (horizontal | vertical | diagonal_ltrb | diagonal_rtlb) & !(player | opponent)
 }
 
-fn flip_data(player: u64, opponent: u64, position: u64) -> u64 {
+/// `player`が`position`に着手したときにひっくり返る石のビットボード
+pub fn flip_data(player: u64, opponent: u64, position: u64) -> u64 {
     fn dir_flip(player: u64, opponent: u64, position: u64, dir_mask: u64, shift_count: u32) -> u64 {
         let mut result: u64 = 0;
         let mask = opponent & dir_mask;
@@ -147,11 +162,6 @@ fn flip_data(player: u64, opponent: u64, position: u64) -> u64 {
     horizontal | vertical | diagonal_ltrb | diagonal_rtlb
 }
 
-fn flip(player: u64, opponent: u64, position: u64) -> (u64, u64) {
-    let flip_data = flip_data(player, opponent, position);
-    (player ^ position ^ flip_data, opponent ^ flip_data)
-}
-
 /// ボード
 #[derive(Clone, Debug)]
 pub struct BitBoard {
@@ -159,6 +169,7 @@ pub struct BitBoard {
     white: u64,
     squares: Squares,
     depth: u32,
+    hash: u64,
 }
 
 impl BitBoard {
@@ -167,21 +178,25 @@ impl BitBoard {
         let (black, white) = squares_to_data(squares);
         let mut ss: Squares = [Square::Empty; BOARD_SIZE * BOARD_SIZE];
         ss.clone_from_slice(squares);
+        let hash = zobrist::zobrist_hash(&ss, depth_to_turn(depth));
         Self {
             black,
             white,
             squares: ss,
             depth,
+            hash,
         }
     }
 
     pub fn new_from_data(black: u64, white: u64, depth: u32) -> Self {
         let squares = data_to_squares(black, white);
+        let hash = zobrist::zobrist_hash(&squares, depth_to_turn(depth));
         Self {
             black,
             white,
             squares,
             depth,
+            hash,
         }
     }
 
@@ -194,6 +209,85 @@ impl BitBoard {
 
         Self::new(&squares, 0)
     }
+
+    /// 黒石のビットボード
+    pub fn black(&self) -> u64 {
+        self.black
+    }
+
+    /// 白石のビットボード
+    pub fn white(&self) -> u64 {
+        self.white
+    }
+
+    /// 対称変換した盤面
+    pub fn transform(&self, symmetry: Symmetry) -> Self {
+        Self::new_from_data(
+            symmetry.transform_data(self.black),
+            symmetry.transform_data(self.white),
+            self.depth,
+        )
+    }
+
+    /// 8通りの対称変換のうち(黒, 白)のビットボードが最小になる盤面と、そのときの変換
+    ///
+    /// 元の盤面の手は`symmetry.transform_move`で正規形の盤面の手に変換できる。
+    pub fn canonical(&self) -> (Self, Symmetry) {
+        let symmetry = Symmetry::ALL
+            .iter()
+            .min_by_key(|s| (s.transform_data(self.black), s.transform_data(self.white)))
+            .copied()
+            .unwrap();
+        (self.transform(symmetry), symmetry)
+    }
+
+    /// 手番側と相手側の石のビットボード
+    fn player_opponent(&self, color: &PlayerColor) -> (u64, u64) {
+        match color {
+            PlayerColor::Black => (self.black, self.white),
+            PlayerColor::White => (self.white, self.black),
+        }
+    }
+
+    fn set_player_opponent(&mut self, color: &PlayerColor, player: u64, opponent: u64) {
+        match color {
+            PlayerColor::Black => {
+                self.black = player;
+                self.white = opponent;
+            }
+            PlayerColor::White => {
+                self.white = player;
+                self.black = opponent;
+            }
+        }
+    }
+}
+
+fn depth_to_turn(depth: u32) -> PlayerColor {
+    if depth % 2 == 0 {
+        PlayerColor::Black
+    } else {
+        PlayerColor::White
+    }
+}
+
+/// 着手によるハッシュ値の差分(手番の交代を含む)
+fn move_hash(color: &PlayerColor, pos: u64, flipped: u64) -> u64 {
+    let mut hash = zobrist::WHITE_TO_MOVE_KEY;
+    hash ^= zobrist::square_key(*color, pos.trailing_zeros() as usize);
+    let mut flipped = flipped;
+    while flipped != 0 {
+        hash ^= zobrist::flip_key(flipped.trailing_zeros() as usize);
+        flipped &= flipped - 1;
+    }
+    hash
+}
+
+fn color_to_square(color: &PlayerColor) -> Square {
+    match color {
+        PlayerColor::Black => Square::Black,
+        PlayerColor::White => Square::White,
+    }
 }
 
 impl Board for BitBoard {
@@ -201,56 +295,71 @@ impl Board for BitBoard {
     where
         Self: Sized,
     {
+        let mut next = self.clone();
+        next.make_move(move_)?;
+        Some(next)
+    }
+
+    fn get_movable_positions(&self, color: &PlayerColor) -> Vec<Position> {
+        let (player, opponent) = if *color == PlayerColor::Black {
+            (self.black, self.white)
+        } else {
+            (self.white, self.black)
+        };
+
+        let movable = movable_position(player, opponent);
+        data_to_positions(movable)
+    }
+
+    fn make_move(&mut self, move_: &Move) -> Option<MoveUndo> {
         match move_ {
             Move::Pass(color) => {
-                if self.get_movable_positions(&color).is_empty() {
-                    Some(BitBoard::new_from_data(
-                        self.black,
-                        self.white,
-                        self.depth + 1,
-                    ))
-                } else {
-                    None
+                let (player, opponent) = self.player_opponent(color);
+                if movable_position(player, opponent) != 0 {
+                    return None;
                 }
+                self.depth += 1;
+                self.hash ^= zobrist::WHITE_TO_MOVE_KEY;
+                Some(MoveUndo::new(*move_, 0))
             }
             Move::Position(color, position) => {
-                let (player, opponent) = if *color == PlayerColor::Black {
-                    (self.black, self.white)
-                } else {
-                    (self.white, self.black)
-                };
-
-                let movable = movable_position(player, opponent);
-                let pos = position_to_data(&position);
+                let (player, opponent) = self.player_opponent(color);
+                let pos = position_to_data(position);
+                if pos & (player | opponent) != 0 {
+                    return None;
+                }
 
-                if pos & movable == 0 {
+                // ひっくり返せる石がなければ無効な手
+                let flipped = flip_data(player, opponent, pos);
+                if flipped == 0 {
                     return None;
                 }
 
-                let (next_player, next_opponent) = flip(player, opponent, pos);
-                let (next_black, next_white) = if *color == PlayerColor::Black {
-                    (next_player, next_opponent)
-                } else {
-                    (next_opponent, next_player)
-                };
-                Some(BitBoard::new_from_data(
-                    next_black,
-                    next_white,
-                    self.depth + 1,
-                ))
+                self.set_player_opponent(color, player ^ pos ^ flipped, opponent ^ flipped);
+                set_squares(&mut self.squares, pos | flipped, color_to_square(color));
+                self.depth += 1;
+                self.hash ^= move_hash(color, pos, flipped);
+                Some(MoveUndo::new(*move_, flipped))
             }
         }
     }
 
-    fn get_movable_positions(&self, color: &PlayerColor) -> Vec<Position> {
-        let (player, opponent) = if *color == PlayerColor::Black {
-            (self.black, self.white)
+    fn undo_move(&mut self, undo: &MoveUndo) {
+        if let Move::Position(color, position) = &undo.move_ {
+            let (player, opponent) = self.player_opponent(color);
+            let pos = position_to_data(position);
+            self.set_player_opponent(color, player ^ pos ^ undo.flipped, opponent ^ undo.flipped);
+            set_squares(&mut self.squares, pos, Square::Empty);
+            set_squares(
+                &mut self.squares,
+                undo.flipped,
+                color_to_square(&color.opponent()),
+            );
+            self.hash ^= move_hash(color, pos, undo.flipped);
         } else {
-            (self.white, self.black)
-        };
-
-        let movable = movable_position(player, opponent);
-        data_to_positions(movable)
+            self.hash ^= zobrist::WHITE_TO_MOVE_KEY;
+        }
+        self.depth -= 1;
     }
 
     fn square_count(&self, color: Square) -> u32 {
@@ -272,6 +381,10 @@ impl Board for BitBoard {
     fn depth(&self) -> u32 {
         self.depth
     }
+
+    fn hash(&self) -> u64 {
+        self.hash
+    }
 }
 
 #[cfg(test)]
@@ -372,4 +485,113 @@ mod tests {
         assert_eq!(board.white_count(), 2);
         assert_eq!(board.empty_count(), 60);
     }
+
+    #[test]
+    fn test_make_undo_move() {
+        let mut board = BitBoard::new_initial();
+        let moves = [
+            Move::new_position(PlayerColor::Black, Position(4, 5)),
+            Move::new_position(PlayerColor::White, Position(5, 5)),
+            Move::new_position(PlayerColor::Black, Position(5, 4)),
+            Move::new_position(PlayerColor::White, Position(3, 5)),
+            Move::new_position(PlayerColor::Black, Position(2, 4)),
+        ];
+
+        let mut history = Vec::new();
+        for m in &moves {
+            let expected = board.apply_move(m).unwrap();
+            let undo = board.make_move(m).unwrap();
+            assert!(board.squares() == expected.squares());
+            assert_eq!(board.depth(), expected.depth());
+            history.push((undo, expected));
+        }
+
+        let m = Move::new_position(PlayerColor::White, Position(0, 0));
+        assert!(board.make_move(&m).is_none());
+
+        while let Some((undo, expected)) = history.pop() {
+            assert!(board.squares() == expected.squares());
+            board.undo_move(&undo);
+        }
+        assert!(board.squares() == BitBoard::new_initial().squares());
+        assert_eq!(board.depth(), 0);
+    }
+
+    #[test]
+    fn test_make_undo_pass() {
+        // 白は置ける場所がない
+        let mut squares: Squares = [Square::Empty; BOARD_SIZE * BOARD_SIZE];
+        squares[0] = Square::Black;
+        squares[1] = Square::White;
+        squares[2] = Square::White;
+        let mut board = BitBoard::new(&squares, 1);
+
+        assert!(board
+            .make_move(&Move::new_pass(PlayerColor::Black))
+            .is_none());
+
+        let undo = board
+            .make_move(&Move::new_pass(PlayerColor::White))
+            .unwrap();
+        assert_eq!(undo.flipped, 0);
+        assert_eq!(board.depth(), 2);
+        board.undo_move(&undo);
+        assert_eq!(board.depth(), 1);
+        assert!(board.squares() == &squares);
+    }
+
+    #[test]
+    fn test_undo_restores_data() {
+        let mut board = BitBoard::new_initial();
+        let undo = board
+            .make_move(&Move::new_position(PlayerColor::Black, Position(2, 3)))
+            .unwrap();
+        assert_eq!(undo.flipped, position_to_data(&Position(3, 3)));
+        board.undo_move(&undo);
+
+        let initial = BitBoard::new_initial();
+        assert_eq!(board.black, initial.black);
+        assert_eq!(board.white, initial.white);
+    }
+
+    #[test]
+    fn test_transform() {
+        let board = BitBoard::new_initial()
+            .apply_move(&Move::new_position(PlayerColor::Black, Position(2, 3)))
+            .unwrap();
+        for symmetry in Symmetry::ALL {
+            let transformed = board.transform(symmetry);
+            assert!(transformed.squares() == &symmetry.transform_squares(board.squares()));
+            assert_eq!(transformed.depth(), board.depth());
+
+            // 変換した盤面での合法手は元の盤面の合法手を変換したもの
+            let mut expected: Vec<Position> = board
+                .get_movable_positions(&PlayerColor::White)
+                .iter()
+                .map(|p| symmetry.transform_position(p))
+                .collect();
+            expected.sort_by_key(position_to_index);
+            let actual = transformed.get_movable_positions(&PlayerColor::White);
+            assert_eq!(actual, expected);
+        }
+    }
+
+    #[test]
+    fn test_canonical() {
+        // 初手4通りはすべて同じ正規形になる
+        let initial = BitBoard::new_initial();
+        let canonicals: Vec<(u64, u64)> = initial
+            .get_movable_positions(&PlayerColor::Black)
+            .iter()
+            .map(|p| {
+                let m = Move::new_position(PlayerColor::Black, *p);
+                let board = initial.apply_move(&m).unwrap();
+                let (canonical, symmetry) = board.canonical();
+                assert!(canonical.squares() == board.transform(symmetry).squares());
+                (canonical.black(), canonical.white())
+            })
+            .collect();
+        assert_eq!(canonicals.len(), 4);
+        assert!(canonicals.iter().all(|c| *c == canonicals[0]));
+    }
 }
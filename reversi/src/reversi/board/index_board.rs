@@ -1,20 +1,22 @@
 ﻿use crate::board::indexer::FlipInfo;
 use crate::board::indexer::Indexer;
 use crate::board::Board;
+use crate::board::MoveUndo;
+use crate::board::{flipped_mask, undo_squares};
 use crate::*;
-use std::rc::Rc;
+use std::sync::Arc;
 
 /// ボード
 #[derive(Clone)]
 pub struct IndexBoard {
     squares: Squares,
     depth: u32,
-    indexer: Rc<Indexer>,
+    indexer: Arc<Indexer>,
 }
 
 impl IndexBoard {
     /// 新規作成
-    pub fn new_initial(indexer: Rc<Indexer>) -> IndexBoard {
+    pub fn new_initial(indexer: Arc<Indexer>) -> IndexBoard {
         let mut squares: Squares = [Square::Empty; BOARD_SIZE * BOARD_SIZE];
         squares[position_to_index(&Position(3, 4))] = Square::Black;
         squares[position_to_index(&Position(4, 3))] = Square::Black;
@@ -24,7 +26,7 @@ impl IndexBoard {
         IndexBoard::new(squares, 0, indexer)
     }
 
-    pub fn new(squares: Squares, depth: u32, indexer: Rc<Indexer>) -> IndexBoard {
+    pub fn new(squares: Squares, depth: u32, indexer: Arc<Indexer>) -> IndexBoard {
         IndexBoard {
             squares,
             depth,
@@ -124,7 +126,7 @@ impl Board for IndexBoard {
         match move_ {
             Move::Pass(color) => {
                 // パスできるかチェック
-                if self.can_pass(&color) {
+                if self.can_pass(color) {
                     Some(IndexBoard::new(
                         self.squares,
                         self.depth + 1,
@@ -135,7 +137,7 @@ impl Board for IndexBoard {
                 }
             }
             Move::Position(color, position) => {
-                let index = position_to_index(&position);
+                let index = position_to_index(position);
                 if self.squares[index] != Square::Empty {
                     // 空きマス以外には石を置けない
                     return None;
@@ -143,7 +145,7 @@ impl Board for IndexBoard {
 
                 // 各方向の情報取得
                 let (l2r_finfo, t2b_finfo, tl2br_finfo, bl2tr_finfo) =
-                    self.get_flip_infos(&color, &position);
+                    self.get_flip_infos(color, position);
 
                 // ひっくり返す石の数
                 let flip_count = l2r_finfo.flip_count()
@@ -238,6 +240,18 @@ impl Board for IndexBoard {
         positions
     }
 
+    fn make_move(&mut self, move_: &Move) -> Option<MoveUndo> {
+        let next = self.apply_move(move_)?;
+        let flipped = flipped_mask(&self.squares, &next.squares, move_);
+        *self = next;
+        Some(MoveUndo::new(*move_, flipped))
+    }
+
+    fn undo_move(&mut self, undo: &MoveUndo) {
+        undo_squares(&mut self.squares, undo);
+        self.depth -= 1;
+    }
+
     fn square_count(&self, color: Square) -> u32 {
         let mut count = 0;
         for s in &self.squares {
@@ -267,7 +281,7 @@ mod tests {
 
     #[test]
     fn test_get_line() {
-        let indexer = Rc::new(Indexer::new());
+        let indexer = Arc::new(Indexer::new());
         let board = IndexBoard::new_initial(indexer);
 
         let line0 = board.get_line(Position(0, 0), LineDirection::Left2Right);
@@ -305,7 +319,7 @@ mod tests {
 
     #[test]
     fn test_apply_action() {
-        let indexer = Rc::new(Indexer::new());
+        let indexer = Arc::new(Indexer::new());
         let board = IndexBoard::new_initial(indexer);
 
         let m = Move::new_position(PlayerColor::Black, Position(0, 0));
@@ -333,4 +347,47 @@ mod tests {
         assert!(next_board.squares[position_to_index(&Position(3, 3))] == Square::White);
         assert!(next_board.squares[position_to_index(&Position(4, 4))] == Square::White);
     }
+
+    #[test]
+    fn test_make_undo_move() {
+        let indexer = Arc::new(Indexer::new());
+        let mut board = IndexBoard::new_initial(indexer.clone());
+        let moves = [
+            Move::new_position(PlayerColor::Black, Position(4, 5)),
+            Move::new_position(PlayerColor::White, Position(5, 5)),
+            Move::new_position(PlayerColor::Black, Position(5, 4)),
+            Move::new_position(PlayerColor::White, Position(3, 5)),
+            Move::new_position(PlayerColor::Black, Position(2, 4)),
+        ];
+
+        let mut history = Vec::new();
+        for m in &moves {
+            let expected = board.apply_move(m).unwrap();
+            let undo = board.make_move(m).unwrap();
+            assert!(board.squares() == expected.squares());
+            assert_eq!(board.depth(), expected.depth());
+            history.push((undo, expected));
+        }
+
+        let m = Move::new_position(PlayerColor::White, Position(0, 0));
+        assert!(board.make_move(&m).is_none());
+
+        while let Some((undo, expected)) = history.pop() {
+            assert!(board.squares() == expected.squares());
+            board.undo_move(&undo);
+        }
+        assert!(board.squares() == IndexBoard::new_initial(indexer.clone()).squares());
+        assert_eq!(board.depth(), 0);
+    }
+
+    #[test]
+    fn test_share_between_threads() {
+        let board = IndexBoard::new_initial(Arc::new(Indexer::new()));
+        let moves = std::thread::scope(|s| {
+            s.spawn(|| board.get_movable_positions(&PlayerColor::Black))
+                .join()
+                .unwrap()
+        });
+        assert_eq!(moves, board.get_movable_positions(&PlayerColor::Black));
+    }
 }
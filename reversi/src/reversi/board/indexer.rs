@@ -69,11 +69,13 @@ fn index_to_line(index: usize) -> Vec<Square> {
     line
 }
 
-fn line_to_index(line: &[Square]) -> usize {
-    line.iter().enumerate().fold(0, |index, (i, s)| {
-        let num = (*s) as u32;
-        index + 3_u32.pow(i as u32) * num
-    }) as usize
+/// 各マスを3進数の1桁(空き0、黒1、白2)として、先頭のマスを最下位の桁にした番号
+///
+/// 長さは問わないので、評価関数のパターンの番号にも使う。
+pub(crate) fn line_to_index(line: &[Square]) -> usize {
+    line.iter()
+        .rev()
+        .fold(0, |index, s| index * 3 + (*s) as usize)
 }
 
 fn create_mobility_table(color: PlayerColor) -> Vec<MobilityInfo> {
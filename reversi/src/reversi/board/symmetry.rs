@@ -0,0 +1,194 @@
+use crate::*;
+
+/// 上下反転 (r, c) -> (7 - r, c)
+pub const fn flip_vertical(data: u64) -> u64 {
+    data.swap_bytes()
+}
+
+/// 左右反転 (r, c) -> (r, 7 - c)
+pub const fn flip_horizontal(data: u64) -> u64 {
+    const K1: u64 = 0x5555_5555_5555_5555;
+    const K2: u64 = 0x3333_3333_3333_3333;
+    const K4: u64 = 0x0f0f_0f0f_0f0f_0f0f;
+    let mut x = data;
+    x = ((x >> 1) & K1) | ((x & K1) << 1);
+    x = ((x >> 2) & K2) | ((x & K2) << 2);
+    x = ((x >> 4) & K4) | ((x & K4) << 4);
+    x
+}
+
+/// 左上から右下への対角線で反転 (r, c) -> (c, r)
+pub const fn flip_diagonal(data: u64) -> u64 {
+    const K1: u64 = 0x5500_5500_5500_5500;
+    const K2: u64 = 0x3333_0000_3333_0000;
+    const K4: u64 = 0x0f0f_0f0f_0000_0000;
+    let mut x = data;
+    let mut t = K4 & (x ^ (x << 28));
+    x ^= t ^ (t >> 28);
+    t = K2 & (x ^ (x << 14));
+    x ^= t ^ (t >> 14);
+    t = K1 & (x ^ (x << 7));
+    x ^= t ^ (t >> 7);
+    x
+}
+
+/// 右上から左下への対角線で反転 (r, c) -> (7 - c, 7 - r)
+pub const fn flip_anti_diagonal(data: u64) -> u64 {
+    const K1: u64 = 0xaa00_aa00_aa00_aa00;
+    const K2: u64 = 0xcccc_0000_cccc_0000;
+    const K4: u64 = 0xf0f0_f0f0_0f0f_0f0f;
+    let mut x = data;
+    let mut t = x ^ (x << 36);
+    x ^= K4 & (t ^ (x >> 36));
+    t = K2 & (x ^ (x << 18));
+    x ^= t ^ (t >> 18);
+    t = K1 & (x ^ (x << 9));
+    x ^= t ^ (t >> 9);
+    x
+}
+
+/// 時計回りに90度回転 (r, c) -> (c, 7 - r)
+pub const fn rotate90(data: u64) -> u64 {
+    flip_horizontal(flip_diagonal(data))
+}
+
+/// 180度回転 (r, c) -> (7 - r, 7 - c)
+pub const fn rotate180(data: u64) -> u64 {
+    data.reverse_bits()
+}
+
+/// 時計回りに270度回転 (r, c) -> (7 - c, r)
+pub const fn rotate270(data: u64) -> u64 {
+    flip_vertical(flip_diagonal(data))
+}
+
+/// 盤面の対称変換(二面体群D4の8要素)
+#[derive(Clone, Copy, PartialEq, Eq, Hash, Debug)]
+pub enum Symmetry {
+    Identity,
+    Rotate90,
+    Rotate180,
+    Rotate270,
+    FlipVertical,
+    FlipHorizontal,
+    FlipDiagonal,
+    FlipAntiDiagonal,
+}
+
+impl Symmetry {
+    pub const ALL: [Symmetry; 8] = [
+        Symmetry::Identity,
+        Symmetry::Rotate90,
+        Symmetry::Rotate180,
+        Symmetry::Rotate270,
+        Symmetry::FlipVertical,
+        Symmetry::FlipHorizontal,
+        Symmetry::FlipDiagonal,
+        Symmetry::FlipAntiDiagonal,
+    ];
+
+    /// 逆変換
+    pub fn inverse(&self) -> Symmetry {
+        match self {
+            Symmetry::Rotate90 => Symmetry::Rotate270,
+            Symmetry::Rotate270 => Symmetry::Rotate90,
+            _ => *self,
+        }
+    }
+
+    /// ビットボードを変換する
+    pub fn transform_data(&self, data: u64) -> u64 {
+        match self {
+            Symmetry::Identity => data,
+            Symmetry::Rotate90 => rotate90(data),
+            Symmetry::Rotate180 => rotate180(data),
+            Symmetry::Rotate270 => rotate270(data),
+            Symmetry::FlipVertical => flip_vertical(data),
+            Symmetry::FlipHorizontal => flip_horizontal(data),
+            Symmetry::FlipDiagonal => flip_diagonal(data),
+            Symmetry::FlipAntiDiagonal => flip_anti_diagonal(data),
+        }
+    }
+
+    /// 位置を変換する
+    pub fn transform_position(&self, position: &Position) -> Position {
+        let n = BOARD_SIZE - 1;
+        let Position(r, c) = *position;
+        match self {
+            Symmetry::Identity => Position(r, c),
+            Symmetry::Rotate90 => Position(c, n - r),
+            Symmetry::Rotate180 => Position(n - r, n - c),
+            Symmetry::Rotate270 => Position(n - c, r),
+            Symmetry::FlipVertical => Position(n - r, c),
+            Symmetry::FlipHorizontal => Position(r, n - c),
+            Symmetry::FlipDiagonal => Position(c, r),
+            Symmetry::FlipAntiDiagonal => Position(n - c, n - r),
+        }
+    }
+
+    /// 手を変換する(パスはそのまま)
+    pub fn transform_move(&self, move_: &Move) -> Move {
+        match move_ {
+            Move::Position(color, position) => {
+                Move::new_position(*color, self.transform_position(position))
+            }
+            Move::Pass(_) => *move_,
+        }
+    }
+
+    /// 盤面の配列を変換する
+    pub fn transform_squares(&self, squares: &Squares) -> Squares {
+        let mut result = [Square::Empty; BOARD_SIZE * BOARD_SIZE];
+        for (i, s) in squares.iter().enumerate() {
+            let p = self.transform_position(&index_to_position(i));
+            result[position_to_index(&p)] = *s;
+        }
+        result
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn bit(position: &Position) -> u64 {
+        1 << position_to_index(position)
+    }
+
+    #[test]
+    fn test_transform_data_matches_position() {
+        for symmetry in Symmetry::ALL {
+            for i in 0..BOARD_SIZE * BOARD_SIZE {
+                let p = index_to_position(i);
+                let expected = bit(&symmetry.transform_position(&p));
+                assert_eq!(symmetry.transform_data(bit(&p)), expected, "{:?}", symmetry);
+            }
+        }
+    }
+
+    #[test]
+    fn test_inverse() {
+        let data = 0x0123_4567_89ab_cdef;
+        for symmetry in Symmetry::ALL {
+            let transformed = symmetry.transform_data(data);
+            assert_eq!(symmetry.inverse().transform_data(transformed), data);
+
+            let p = Position(1, 6);
+            let q = symmetry.transform_position(&p);
+            assert_eq!(symmetry.inverse().transform_position(&q), p);
+        }
+    }
+
+    #[test]
+    fn test_all_distinct() {
+        // 非対称な配置なら8通りすべて異なる
+        let data = bit(&Position(0, 1)) | bit(&Position(2, 5));
+        let mut results: Vec<u64> = Symmetry::ALL
+            .iter()
+            .map(|s| s.transform_data(data))
+            .collect();
+        results.sort_unstable();
+        results.dedup();
+        assert_eq!(results.len(), 8);
+    }
+}
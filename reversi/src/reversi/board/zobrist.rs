@@ -0,0 +1,138 @@
+use crate::*;
+
+const SQUARE_COUNT: usize = BOARD_SIZE * BOARD_SIZE;
+
+/// 乱数生成(SplitMix64)
+const fn split_mix64(state: u64) -> (u64, u64) {
+    let state = state.wrapping_add(0x9e37_79b9_7f4a_7c15);
+    let mut z = state;
+    z = (z ^ (z >> 30)).wrapping_mul(0xbf58_476d_1ce4_e5b9);
+    z = (z ^ (z >> 27)).wrapping_mul(0x94d0_49bb_1331_11eb);
+    (state, z ^ (z >> 31))
+}
+
+/// マスごとの乱数表
+/// [0]が黒石、[1]が白石
+const fn create_square_keys() -> [[u64; SQUARE_COUNT]; 2] {
+    let mut keys = [[0; SQUARE_COUNT]; 2];
+    let mut state = 0x5265_7665_7273_6921;
+    let mut color = 0;
+    while color < 2 {
+        let mut i = 0;
+        while i < SQUARE_COUNT {
+            let (next_state, key) = split_mix64(state);
+            state = next_state;
+            keys[color][i] = key;
+            i += 1;
+        }
+        color += 1;
+    }
+    keys
+}
+
+const SQUARE_KEYS: [[u64; SQUARE_COUNT]; 2] = create_square_keys();
+
+/// 白番のときに加える乱数
+pub(crate) const WHITE_TO_MOVE_KEY: u64 = split_mix64(0x7475_726e).1;
+
+/// `index`のマスに`color`の石があるときの乱数
+pub(crate) const fn square_key(color: PlayerColor, index: usize) -> u64 {
+    match color {
+        PlayerColor::Black => SQUARE_KEYS[0][index],
+        PlayerColor::White => SQUARE_KEYS[1][index],
+    }
+}
+
+/// `index`のマスの石が裏返ったときにハッシュ値に加える乱数
+pub(crate) const fn flip_key(index: usize) -> u64 {
+    SQUARE_KEYS[0][index] ^ SQUARE_KEYS[1][index]
+}
+
+/// 盤面と手番からZobristハッシュを計算する
+pub fn zobrist_hash(squares: &Squares, turn: PlayerColor) -> u64 {
+    let hash = squares.iter().enumerate().fold(0, |hash, (i, s)| match s {
+        Square::Black => hash ^ square_key(PlayerColor::Black, i),
+        Square::White => hash ^ square_key(PlayerColor::White, i),
+        Square::Empty => hash,
+    });
+    match turn {
+        PlayerColor::Black => hash,
+        PlayerColor::White => hash ^ WHITE_TO_MOVE_KEY,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_keys_are_unique() {
+        let mut keys: Vec<u64> = SQUARE_KEYS.iter().flatten().copied().collect();
+        keys.push(WHITE_TO_MOVE_KEY);
+        let count = keys.len();
+        keys.sort_unstable();
+        keys.dedup();
+        assert_eq!(keys.len(), count);
+        assert!(keys.iter().all(|k| *k != 0));
+    }
+
+    #[test]
+    fn test_zobrist_hash() {
+        let empty: Squares = [Square::Empty; SQUARE_COUNT];
+        assert_eq!(zobrist_hash(&empty, PlayerColor::Black), 0);
+        assert_eq!(zobrist_hash(&empty, PlayerColor::White), WHITE_TO_MOVE_KEY);
+
+        let mut squares = empty;
+        squares[10] = Square::Black;
+        let black = zobrist_hash(&squares, PlayerColor::Black);
+        squares[10] = Square::White;
+        let white = zobrist_hash(&squares, PlayerColor::Black);
+        assert_ne!(black, white);
+        assert_eq!(black ^ white, flip_key(10));
+    }
+
+    #[test]
+    fn test_same_hash_for_all_boards() {
+        use crate::board::{ArrayBoard, BitBoard, Board, IndexBoard, Indexer};
+        use std::sync::Arc;
+
+        let moves = [
+            Move::new_position(PlayerColor::Black, Position(4, 5)),
+            Move::new_position(PlayerColor::White, Position(5, 5)),
+            Move::new_position(PlayerColor::Black, Position(5, 4)),
+            Move::new_position(PlayerColor::White, Position(3, 5)),
+            Move::new_position(PlayerColor::Black, Position(2, 4)),
+            Move::new_position(PlayerColor::White, Position(1, 3)),
+            Move::new_position(PlayerColor::Black, Position(2, 3)),
+            Move::new_position(PlayerColor::White, Position(5, 3)),
+            Move::new_position(PlayerColor::Black, Position(3, 2)),
+            Move::new_position(PlayerColor::White, Position(3, 1)),
+            Move::new_pass(PlayerColor::Black),
+        ];
+
+        let mut array_board = ArrayBoard::new_initial();
+        let mut bit_board = BitBoard::new_initial();
+        let mut index_board = IndexBoard::new_initial(Arc::new(Indexer::new()));
+        let mut hashes = vec![bit_board.hash()];
+        let mut undos = Vec::new();
+        for m in &moves {
+            array_board = array_board.apply_move(m).unwrap();
+            index_board = index_board.apply_move(m).unwrap();
+            undos.push(bit_board.make_move(m).unwrap());
+
+            let expected = zobrist_hash(bit_board.squares(), bit_board.turn());
+            assert_eq!(bit_board.hash(), expected);
+            assert_eq!(array_board.hash(), expected);
+            assert_eq!(index_board.hash(), expected);
+            assert!(!hashes.contains(&expected));
+            hashes.push(expected);
+        }
+
+        // 取り消したときも元のハッシュ値に戻る
+        hashes.pop();
+        while let Some(undo) = undos.pop() {
+            bit_board.undo_move(&undo);
+            assert_eq!(bit_board.hash(), hashes.pop().unwrap());
+        }
+    }
+}
@@ -2,9 +2,13 @@ use crate::{game::GameState, Move};
 
 mod ai_player;
 mod console_io_player;
+mod mcts_player;
+mod puct_player;
 
-pub use ai_player::AiPlayer;
+pub use ai_player::{AiPlayer, SearchEngine};
 pub use console_io_player::ConsoleIoPlayer;
+pub use mcts_player::MctsPlayer;
+pub use puct_player::PuctPlayer;
 
 pub trait Player {
     fn take_action(&self, param: &GameState) -> Move;
@@ -1,22 +1,530 @@
-use crate::ai::SimpleEvaluator;
+use std::cell::RefCell;
+use std::time::Duration;
+
+use crate::ai::{
+    EndgameSolver, Evaluator, EvaluatorNegaAlphaEvaluationFunction,
+    EvaluatorNegaMaxEvaluationFunction, EvaluatorNegaScoutEvaluationFunction, GameOutcome,
+    IterativeDeepening, LazySmp, MoveOrdering, NegaAlpha, NegaAlphaNode, NegaMax, NegaMaxNode,
+    NegaScout, NegaScoutNode, Node, ReplacementPolicy, RootSearch, SearchLimits, SearchPosition,
+    SearchResult, SharedTranspositionTable, SimpleEvaluator, TranspositionTable,
+};
 use crate::board::BitBoard;
 use crate::board::Board;
 use crate::game::GameState;
 use crate::player::Player;
-use crate::Move;
+use crate::{Move, PlayerColor};
+
+/// 探索アルゴリズム
+#[derive(Clone, Copy, PartialEq, Eq, Debug)]
+pub enum SearchEngine {
+    NegaMax,
+    NegaAlpha,
+    NegaScout,
+    /// 置換表付きNegaAlphaの反復深化
+    /// 探索深さは最大の深さとして扱い、制限時間があればその時間内で読めるところまで読む
+    IterativeDeepening,
+    /// 反復深化の各反復をMTD(f)で探索する
+    /// 探索深さと制限時間の扱いは[`SearchEngine::IterativeDeepening`]と同じ
+    Mtdf,
+}
+
+/// 反復深化で使う置換表のサイズ(MB)
+const TRANSPOSITION_TABLE_SIZE_MB: usize = 16;
+
+/// Lazy SMPで探索する関数
+///
+/// 評価関数の複製が必要なので、`E: Clone + Send`のときだけ[`AiPlayer::with_lazy_smp`]で設定する。
+type LazySmpSearch<E> = fn(&E, &BitBoard, PlayerColor, &SearchLimits, usize) -> Option<Move>;
 
-pub struct AiPlayer {
+/// 探索で手を決めるプレイヤー
+///
+/// 評価関数は手番をまたいで保持するので、キャッシュなどを持つ評価関数も使える。
+/// [`AiPlayer::with_lazy_smp`]ではスレッドごとに評価関数を複製する。
+pub struct AiPlayer<E = SimpleEvaluator>
+where
+    E: Evaluator,
+{
     search_depth: usize,
+    engine: SearchEngine,
+    time_limit: Option<Duration>,
+    wld_empties: Option<u32>,
+    tree_search: bool,
+    threads: usize,
+    lazy_smp: Option<LazySmpSearch<E>>,
+    evaluator: RefCell<E>,
 }
 
 impl AiPlayer {
+    /// NegaAlphaと[`SimpleEvaluator`]を使うプレイヤーを作成
     pub fn new(search_depth: usize) -> AiPlayer {
-        AiPlayer { search_depth }
+        AiPlayer::with_engine(search_depth, SearchEngine::NegaAlpha)
+    }
+}
+
+impl<E> AiPlayer<E>
+where
+    E: Evaluator + Default,
+{
+    /// 探索アルゴリズムを指定し、評価関数は既定値で作成
+    pub fn with_engine(search_depth: usize, engine: SearchEngine) -> AiPlayer<E> {
+        AiPlayer::with_evaluator(search_depth, engine, E::default())
+    }
+}
+
+impl<E> AiPlayer<E>
+where
+    E: Evaluator,
+{
+    /// 探索アルゴリズムと評価関数を指定して作成
+    pub fn with_evaluator(search_depth: usize, engine: SearchEngine, evaluator: E) -> AiPlayer<E> {
+        AiPlayer {
+            search_depth,
+            engine,
+            time_limit: None,
+            wld_empties: None,
+            tree_search: false,
+            threads: 1,
+            lazy_smp: None,
+            evaluator: RefCell::new(evaluator),
+        }
+    }
+
+    /// 1手あたりの制限時間を設定する(反復深化する探索のみ有効)
+    pub fn with_time_limit(mut self, time_limit: Duration) -> AiPlayer<E> {
+        self.time_limit = Some(time_limit);
+        self
+    }
+
+    pub fn time_limit(&self) -> Option<Duration> {
+        self.time_limit
+    }
+
+    /// 空きマスが`wld_empties`以下になったら勝敗読みで手を決める(`None`なら勝敗読みしない)
+    ///
+    /// 既定では勝敗読みしない。有効にする場合は14程度が目安。
+    pub fn with_wld_empties(mut self, wld_empties: Option<u32>) -> AiPlayer<E> {
+        self.wld_empties = wld_empties;
+        self
+    }
+
+    pub fn wld_empties(&self) -> Option<u32> {
+        self.wld_empties
+    }
+
+    /// 探索木を作って探索する(デバッグ用)
+    ///
+    /// 反復深化する探索では無効。
+    /// 探索した全ノードを保持するので、深い探索ではメモリを大量に使う。
+    pub fn with_tree_search(mut self, tree_search: bool) -> AiPlayer<E> {
+        self.tree_search = tree_search;
+        self
+    }
+
+    pub fn tree_search(&self) -> bool {
+        self.tree_search
+    }
+
+    pub fn threads(&self) -> usize {
+        self.threads
+    }
+
+    pub fn search_depth(&self) -> usize {
+        self.search_depth
+    }
+
+    pub fn engine(&self) -> SearchEngine {
+        self.engine
+    }
+
+    /// 実際に探索する深さ
+    fn depth(&self) -> usize {
+        // 深さ0では手が決まらないので最低でも1手は読む
+        self.search_depth.max(1)
+    }
+
+    /// 反復深化の制限
+    fn limits(&self) -> SearchLimits {
+        SearchLimits {
+            max_depth: self.depth(),
+            time: self.time_limit,
+            nodes: None,
+        }
+    }
+
+    fn search_tree<N>(
+        &self,
+        mut root: N,
+        search: impl FnOnce(&mut N, usize) -> SearchResult,
+    ) -> Option<Move>
+    where
+        N: Node,
+    {
+        search(&mut root, self.depth()).best_move
+    }
+
+    /// 勝ちか引き分けが確定する手があれば返す
+    fn solve_wld(&self, board: &BitBoard, color: PlayerColor) -> Option<Move> {
+        let wld_empties = self.wld_empties?;
+        if board.empty_count() > wld_empties {
+            return None;
+        }
+        let result = EndgameSolver::new().solve_wld(board, color);
+        match result.outcome {
+            GameOutcome::Win | GameOutcome::Draw => result.best_move,
+            // 負けが確定していても評価関数で粘る手を選ぶ
+            GameOutcome::Loss => None,
+        }
+    }
+}
+
+impl<E> AiPlayer<E>
+where
+    E: Evaluator + Clone + Send,
+{
+    /// 置換表を共有して`threads`スレッドで反復深化するLazy SMPで探索する(最低1)
+    ///
+    /// 探索アルゴリズムは[`SearchEngine::IterativeDeepening`]になり、
+    /// 探索深さと制限時間の扱いも同じ。
+    pub fn with_lazy_smp(mut self, threads: usize) -> AiPlayer<E> {
+        self.engine = SearchEngine::IterativeDeepening;
+        self.threads = threads.max(1);
+        self.lazy_smp = Some(lazy_smp_search::<E>);
+        self
     }
 }
 
-impl Player for AiPlayer {
+fn lazy_smp_search<E>(
+    evaluator: &E,
+    board: &BitBoard,
+    color: PlayerColor,
+    limits: &SearchLimits,
+    threads: usize,
+) -> Option<Move>
+where
+    E: Evaluator + Clone + Send,
+{
+    let table = SharedTranspositionTable::new(
+        TRANSPOSITION_TABLE_SIZE_MB,
+        ReplacementPolicy::DepthPreferred,
+    );
+    let mut search = LazySmp::new(
+        EvaluatorNegaAlphaEvaluationFunction::new(evaluator.clone()),
+        table,
+    )
+    .with_threads(threads);
+    search.search(board, color, limits).best_move
+}
+
+impl<E> Player for AiPlayer<E>
+where
+    E: Evaluator,
+{
     fn take_action(&self, state: &GameState) -> Move {
-        todo!()
+        let color = state.turn;
+        let board = BitBoard::new(&state.board, state.depth);
+        let positions = board.get_movable_positions(&color);
+        if positions.is_empty() {
+            return Move::new_pass(color);
+        }
+
+        if let Some(move_) = self.solve_wld(&board, color) {
+            return move_;
+        }
+
+        let mut evaluator = self.evaluator.borrow_mut();
+        let evaluator = &mut *evaluator;
+        let move_count = state.depth as u8;
+        let last_move = Move::new_pass(color.opponent());
+        let best_move = match self.engine {
+            SearchEngine::NegaMax => {
+                let mut nega_max = NegaMax::new(EvaluatorNegaMaxEvaluationFunction::new(evaluator));
+                if self.tree_search {
+                    let root = NegaMaxNode::new(board, color, move_count, last_move);
+                    self.search_tree(root, |node, depth| nega_max.search(node, depth))
+                } else {
+                    let mut position = SearchPosition::new(board, color);
+                    nega_max
+                        .search_position(&mut position, self.depth())
+                        .best_move
+                }
+            }
+            SearchEngine::NegaAlpha => {
+                let mut nega_alpha =
+                    NegaAlpha::new(EvaluatorNegaAlphaEvaluationFunction::new(evaluator));
+                if self.tree_search {
+                    let root = NegaAlphaNode::new(board, color, move_count, last_move);
+                    self.search_tree(root, |node, depth| nega_alpha.search(node, depth))
+                } else {
+                    let mut position = SearchPosition::new(board, color);
+                    nega_alpha
+                        .search_position(&mut position, self.depth())
+                        .best_move
+                }
+            }
+            SearchEngine::NegaScout => {
+                let mut nega_scout =
+                    NegaScout::new(EvaluatorNegaScoutEvaluationFunction::new(evaluator));
+                if self.tree_search {
+                    let root = NegaScoutNode::new(board, color, move_count, last_move);
+                    self.search_tree(root, |node, depth| nega_scout.search(node, depth))
+                } else {
+                    let mut position = SearchPosition::new(board, color);
+                    nega_scout
+                        .search_position(&mut position, self.depth())
+                        .best_move
+                }
+            }
+            SearchEngine::IterativeDeepening if self.lazy_smp.is_some() => {
+                let search = self.lazy_smp.unwrap();
+                search(evaluator, &board, color, &self.limits(), self.threads)
+            }
+            SearchEngine::IterativeDeepening | SearchEngine::Mtdf => {
+                let root_search = match self.engine {
+                    SearchEngine::Mtdf => RootSearch::Mtdf,
+                    _ => RootSearch::AlphaBeta,
+                };
+                let table = TranspositionTable::new(
+                    TRANSPOSITION_TABLE_SIZE_MB,
+                    ReplacementPolicy::DepthPreferred,
+                );
+                let mut search = IterativeDeepening::new(
+                    NegaAlpha::with_transposition_table(
+                        EvaluatorNegaAlphaEvaluationFunction::new(evaluator),
+                        table,
+                    )
+                    .with_move_ordering(MoveOrdering::all()),
+                )
+                .with_root_search(root_search);
+                search.search(&board, color, &self.limits()).best_move
+            }
+        };
+
+        best_move.unwrap_or_else(|| Move::new_position(color, positions[0]))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::ai::EvalResult;
+    use crate::game::play_game;
+    use crate::{Position, Square, Squares, BOARD_SIZE};
+
+    /// 石数の差だけを見る評価関数
+    #[derive(Clone, Default)]
+    struct CountEvaluator {}
+
+    impl Evaluator for CountEvaluator {
+        fn evaluate(&mut self, board: &Squares, color: &PlayerColor) -> EvalResult {
+            let (player, opponent) = match color {
+                PlayerColor::Black => (Square::Black, Square::White),
+                PlayerColor::White => (Square::White, Square::Black),
+            };
+            let value = board.iter().fold(0, |v, s| {
+                if *s == player {
+                    v + 1
+                } else if *s == opponent {
+                    v - 1
+                } else {
+                    v
+                }
+            });
+            EvalResult {
+                value,
+                policy: None,
+            }
+        }
+    }
+
+    #[test]
+    fn test_take_action_returns_legal_move() {
+        let board = BitBoard::new_initial();
+        let state = GameState::new(&board);
+        for engine in [
+            SearchEngine::NegaMax,
+            SearchEngine::NegaAlpha,
+            SearchEngine::NegaScout,
+            SearchEngine::IterativeDeepening,
+            SearchEngine::Mtdf,
+        ] {
+            let player = AiPlayer::<SimpleEvaluator>::with_engine(3, engine);
+            let move_ = player.take_action(&state);
+            assert!(board.apply_move(&move_).is_some());
+        }
+        let player = AiPlayer::<SimpleEvaluator>::new(3).with_lazy_smp(2);
+        let move_ = player.take_action(&state);
+        assert!(board.apply_move(&move_).is_some());
+    }
+
+    #[test]
+    fn test_take_action_pass() {
+        // 白は置ける場所がない
+        let mut squares: Squares = [Square::Empty; BOARD_SIZE * BOARD_SIZE];
+        squares[0] = Square::Black;
+        squares[1] = Square::White;
+        squares[2] = Square::White;
+        let board = BitBoard::new(&squares, 1);
+        let state = GameState::new(&board);
+        assert_eq!(state.turn, PlayerColor::White);
+
+        let player = AiPlayer::new(3);
+        assert_eq!(
+            player.take_action(&state),
+            Move::new_pass(PlayerColor::White)
+        );
+    }
+
+    #[test]
+    fn test_take_action_best_move() {
+        // 黒はa1に置くと1枚、d1に置くと3枚返せる
+        let mut squares: Squares = [Square::Empty; BOARD_SIZE * BOARD_SIZE];
+        squares[1] = Square::White;
+        squares[2] = Square::Black;
+        squares[4] = Square::White;
+        squares[5] = Square::White;
+        squares[6] = Square::White;
+        squares[7] = Square::Black;
+        let board = BitBoard::new(&squares, 0);
+        let state = GameState::new(&board);
+
+        for engine in [
+            SearchEngine::NegaMax,
+            SearchEngine::NegaAlpha,
+            SearchEngine::NegaScout,
+            SearchEngine::IterativeDeepening,
+            SearchEngine::Mtdf,
+        ] {
+            let player = AiPlayer::<CountEvaluator>::with_engine(1, engine);
+            assert_eq!(
+                player.take_action(&state),
+                Move::new_position(PlayerColor::Black, Position(0, 3))
+            );
+        }
+        let player =
+            AiPlayer::<CountEvaluator>::with_engine(1, SearchEngine::NegaAlpha).with_lazy_smp(2);
+        assert_eq!(
+            player.take_action(&state),
+            Move::new_position(PlayerColor::Black, Position(0, 3))
+        );
+    }
+
+    #[test]
+    fn test_tree_search() {
+        // 探索木を作っても作らなくても同じ手を選ぶ
+        let board = BitBoard::new_initial()
+            .apply_move(&Move::new_position(PlayerColor::Black, Position(2, 3)))
+            .unwrap();
+        let state = GameState::new(&board);
+        for engine in [
+            SearchEngine::NegaMax,
+            SearchEngine::NegaAlpha,
+            SearchEngine::NegaScout,
+        ] {
+            let player = AiPlayer::<SimpleEvaluator>::with_engine(3, engine);
+            let tree_player =
+                AiPlayer::<SimpleEvaluator>::with_engine(3, engine).with_tree_search(true);
+            assert!(!player.tree_search());
+            assert_eq!(player.take_action(&state), tree_player.take_action(&state));
+        }
+    }
+
+    #[test]
+    fn test_play_game_between_ai_players() {
+        let board = BitBoard::new_initial();
+        let black = Box::new(AiPlayer::new(2));
+        let white = Box::new(AiPlayer::<SimpleEvaluator>::with_engine(
+            2,
+            SearchEngine::NegaMax,
+        ));
+        let result = play_game(&board, black, white);
+
+        assert!(result.state.is_end);
+        assert_eq!(result.game_record.len(), result.history.len());
+    }
+
+    #[test]
+    fn test_time_limit() {
+        let board = BitBoard::new_initial();
+        let state = GameState::new(&board);
+        let player = AiPlayer::<SimpleEvaluator>::with_engine(64, SearchEngine::IterativeDeepening)
+            .with_time_limit(Duration::from_millis(50));
+        let start = std::time::Instant::now();
+        let move_ = player.take_action(&state);
+        assert!(start.elapsed() < Duration::from_secs(5));
+        assert!(board.apply_move(&move_).is_some());
+    }
+
+    /// 呼び出し回数を数える評価関数(複製できない)
+    #[derive(Default)]
+    struct CallCountEvaluator {
+        calls: usize,
+    }
+
+    impl Evaluator for CallCountEvaluator {
+        fn evaluate(&mut self, board: &Squares, color: &PlayerColor) -> EvalResult {
+            self.calls += 1;
+            CountEvaluator {}.evaluate(board, color)
+        }
+    }
+
+    #[test]
+    fn test_evaluator_without_clone() {
+        let board = BitBoard::new_initial();
+        let state = GameState::new(&board);
+        let player: Box<dyn Player> = Box::new(AiPlayer::<CallCountEvaluator>::with_engine(
+            2,
+            SearchEngine::IterativeDeepening,
+        ));
+        let move_ = player.take_action(&state);
+        assert!(board.apply_move(&move_).is_some());
+
+        let player = AiPlayer::<CallCountEvaluator>::with_engine(2, SearchEngine::NegaAlpha);
+        player.take_action(&state);
+        assert!(player.evaluator.borrow().calls > 0);
+    }
+
+    #[test]
+    fn test_threads() {
+        let board = BitBoard::new_initial();
+        let state = GameState::new(&board);
+        let player = AiPlayer::<SimpleEvaluator>::with_engine(64, SearchEngine::NegaAlpha)
+            .with_lazy_smp(4)
+            .with_time_limit(Duration::from_millis(50));
+        assert_eq!(player.threads(), 4);
+        assert_eq!(player.engine(), SearchEngine::IterativeDeepening);
+        let move_ = player.take_action(&state);
+        assert!(board.apply_move(&move_).is_some());
+    }
+
+    #[test]
+    fn test_take_action_wld() {
+        // 終盤まで進めた局面で、勝ちが確定しているなら勝ちを保つ手を選ぶ
+        let mut board = BitBoard::new_initial();
+        while board.empty_count() > 10 {
+            let color = board.turn();
+            let positions = board.get_movable_positions(&color);
+            let m = match positions.last() {
+                Some(p) => Move::new_position(color, *p),
+                None => Move::new_pass(color),
+            };
+            board = board.apply_move(&m).unwrap();
+        }
+        let state = GameState::new(&board);
+        let color = state.turn;
+
+        let player = AiPlayer::<CountEvaluator>::with_engine(1, SearchEngine::NegaAlpha);
+        assert_eq!(player.wld_empties(), None);
+        let player = player.with_wld_empties(Some(10));
+        let move_ = player.take_action(&state);
+        let next = board.apply_move(&move_).unwrap();
+
+        let mut solver = EndgameSolver::new();
+        let expected = solver.solve_wld(&board, color).outcome;
+        let score = -solver.solve(&next, color.opponent()).score;
+        match expected {
+            GameOutcome::Win => assert!(score > 0),
+            GameOutcome::Draw => assert_eq!(score, 0),
+            GameOutcome::Loss => assert!(score < 0),
+        }
     }
 }
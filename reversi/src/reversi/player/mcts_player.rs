@@ -0,0 +1,127 @@
+use std::time::Duration;
+
+use crate::ai::{Mcts, MctsLimits, Playout, DEFAULT_EXPLORATION};
+use crate::board::BitBoard;
+use crate::board::Board;
+use crate::game::GameState;
+use crate::player::Player;
+use crate::Move;
+
+/// モンテカルロ木探索で手を決めるプレイヤー
+///
+/// 乱数の種は局面ごとに`seed`と盤面のハッシュ値から決めるので、同じ局面では同じ手を選ぶ。
+pub struct MctsPlayer {
+    limits: MctsLimits,
+    playout: Playout,
+    exploration: f64,
+    seed: u64,
+}
+
+impl MctsPlayer {
+    /// 1手あたり`iterations`回のプレイアウトで手を決める
+    pub fn new(iterations: u64, playout: Playout) -> MctsPlayer {
+        MctsPlayer::with_limits(MctsLimits::iterations(iterations), playout)
+    }
+
+    pub fn with_limits(limits: MctsLimits, playout: Playout) -> MctsPlayer {
+        MctsPlayer {
+            limits,
+            playout,
+            exploration: DEFAULT_EXPLORATION,
+            seed: 0,
+        }
+    }
+
+    /// 1手あたりの制限時間を設定する
+    pub fn with_time_limit(mut self, time_limit: Duration) -> MctsPlayer {
+        self.limits.time = Some(time_limit);
+        self
+    }
+
+    /// UCTの探索項の係数を設定する
+    pub fn with_exploration(mut self, exploration: f64) -> MctsPlayer {
+        self.exploration = exploration;
+        self
+    }
+
+    pub fn with_seed(mut self, seed: u64) -> MctsPlayer {
+        self.seed = seed;
+        self
+    }
+
+    pub fn limits(&self) -> &MctsLimits {
+        &self.limits
+    }
+
+    pub fn playout(&self) -> Playout {
+        self.playout
+    }
+}
+
+impl Player for MctsPlayer {
+    fn take_action(&self, state: &GameState) -> Move {
+        let color = state.turn;
+        let board = BitBoard::new(&state.board, state.depth);
+        let positions = board.get_movable_positions(&color);
+        if positions.is_empty() {
+            return Move::new_pass(color);
+        }
+
+        let mut mcts =
+            Mcts::new(self.playout, self.seed ^ board.hash()).with_exploration(self.exploration);
+        mcts.search(&board, color, &self.limits)
+            .best_move
+            .unwrap_or_else(|| Move::new_position(color, positions[0]))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::game::play_game;
+    use crate::player::AiPlayer;
+    use crate::PlayerColor;
+
+    #[test]
+    fn test_take_action_returns_legal_move() {
+        let board = BitBoard::new_initial();
+        let state = GameState::new(&board);
+        for playout in [Playout::Random, Playout::Heuristic] {
+            let player = MctsPlayer::new(200, playout);
+            let move_ = player.take_action(&state);
+            assert!(board.apply_move(&move_).is_some());
+            // 同じ局面では同じ手を選ぶ
+            assert_eq!(player.take_action(&state), move_);
+        }
+    }
+
+    #[test]
+    fn test_time_limit() {
+        let board = BitBoard::new_initial();
+        let state = GameState::new(&board);
+        let player = MctsPlayer::with_limits(
+            MctsLimits::time(Duration::from_millis(50)),
+            Playout::Heuristic,
+        );
+        let start = std::time::Instant::now();
+        let move_ = player.take_action(&state);
+        assert!(start.elapsed() < Duration::from_secs(5));
+        assert!(board.apply_move(&move_).is_some());
+    }
+
+    #[test]
+    fn test_play_against_nega_alpha() {
+        let board = BitBoard::new_initial();
+        let black = Box::new(MctsPlayer::new(100, Playout::Random).with_seed(1));
+        // AiPlayer::newはNegaAlphaで探索する
+        let white = Box::new(AiPlayer::new(2));
+        let result = play_game(&board, black, white);
+
+        assert!(result.state.is_end);
+        assert_eq!(result.game_record.len(), result.history.len());
+        assert!(matches!(
+            result.game_record[0],
+            Move::Position(PlayerColor::Black, _)
+        ));
+    }
+}
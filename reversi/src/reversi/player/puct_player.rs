@@ -0,0 +1,115 @@
+use std::cell::RefCell;
+
+use crate::ai::{Evaluator, MctsLimits, Puct, PuctConfig};
+use crate::board::BitBoard;
+use crate::board::Board;
+use crate::game::GameState;
+use crate::player::Player;
+use crate::Move;
+
+/// PUCT探索で手を決めるプレイヤー
+///
+/// 自分の手を指した後の探索木を残し、次の手番で相手の手の後の部分木を再利用する。
+pub struct PuctPlayer<E>
+where
+    E: Evaluator,
+{
+    limits: MctsLimits,
+    puct: RefCell<Puct<E>>,
+}
+
+impl<E> PuctPlayer<E>
+where
+    E: Evaluator,
+{
+    pub fn new(evaluator: E, limits: MctsLimits, config: PuctConfig) -> PuctPlayer<E> {
+        PuctPlayer::with_seed(evaluator, limits, config, 0)
+    }
+
+    /// 乱数の種を指定して作成(ノイズと温度による手の選択に使う)
+    pub fn with_seed(
+        evaluator: E,
+        limits: MctsLimits,
+        config: PuctConfig,
+        seed: u64,
+    ) -> PuctPlayer<E> {
+        PuctPlayer {
+            limits,
+            puct: RefCell::new(Puct::new(evaluator, config, seed)),
+        }
+    }
+
+    pub fn limits(&self) -> &MctsLimits {
+        &self.limits
+    }
+
+    pub fn config(&self) -> PuctConfig {
+        *self.puct.borrow().config()
+    }
+
+    /// 保持している探索木のノード数
+    pub fn tree_size(&self) -> usize {
+        self.puct.borrow().tree_size()
+    }
+}
+
+impl<E> Player for PuctPlayer<E>
+where
+    E: Evaluator,
+{
+    fn take_action(&self, state: &GameState) -> Move {
+        let color = state.turn;
+        let board = BitBoard::new(&state.board, state.depth);
+        let positions = board.get_movable_positions(&color);
+        if positions.is_empty() {
+            return Move::new_pass(color);
+        }
+
+        let mut puct = self.puct.borrow_mut();
+        let result = puct.search(&board, color, &self.limits);
+        let move_ = puct
+            .select_move(&result, state.depth)
+            .unwrap_or_else(|| Move::new_position(color, positions[0]));
+        puct.advance(&move_);
+        move_
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::ai::SimpleEvaluator;
+    use crate::game::play_game;
+    use crate::player::AiPlayer;
+
+    #[test]
+    fn test_take_action_keeps_tree() {
+        let board = BitBoard::new_initial();
+        let state = GameState::new(&board);
+        let player = PuctPlayer::new(
+            SimpleEvaluator::new(),
+            MctsLimits::iterations(200),
+            PuctConfig::default(),
+        );
+        let move_ = player.take_action(&state);
+        assert!(board.apply_move(&move_).is_some());
+        // 指した手の後の部分木が残っている
+        assert!(player.tree_size() > 1);
+    }
+
+    #[test]
+    fn test_play_against_nega_alpha() {
+        let board = BitBoard::new_initial();
+        let black = Box::new(PuctPlayer::with_seed(
+            SimpleEvaluator::new(),
+            MctsLimits::iterations(100),
+            PuctConfig::self_play(),
+            1,
+        ));
+        let white = Box::new(AiPlayer::new(2));
+        let result = play_game(&board, black, white);
+
+        assert!(result.state.is_end);
+        assert_eq!(result.game_record.len(), result.history.len());
+    }
+}